@@ -0,0 +1,327 @@
+//! Derive macros for mapping Rust types to AMF classes with flash-lso
+//!
+//! `#[derive(AmfObject)]` implements `AmfClass` for a sealed or dynamic class and
+//! `#[derive(AmfExternalizable)]` implements `AmfClass` and `AmfExternalizable` for a class that uses `IExternalizable`
+//!
+//! Supported attributes:
+//! - `#[amf(class = "com.example.Name")]` on the struct sets the class alias, by default this is the struct name
+//! - `#[amf(dynamic)]` on the struct marks the class as dynamic
+//! - `#[amf(rename = "name")]` on a field sets the property name, by default this is the field name
+//! - `#[amf(skip)]` on a field excludes it, it is set to `Default::default()` when reading
+//! - `#[amf(dynamic)]` on a field of a `DynamicProperties` type collects all undeclared properties, this implies `#[amf(dynamic)]` on the struct
+//! - `#[amf(external = "i32")]` on a field of an externalizable class sets how the field is written, one of
+//!   `u8`, `i8`, `bool`, `u16`, `i16`, `u32`, `i32`, `f32`, `f64`, `utf` or `amf3`.
+//!   By default this is inferred from primitive field types, `String` uses `utf` and everything else uses `amf3`
+
+#![deny(
+    anonymous_parameters,
+    nonstandard_style,
+    rust_2018_idioms,
+    trivial_casts,
+    trivial_numeric_casts,
+    unreachable_pub,
+    unused_extern_crates,
+    unused_qualifications,
+    missing_docs,
+    unsafe_code,
+    clippy::unwrap_used
+)]
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, Ident, LitStr, Type, parse_macro_input};
+
+/// Derive `AmfClass` for a sealed or dynamic class
+#[proc_macro_derive(AmfObject, attributes(amf))]
+pub fn derive_amf_object(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, false)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `AmfClass` and `AmfExternalizable` for a class that is read and written in field order
+#[proc_macro_derive(AmfExternalizable, attributes(amf))]
+pub fn derive_amf_externalizable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, true)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Options given on the struct
+#[derive(Default)]
+struct ClassOptions {
+    class: Option<String>,
+    dynamic: bool,
+}
+
+/// Options given on a field
+#[derive(Default)]
+struct FieldOptions {
+    rename: Option<String>,
+    skip: bool,
+    dynamic: bool,
+    external: Option<LitStr>,
+}
+
+/// A field that is read and written as a property
+struct Property<'f> {
+    ident: &'f Ident,
+    name: String,
+    ty: &'f Type,
+    external: Option<LitStr>,
+}
+
+fn parse_class_options(input: &DeriveInput) -> syn::Result<ClassOptions> {
+    let mut options = ClassOptions::default();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("amf")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("class") {
+                options.class = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("dynamic") {
+                options.dynamic = true;
+            } else {
+                return Err(meta.error("unknown amf attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+fn parse_field_options(field: &syn::Field) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("amf")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                options.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("skip") {
+                options.skip = true;
+            } else if meta.path.is_ident("dynamic") {
+                options.dynamic = true;
+            } else if meta.path.is_ident("external") {
+                options.external = Some(meta.value()?.parse::<LitStr>()?);
+            } else {
+                return Err(meta.error("unknown amf attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+/// Get the `ExternalKind` variant used to read and write a field
+fn external_kind(property: &Property<'_>) -> syn::Result<Ident> {
+    let kind = match &property.external {
+        Some(kind) => kind.value(),
+        None => match property.ty {
+            Type::Path(p) if p.qself.is_none() && p.path.segments.len() == 1 => {
+                match p.path.segments[0].ident.to_string().as_str() {
+                    "String" => "utf".to_string(),
+                    s @ ("u8" | "i8" | "bool" | "u16" | "i16" | "u32" | "i32" | "f32" | "f64") => {
+                        s.to_string()
+                    }
+                    _ => "amf3".to_string(),
+                }
+            }
+            _ => "amf3".to_string(),
+        },
+    };
+
+    let variant = match kind.as_str() {
+        "u8" => "U8",
+        "i8" => "I8",
+        "bool" => "Bool",
+        "u16" => "U16",
+        "i16" => "I16",
+        "u32" => "U32",
+        "i32" => "I32",
+        "f32" => "F32",
+        "f64" => "F64",
+        "utf" => "Utf",
+        "amf3" => "Amf3",
+        _ => {
+            let span = property
+                .external
+                .as_ref()
+                .map_or_else(Span::call_site, LitStr::span);
+            return Err(syn::Error::new(span, "unknown external field kind"));
+        }
+    };
+    Ok(Ident::new(variant, Span::call_site()))
+}
+
+fn expand(input: &DeriveInput, externalizable: bool) -> syn::Result<TokenStream2> {
+    let options = parse_class_options(input)?;
+
+    let fields = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(f) => &f.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    input,
+                    "only structs with named fields are supported",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "only structs with named fields are supported",
+            ));
+        }
+    };
+
+    let mut properties = Vec::new();
+    let mut skipped = Vec::new();
+    let mut dynamic_field = None;
+    for field in fields {
+        let field_options = parse_field_options(field)?;
+        let ident = field.ident.as_ref().ok_or_else(|| {
+            syn::Error::new_spanned(field, "only structs with named fields are supported")
+        })?;
+
+        if field_options.skip {
+            skipped.push(ident);
+        } else if field_options.dynamic {
+            if externalizable {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "externalizable classes can't have dynamic properties",
+                ));
+            }
+            if dynamic_field.replace(ident).is_some() {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "only one field can hold dynamic properties",
+                ));
+            }
+        } else {
+            properties.push(Property {
+                ident,
+                name: field_options
+                    .rename
+                    .unwrap_or_else(|| ident.to_string().trim_start_matches("r#").to_string()),
+                ty: &field.ty,
+                external: field_options.external,
+            });
+        }
+    }
+
+    if externalizable && options.dynamic {
+        return Err(syn::Error::new_spanned(
+            input,
+            "externalizable classes can't be dynamic",
+        ));
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let alias = options.class.unwrap_or_else(|| name.to_string());
+
+    let attributes = if externalizable {
+        quote!(::flash_lso::types::Attribute::External.into())
+    } else if options.dynamic || dynamic_field.is_some() {
+        quote!(::flash_lso::types::Attribute::Dynamic.into())
+    } else {
+        quote!(::flash_lso::alias::__private::EnumSet::empty())
+    };
+
+    let idents: Vec<_> = properties.iter().map(|p| p.ident).collect();
+    let names: Vec<_> = properties.iter().map(|p| p.name.as_str()).collect();
+
+    // Externalizable classes have no sealed properties, their data is written by `writeExternal`
+    let static_properties = if externalizable {
+        quote!(::flash_lso::alias::__private::Vec::new())
+    } else {
+        quote!(
+            ::flash_lso::alias::__private::vec![#(::flash_lso::alias::__private::String::from(#names)),*]
+        )
+    };
+
+    let (dynamic_to, dynamic_from) = match dynamic_field {
+        Some(field) => (
+            quote!(elements.extend(::flash_lso::alias::DynamicProperties::to_elements(&self.#field));),
+            quote!(#field: ::flash_lso::alias::DynamicProperties::from_elements(
+                elements.iter().filter(|e| ![#(#names),*].contains(&e.name.as_str()))
+            )?,),
+        ),
+        None => (quote!(), quote!()),
+    };
+
+    let mut out = quote! {
+        impl #impl_generics ::flash_lso::alias::AmfClass for #name #ty_generics #where_clause {
+            const ALIAS: &'static str = #alias;
+
+            fn attributes() -> ::flash_lso::alias::__private::EnumSet<::flash_lso::types::Attribute> {
+                #attributes
+            }
+
+            fn static_properties() -> ::flash_lso::alias::__private::Vec<::flash_lso::alias::__private::String> {
+                #static_properties
+            }
+
+            fn to_elements(&self) -> ::flash_lso::alias::__private::Vec<::flash_lso::types::Element> {
+                #[allow(unused_mut)]
+                let mut elements = ::flash_lso::alias::__private::vec![
+                    #(::flash_lso::types::Element::new(#names, ::flash_lso::alias::ToAmf::to_amf(&self.#idents))),*
+                ];
+                #dynamic_to
+                elements
+            }
+
+            fn from_elements(
+                elements: &[::flash_lso::types::Element],
+            ) -> ::core::result::Result<Self, ::flash_lso::errors::ConvertError> {
+                ::core::result::Result::Ok(Self {
+                    #(#idents: ::flash_lso::alias::property(elements, #names)?,)*
+                    #dynamic_from
+                    #(#skipped: ::core::default::Default::default(),)*
+                })
+            }
+        }
+    };
+
+    if externalizable {
+        let kinds = properties
+            .iter()
+            .map(external_kind)
+            .collect::<syn::Result<Vec<_>>>()?;
+
+        out.extend(quote! {
+            impl #impl_generics ::flash_lso::alias::AmfExternalizable for #name #ty_generics #where_clause {
+                #[allow(unused_variables)]
+                fn read_external<'a>(
+                    ctx: &mut ::flash_lso::amf3::decode_context::DecodeContext<'a, '_>,
+                ) -> ::core::result::Result<::flash_lso::alias::__private::Vec<::flash_lso::types::Element>, ::flash_lso::errors::Error<'a>> {
+                    ::core::result::Result::Ok(::flash_lso::alias::__private::vec![
+                        #(::flash_lso::types::Element::new(
+                            #names,
+                            ::flash_lso::alias::read_external_field(ctx, ::flash_lso::alias::ExternalKind::#kinds)?,
+                        )),*
+                    ])
+                }
+
+                #[allow(unused_variables)]
+                fn write_external(
+                    writer: &mut dyn ::flash_lso::write::Sink,
+                    elements: &[::flash_lso::types::Element],
+                    encoder: &mut ::flash_lso::amf3::write::AMF3Encoder,
+                ) -> ::core::result::Result<(), ::flash_lso::errors::EncodeError> {
+                    #(::flash_lso::alias::write_external_field(
+                        writer,
+                        encoder,
+                        ::flash_lso::alias::ExternalKind::#kinds,
+                        elements,
+                        #names,
+                    )?;)*
+                    ::core::result::Result::Ok(())
+                }
+            }
+        });
+    }
+
+    Ok(out)
+}
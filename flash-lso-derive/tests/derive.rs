@@ -0,0 +1,151 @@
+use flash_lso::alias::{AmfClass, TypedElement, TypedLso, TypedValue};
+use flash_lso::read::Reader;
+use flash_lso::types::{AMFVersion, Attribute, ClassDefinition, Element, Header, Value};
+use flash_lso::write::Writer;
+use flash_lso_derive::{AmfExternalizable, AmfObject};
+use std::collections::BTreeMap;
+
+#[derive(AmfObject, Debug, PartialEq)]
+#[amf(class = "com.game.PlayerState")]
+struct PlayerState {
+    name: String,
+    #[amf(rename = "lvl")]
+    level: i32,
+    guild: Option<String>,
+    #[amf(skip)]
+    cached: bool,
+}
+
+#[derive(AmfObject, Debug, PartialEq)]
+#[amf(class = "com.game.Settings")]
+struct Settings {
+    volume: f64,
+    #[amf(dynamic)]
+    extra: BTreeMap<String, Value>,
+}
+
+#[derive(AmfExternalizable, Debug, PartialEq)]
+#[amf(class = "com.game.Position")]
+struct Position {
+    x: i16,
+    y: i16,
+    #[amf(external = "u32")]
+    layer: u32,
+    label: String,
+    tags: Vec<String>,
+}
+
+fn roundtrip(lso: &TypedLso) -> TypedLso {
+    let mut writer = Writer::default();
+    writer.class_registry.register::<PlayerState>();
+    writer.class_registry.register::<Settings>();
+    writer.class_registry.register::<Position>();
+    writer.amf3_encoder.register_externalizable::<Position>();
+    let mut bytes = Vec::new();
+    writer
+        .write_typed(&mut bytes, lso)
+        .expect("Failed to write");
+
+    let mut reader = Reader::default();
+    reader.class_registry.register::<PlayerState>();
+    reader.class_registry.register::<Settings>();
+    reader.class_registry.register::<Position>();
+    reader.amf3_decoder.register_externalizable::<Position>();
+    reader.parse_typed(&bytes).expect("Failed to read")
+}
+
+#[test]
+fn test_derive_class_definitions() {
+    assert_eq!(
+        PlayerState::class_definition(),
+        ClassDefinition {
+            name: "com.game.PlayerState".to_string(),
+            attributes: Default::default(),
+            static_properties: vec!["name".to_string(), "lvl".to_string(), "guild".to_string()],
+        }
+    );
+    assert_eq!(Settings::attributes(), Attribute::Dynamic);
+    assert_eq!(Position::attributes(), Attribute::External);
+    assert!(Position::static_properties().is_empty());
+}
+
+#[test]
+fn test_derive_roundtrip() {
+    let lso = TypedLso {
+        header: Header::new("save", AMFVersion::AMF3),
+        body: vec![
+            TypedElement::new(
+                "player",
+                TypedValue::new(PlayerState {
+                    name: "cub3d".to_string(),
+                    level: 12,
+                    guild: Some("ruffle".to_string()),
+                    cached: true,
+                }),
+            ),
+            TypedElement::new(
+                "settings",
+                TypedValue::new(Settings {
+                    volume: 0.5,
+                    extra: BTreeMap::from([("muted".to_string(), Value::Bool(false))]),
+                }),
+            ),
+            TypedElement::new(
+                "position",
+                TypedValue::new(Position {
+                    x: -3,
+                    y: 4,
+                    layer: 3_000_000_000,
+                    label: "spawn".to_string(),
+                    tags: vec!["a".to_string(), "b".to_string()],
+                }),
+            ),
+        ],
+    };
+
+    let read = roundtrip(&lso);
+    assert_eq!(
+        read.body[0].value.downcast_ref::<PlayerState>(),
+        Some(&PlayerState {
+            name: "cub3d".to_string(),
+            level: 12,
+            guild: Some("ruffle".to_string()),
+            cached: false,
+        })
+    );
+    assert_eq!(
+        read.body[1].value.downcast_ref::<Settings>(),
+        lso.body[1].value.downcast_ref::<Settings>()
+    );
+    assert_eq!(
+        read.body[2].value.downcast_ref::<Position>(),
+        lso.body[2].value.downcast_ref::<Position>()
+    );
+}
+
+#[test]
+fn test_derive_externalizable_layout() {
+    use flash_lso::alias::AmfExternalizable;
+    use flash_lso::amf3::write::AMF3Encoder;
+
+    let elements = Position {
+        x: 1,
+        y: 2,
+        layer: 3,
+        label: "hi".to_string(),
+        tags: Vec::new(),
+    }
+    .to_elements();
+
+    let mut bytes = Vec::new();
+    Position::write_external(&mut bytes, &elements, &mut AMF3Encoder::default())
+        .expect("Failed to write");
+    assert_eq!(
+        bytes,
+        [0, 1, 0, 2, 0, 0, 0, 3, 0, 2, b'h', b'i', 0x09, 0x01, 0x01]
+    );
+
+    // Missing dynamic properties are not an error, missing sealed ones are
+    assert!(Settings::from_elements(&[Element::new("volume", Value::Number(1.0))]).is_ok());
+    assert!(PlayerState::from_elements(&[]).is_err());
+}
@@ -1,10 +1,7 @@
-#![feature(test)]
-
-extern crate test;
-
 use flash_lso::read::Reader;
+use std::hint::black_box;
 
-use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use criterion::{Criterion, criterion_group, criterion_main};
 
 criterion_group!(benches, criterion_benchmark);
 criterion_main!(benches);
@@ -19,6 +16,12 @@ macro_rules! auto_bench {
                             black_box(Reader::default().parse(input_bytes).unwrap());
                         })
                     });
+                    c.bench_function(concat!("parse_borrowed_", $path), |b| {
+                        let input_bytes = include_bytes!(concat!("../tests/sol/", $path, ".sol"));
+                        b.iter(|| {
+                            black_box(Reader::default().parse_borrowed(input_bytes).unwrap());
+                        })
+                    });
                 )*
             }
         }
@@ -0,0 +1,83 @@
+use crate::alias::{FromAmf, ToAmf};
+use crate::errors::ConvertError;
+use crate::prelude::*;
+use crate::types::{
+    Attribute, ClassDefinition, CustomObject, Element, ObjectId, ObjectValue, Value,
+};
+use enumset::EnumSet;
+
+/// A Rust type that is read and written as an AMF typed object, the equivalent of `registerClassAlias`
+///
+/// Sealed and dynamic classes are represented as `Value::Object`, externalizable classes as `Value::Custom`.
+/// Externalizable classes also need a custom encoder and decoder registered for their alias, which produce the
+/// elements given to `from_elements` and taken from `to_elements`
+pub trait AmfClass: Sized + 'static {
+    /// The class alias, for example `com.game.PlayerState`
+    const ALIAS: &'static str;
+
+    /// The attributes of this class, sealed by default
+    fn attributes() -> EnumSet<Attribute> {
+        EnumSet::empty()
+    }
+
+    /// The names of the sealed properties of this class, in the order they are written
+    fn static_properties() -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Get the class definition that is written for this class
+    fn class_definition() -> ClassDefinition {
+        ClassDefinition {
+            name: Self::ALIAS.to_string(),
+            attributes: Self::attributes(),
+            static_properties: Self::static_properties(),
+        }
+    }
+
+    /// Convert this value into the elements of an object
+    fn to_elements(&self) -> Vec<Element>;
+
+    /// Build this value from the elements of an object
+    fn from_elements(elements: &[Element]) -> Result<Self, ConvertError>;
+}
+
+impl<T: AmfClass> FromAmf for T {
+    fn from_amf(value: &Value) -> Result<Self, ConvertError> {
+        let (elements, def) = match value {
+            Value::Object { data, .. } => (&data.elements, data.class_definition.as_ref()),
+            Value::Custom(custom) => (&custom.custom_elements, custom.class_definition.as_ref()),
+            _ => return Err(ConvertError::UnexpectedType("object")),
+        };
+
+        let name = def.map_or("Object", |def| def.name.as_str());
+        if name != T::ALIAS {
+            return Err(ConvertError::UnexpectedClass {
+                expected: T::ALIAS.to_string(),
+                found: name.to_string(),
+            });
+        }
+
+        T::from_elements(elements)
+    }
+}
+
+impl<T: AmfClass> ToAmf for T {
+    fn to_amf(&self) -> Value {
+        let def = T::class_definition();
+        if def.attributes.contains(Attribute::External) {
+            Value::Custom(CustomObject {
+                custom_elements: self.to_elements(),
+                elements: Vec::new(),
+                class_definition: Some(def),
+            })
+        } else {
+            Value::Object {
+                id: ObjectId::INVALID,
+                data: ObjectValue {
+                    elements: self.to_elements(),
+                    class_definition: Some(def),
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::errors::ConvertError;
+use crate::prelude::*;
+use crate::types::{Element, ObjectId, Value};
+
+/// Conversion of a `Value` into a Rust type
+pub trait FromAmf: Sized {
+    /// Convert the given value into this type
+    fn from_amf(value: &Value) -> Result<Self, ConvertError>;
+
+    /// Called when a property of this type is not present on an object
+    ///
+    /// By default this is an error, optional types can override this to provide a default
+    fn from_missing(name: &str) -> Result<Self, ConvertError> {
+        Err(ConvertError::MissingProperty(name.to_string()))
+    }
+}
+
+/// Conversion of a Rust type into a `Value`
+pub trait ToAmf {
+    /// Convert this type into a value
+    fn to_amf(&self) -> Value;
+}
+
+/// Read the property with the given name from a list of elements
+pub fn property<T: FromAmf>(elements: &[Element], name: &str) -> Result<T, ConvertError> {
+    match elements.iter().find(|e| e.name == name) {
+        Some(e) => T::from_amf(&e.value),
+        None => T::from_missing(name),
+    }
+}
+
+/// Convert a number to an integer type, failing if it is not a whole number in range
+fn integral<T: TryFrom<i64>>(n: f64) -> Result<T, ConvertError> {
+    // Casting saturates, so values that are fractional or out of range won't convert back to the same number
+    let i = n as i64;
+    if i as f64 != n {
+        return Err(ConvertError::OutOfRange);
+    }
+    T::try_from(i).map_err(|_| ConvertError::OutOfRange)
+}
+
+impl FromAmf for Value {
+    fn from_amf(value: &Value) -> Result<Self, ConvertError> {
+        Ok(value.clone())
+    }
+}
+
+impl ToAmf for Value {
+    fn to_amf(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl FromAmf for f64 {
+    fn from_amf(value: &Value) -> Result<Self, ConvertError> {
+        match value {
+            Value::Number(n) => Ok(*n),
+            Value::Integer(i) => Ok(f64::from(*i)),
+            _ => Err(ConvertError::UnexpectedType("number")),
+        }
+    }
+}
+
+impl ToAmf for f64 {
+    fn to_amf(&self) -> Value {
+        Value::Number(*self)
+    }
+}
+
+impl FromAmf for i32 {
+    fn from_amf(value: &Value) -> Result<Self, ConvertError> {
+        match value {
+            Value::Integer(i) => Ok(*i),
+            Value::Number(n) => integral(*n),
+            _ => Err(ConvertError::UnexpectedType("integer")),
+        }
+    }
+}
+
+impl ToAmf for i32 {
+    fn to_amf(&self) -> Value {
+        Value::Integer(*self)
+    }
+}
+
+impl FromAmf for u32 {
+    fn from_amf(value: &Value) -> Result<Self, ConvertError> {
+        match value {
+            Value::Integer(i) => u32::try_from(*i).map_err(|_| ConvertError::OutOfRange),
+            Value::Number(n) => integral(*n),
+            _ => Err(ConvertError::UnexpectedType("integer")),
+        }
+    }
+}
+
+impl ToAmf for u32 {
+    fn to_amf(&self) -> Value {
+        Value::Number(f64::from(*self))
+    }
+}
+
+/// Implement the conversion traits for integer types that always fit in an AMF3 integer
+macro_rules! small_integer {
+    ($($t:ty),*) => {
+        $(
+            impl FromAmf for $t {
+                fn from_amf(value: &Value) -> Result<Self, ConvertError> {
+                    match value {
+                        Value::Integer(i) => <$t>::try_from(*i).map_err(|_| ConvertError::OutOfRange),
+                        Value::Number(n) => integral(*n),
+                        _ => Err(ConvertError::UnexpectedType("integer")),
+                    }
+                }
+            }
+
+            impl ToAmf for $t {
+                fn to_amf(&self) -> Value {
+                    Value::Integer(i32::from(*self))
+                }
+            }
+        )*
+    };
+}
+
+small_integer!(u8, i8, u16, i16);
+
+impl FromAmf for f32 {
+    fn from_amf(value: &Value) -> Result<Self, ConvertError> {
+        f64::from_amf(value).map(|n| n as f32)
+    }
+}
+
+impl ToAmf for f32 {
+    fn to_amf(&self) -> Value {
+        Value::Number(f64::from(*self))
+    }
+}
+
+impl FromAmf for bool {
+    fn from_amf(value: &Value) -> Result<Self, ConvertError> {
+        match value {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(ConvertError::UnexpectedType("bool")),
+        }
+    }
+}
+
+impl ToAmf for bool {
+    fn to_amf(&self) -> Value {
+        Value::Bool(*self)
+    }
+}
+
+impl FromAmf for String {
+    fn from_amf(value: &Value) -> Result<Self, ConvertError> {
+        match value {
+            Value::String(s) => Ok(s.clone()),
+            _ => Err(ConvertError::UnexpectedType("string")),
+        }
+    }
+}
+
+impl ToAmf for String {
+    fn to_amf(&self) -> Value {
+        Value::String(self.clone())
+    }
+}
+
+impl ToAmf for str {
+    fn to_amf(&self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl<T: FromAmf> FromAmf for Option<T> {
+    fn from_amf(value: &Value) -> Result<Self, ConvertError> {
+        match value {
+            Value::Null | Value::Undefined => Ok(None),
+            v => T::from_amf(v).map(Some),
+        }
+    }
+
+    fn from_missing(_name: &str) -> Result<Self, ConvertError> {
+        Ok(None)
+    }
+}
+
+impl<T: ToAmf> ToAmf for Option<T> {
+    fn to_amf(&self) -> Value {
+        match self {
+            Some(v) => v.to_amf(),
+            None => Value::Null,
+        }
+    }
+}
+
+impl<T: FromAmf> FromAmf for Vec<T> {
+    fn from_amf(value: &Value) -> Result<Self, ConvertError> {
+        let values = match value {
+            Value::StrictArray(_, values) => values,
+            Value::VectorObject(vector) => &vector.items,
+            Value::ECMAArray(array) if array.assoc.is_empty() => &array.dense,
+            _ => return Err(ConvertError::UnexpectedType("array")),
+        };
+        values.iter().map(T::from_amf).collect()
+    }
+}
+
+impl<T: ToAmf> ToAmf for Vec<T> {
+    fn to_amf(&self) -> Value {
+        Value::StrictArray(ObjectId::INVALID, self.iter().map(T::to_amf).collect())
+    }
+}
@@ -0,0 +1,62 @@
+use crate::alias::{FromAmf, ToAmf};
+use crate::errors::ConvertError;
+use crate::prelude::*;
+use crate::types::Element;
+use alloc::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+/// A collection holding the dynamic properties of an object, those not declared by its class
+pub trait DynamicProperties: Sized {
+    /// Build the collection from the dynamic properties of an object
+    fn from_elements<'e>(elements: impl Iterator<Item = &'e Element>)
+    -> Result<Self, ConvertError>;
+
+    /// Convert the collection into dynamic properties
+    fn to_elements(&self) -> Vec<Element>;
+}
+
+impl DynamicProperties for Vec<Element> {
+    fn from_elements<'e>(
+        elements: impl Iterator<Item = &'e Element>,
+    ) -> Result<Self, ConvertError> {
+        Ok(elements.cloned().collect())
+    }
+
+    fn to_elements(&self) -> Vec<Element> {
+        self.clone()
+    }
+}
+
+impl<V: FromAmf + ToAmf> DynamicProperties for BTreeMap<String, V> {
+    fn from_elements<'e>(
+        elements: impl Iterator<Item = &'e Element>,
+    ) -> Result<Self, ConvertError> {
+        elements
+            .map(|e| Ok((e.name.clone(), V::from_amf(&e.value)?)))
+            .collect()
+    }
+
+    fn to_elements(&self) -> Vec<Element> {
+        self.iter()
+            .map(|(k, v)| Element::new(k.clone(), v.to_amf()))
+            .collect()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V: FromAmf + ToAmf> DynamicProperties for HashMap<String, V> {
+    fn from_elements<'e>(
+        elements: impl Iterator<Item = &'e Element>,
+    ) -> Result<Self, ConvertError> {
+        elements
+            .map(|e| Ok((e.name.clone(), V::from_amf(&e.value)?)))
+            .collect()
+    }
+
+    fn to_elements(&self) -> Vec<Element> {
+        self.iter()
+            .map(|(k, v)| Element::new(k.clone(), v.to_amf()))
+            .collect()
+    }
+}
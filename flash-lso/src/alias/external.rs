@@ -0,0 +1,134 @@
+use crate::alias::{AmfClass, FromAmf};
+use crate::amf3::custom_encoder::{CustomEncoder, ExternalizableDecoder};
+use crate::amf3::decode_context::DecodeContext;
+use crate::amf3::write::AMF3Encoder;
+use crate::errors::{ConvertError, EncodeError, Error};
+use crate::prelude::*;
+use crate::types::{ClassDefinition, Element, Value};
+use crate::write::Sink;
+use core::marker::PhantomData;
+
+/// An `AmfClass` that uses `IExternalizable` to read and write its data
+///
+/// The elements produced by `read_external` and consumed by `write_external` are the ones passed to
+/// `AmfClass::from_elements` and returned by `AmfClass::to_elements`
+pub trait AmfExternalizable: AmfClass {
+    /// Read the externalized data, the equivalent of `readExternal`
+    fn read_external<'a>(ctx: &mut DecodeContext<'a, '_>) -> Result<Vec<Element>, Error<'a>>;
+
+    /// Write the externalized data, the equivalent of `writeExternal`
+    fn write_external(
+        writer: &mut dyn Sink,
+        elements: &[Element],
+        encoder: &mut AMF3Encoder,
+    ) -> Result<(), EncodeError>;
+}
+
+/// How a single field of an externalizable class is written by `writeExternal`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ExternalKind {
+    /// `writeByte` of an unsigned value
+    U8,
+    /// `writeByte`
+    I8,
+    /// `writeBoolean`
+    Bool,
+    /// `writeShort` of an unsigned value
+    U16,
+    /// `writeShort`
+    I16,
+    /// `writeUnsignedInt`
+    U32,
+    /// `writeInt`
+    I32,
+    /// `writeFloat`
+    F32,
+    /// `writeDouble`
+    F64,
+    /// `writeUTF`
+    Utf,
+    /// `writeObject` with AMF3 encoding
+    Amf3,
+}
+
+/// Read a single field of an externalizable class
+pub fn read_external_field<'a>(
+    ctx: &mut DecodeContext<'a, '_>,
+    kind: ExternalKind,
+) -> Result<Value, Error<'a>> {
+    Ok(match kind {
+        ExternalKind::U8 => Value::Integer(i32::from(ctx.read_u8()?)),
+        ExternalKind::I8 => Value::Integer(i32::from(ctx.read_i8()?)),
+        ExternalKind::Bool => Value::Bool(ctx.read_bool()?),
+        ExternalKind::U16 => Value::Integer(i32::from(ctx.read_u16_be()?)),
+        ExternalKind::I16 => Value::Integer(i32::from(ctx.read_i16_be()?)),
+        ExternalKind::U32 => Value::Number(f64::from(ctx.read_u32_be()?)),
+        ExternalKind::I32 => Value::Integer(ctx.read_i32_be()?),
+        ExternalKind::F32 => Value::Number(f64::from(ctx.read_f32_be()?)),
+        ExternalKind::F64 => Value::Number(ctx.read_f64_be()?),
+        ExternalKind::Utf => Value::String(ctx.read_utf()?),
+        ExternalKind::Amf3 => ctx.read_amf3_value()?,
+    })
+}
+
+/// Write the field with the given name of an externalizable class
+pub fn write_external_field(
+    writer: &mut dyn Sink,
+    encoder: &mut AMF3Encoder,
+    kind: ExternalKind,
+    elements: &[Element],
+    name: &str,
+) -> Result<(), EncodeError> {
+    let value = elements
+        .iter()
+        .find(|e| e.name == name)
+        .map(|e| &e.value)
+        .ok_or_else(|| EncodeError::MissingElement(name.to_string()))?;
+
+    match kind {
+        ExternalKind::U8 => writer.write_all(&u8::from_amf(value)?.to_be_bytes())?,
+        ExternalKind::I8 => writer.write_all(&i8::from_amf(value)?.to_be_bytes())?,
+        ExternalKind::Bool => writer.write_all(&[u8::from(bool::from_amf(value)?)])?,
+        ExternalKind::U16 => writer.write_all(&u16::from_amf(value)?.to_be_bytes())?,
+        ExternalKind::I16 => writer.write_all(&i16::from_amf(value)?.to_be_bytes())?,
+        ExternalKind::U32 => writer.write_all(&u32::from_amf(value)?.to_be_bytes())?,
+        ExternalKind::I32 => writer.write_all(&i32::from_amf(value)?.to_be_bytes())?,
+        ExternalKind::F32 => writer.write_all(&f32::from_amf(value)?.to_be_bytes())?,
+        ExternalKind::F64 => writer.write_all(&f64::from_amf(value)?.to_be_bytes())?,
+        ExternalKind::Utf => {
+            let s = String::from_amf(value)?;
+            let length = u16::try_from(s.len()).map_err(|_| ConvertError::OutOfRange)?;
+            writer.write_all(&length.to_be_bytes())?;
+            writer.write_all(s.as_bytes())?;
+        }
+        ExternalKind::Amf3 => encoder.write_value_element(writer, value)?,
+    }
+    Ok(())
+}
+
+/// Adapts an `AmfExternalizable` into a custom encoder and decoder that can be registered for its alias
+pub struct ExternalizableCodec<T>(PhantomData<fn() -> T>);
+
+impl<T> Default for ExternalizableCodec<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: AmfExternalizable> ExternalizableDecoder for ExternalizableCodec<T> {
+    fn decode<'a>(&self, ctx: &mut DecodeContext<'a, '_>) -> Result<Vec<Element>, Error<'a>> {
+        T::read_external(ctx)
+    }
+}
+
+impl<T: AmfExternalizable> CustomEncoder for ExternalizableCodec<T> {
+    fn encode(
+        &self,
+        writer: &mut dyn Sink,
+        elements: &[Element],
+        _class_def: &Option<ClassDefinition>,
+        encoder: &mut AMF3Encoder,
+    ) -> Result<(), EncodeError> {
+        T::write_external(writer, elements, encoder)
+    }
+}
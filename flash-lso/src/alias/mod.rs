@@ -0,0 +1,27 @@
+mod class;
+mod convert;
+mod dynamic;
+#[cfg(feature = "amf3")]
+mod external;
+mod registry;
+
+pub use class::AmfClass;
+pub use convert::{FromAmf, ToAmf, property};
+pub use dynamic::DynamicProperties;
+#[cfg(feature = "amf3")]
+pub use external::{
+    AmfExternalizable, ExternalKind, ExternalizableCodec, read_external_field, write_external_field,
+};
+pub use registry::{ClassRegistry, TypedElement, TypedLso, TypedValue};
+
+#[cfg(feature = "derive")]
+pub use flash_lso_derive::{AmfExternalizable, AmfObject};
+
+/// Re-exports used by the code generated by `flash-lso-derive`, not public API
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::string::String;
+    pub use alloc::vec;
+    pub use alloc::vec::Vec;
+    pub use enumset::EnumSet;
+}
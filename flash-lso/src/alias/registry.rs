@@ -0,0 +1,173 @@
+use crate::alias::{AmfClass, FromAmf, ToAmf};
+use crate::errors::{ConvertError, EncodeError};
+use crate::prelude::*;
+use crate::types::{Element, Header, Lso, Value};
+use alloc::collections::BTreeMap;
+use core::any::{Any, TypeId};
+
+type DecodeFn = fn(&Value) -> Result<Box<dyn Any>, ConvertError>;
+type EncodeFn = fn(&dyn Any) -> Option<Value>;
+
+/// A registry mapping class aliases to the Rust types that represent them
+#[derive(Default, Clone)]
+pub struct ClassRegistry {
+    /// Constructors for each registered type, by class alias
+    decoders: BTreeMap<String, DecodeFn>,
+
+    /// Converters back to `Value` for each registered type
+    encoders: BTreeMap<TypeId, EncodeFn>,
+}
+
+impl ClassRegistry {
+    /// Register a type, objects with a class name of `T::ALIAS` will be converted to `T`
+    pub fn register<T: AmfClass>(&mut self) {
+        self.decoders.insert(T::ALIAS.to_string(), |v| {
+            let t: Box<dyn Any> = Box::new(T::from_amf(v)?);
+            Ok(t)
+        });
+        self.encoders
+            .insert(TypeId::of::<T>(), |v| v.downcast_ref::<T>().map(T::to_amf));
+    }
+
+    /// Check if a type is registered for the given class alias
+    pub fn is_registered(&self, alias: &str) -> bool {
+        self.decoders.contains_key(alias)
+    }
+
+    /// Convert a value into its registered type, values without a registered class are returned unchanged
+    pub fn decode(&self, value: Value) -> Result<TypedValue, ConvertError> {
+        let name = match &value {
+            Value::Object { data, .. } => data.class_definition.as_ref().map(|def| &def.name),
+            Value::Custom(custom) => custom.class_definition.as_ref().map(|def| &def.name),
+            _ => None,
+        };
+
+        match name.and_then(|name| self.decoders.get(name)) {
+            Some(decode) => decode(&value).map(TypedValue::Typed),
+            None => Ok(TypedValue::Value(value)),
+        }
+    }
+
+    /// Convert a typed value back into a `Value`
+    pub fn encode(&self, value: &TypedValue) -> Result<Value, EncodeError> {
+        match value {
+            TypedValue::Typed(t) => self
+                .encoders
+                .get(&(**t).type_id())
+                .and_then(|encode| encode(t.as_ref()))
+                .ok_or(EncodeError::UnregisteredType),
+            TypedValue::Value(v) => Ok(v.clone()),
+        }
+    }
+
+    /// Convert the root level values of an lso into their registered types
+    ///
+    /// Objects nested in other values are left as `Value`, as a `Value` can not hold a typed instance, unless they
+    /// are read through the `FromAmf` of a registered class as one of its properties
+    pub fn decode_lso(&self, lso: Lso) -> Result<TypedLso, ConvertError> {
+        let body = lso
+            .body
+            .into_iter()
+            .map(|e| {
+                Ok(TypedElement {
+                    name: e.name,
+                    value: self.decode(e.value)?,
+                })
+            })
+            .collect::<Result<_, ConvertError>>()?;
+        Ok(TypedLso {
+            header: lso.header,
+            body,
+        })
+    }
+
+    /// Convert a typed lso back into an lso of plain values
+    pub fn encode_lso(&self, lso: &TypedLso) -> Result<Lso, EncodeError> {
+        let body = lso
+            .body
+            .iter()
+            .map(|e| Ok(Element::new(e.name.clone(), self.encode(&e.value)?)))
+            .collect::<Result<_, EncodeError>>()?;
+        Ok(Lso {
+            header: lso.header.clone(),
+            body,
+        })
+    }
+}
+
+/// A value that is either an instance of a registered class or a plain `Value`
+#[derive(Debug)]
+pub enum TypedValue {
+    /// An instance of a registered class
+    Typed(Box<dyn Any>),
+
+    /// A value with no registered class
+    Value(Value),
+}
+
+impl TypedValue {
+    /// Create a typed value from an instance of a class
+    pub fn new<T: AmfClass>(value: T) -> Self {
+        Self::Typed(Box::new(value))
+    }
+
+    /// Get a reference to the instance of `T`, if this is one
+    pub fn downcast_ref<T: AmfClass>(&self) -> Option<&T> {
+        match self {
+            Self::Typed(t) => t.downcast_ref(),
+            Self::Value(_) => None,
+        }
+    }
+
+    /// Take the instance of `T`, returning self if this is not one
+    pub fn downcast<T: AmfClass>(self) -> Result<T, Self> {
+        match self {
+            Self::Typed(t) => t.downcast().map(|t| *t).map_err(Self::Typed),
+            v => Err(v),
+        }
+    }
+
+    /// Get the plain value, if this is not an instance of a registered class
+    pub fn as_value(&self) -> Option<&Value> {
+        match self {
+            Self::Typed(_) => None,
+            Self::Value(v) => Some(v),
+        }
+    }
+}
+
+impl From<Value> for TypedValue {
+    fn from(value: Value) -> Self {
+        Self::Value(value)
+    }
+}
+
+/// A named `TypedValue`
+#[derive(Debug)]
+pub struct TypedElement {
+    /// The name of the element
+    pub name: String,
+
+    /// The value of the element
+    pub value: TypedValue,
+}
+
+impl TypedElement {
+    /// Create a new TypedElement
+    pub fn new(name: impl Into<String>, value: impl Into<TypedValue>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
+
+/// An lso where the root level values may be instances of registered classes
+#[derive(Debug)]
+pub struct TypedLso {
+    /// The header of this lso
+    pub header: Header,
+
+    /// The elements at the root level of this lso
+    pub body: Vec<TypedElement>,
+}
@@ -0,0 +1,254 @@
+use crate::PADDING;
+use crate::amf0::read::{AMF0Decoder, read_type_marker};
+use crate::amf0::type_marker::TypeMarker;
+#[cfg(feature = "amf3")]
+use crate::amf3;
+use crate::encoding::StringEncoding;
+use crate::errors::Error;
+use crate::nom_utils::AMFResult;
+use crate::prelude::*;
+use crate::types::{
+    ClassDefinitionRef, DecodeLimits, DuplicateKeys, ElementRef, ObjectId, ObjectValueRef,
+    Reference, ValueRef,
+};
+use alloc::borrow::Cow;
+use core::convert::TryInto;
+use enumset::EnumSet;
+use nom::Err;
+use nom::Parser;
+use nom::bytes::complete::{tag, take};
+use nom::error::{ErrorKind, make_error};
+use nom::multi::{many_m_n, many0};
+use nom::number::complete::{be_f64, be_u8, be_u16, be_u32};
+
+/// Decodes AMF0 into `ValueRef`s that borrow from the input
+pub(crate) struct BorrowedDecoder<'a> {
+    encoding: StringEncoding,
+    duplicate_keys: DuplicateKeys,
+    limits: DecodeLimits,
+
+    /// How deeply the value being parsed is nested
+    depth: usize,
+
+    #[cfg(feature = "amf3")]
+    amf3_decoder: amf3::borrowed::BorrowedDecoder<'a>,
+
+    #[cfg(feature = "amf3")]
+    unwrap_amf3: bool,
+
+    #[cfg(not(feature = "amf3"))]
+    _input: core::marker::PhantomData<&'a [u8]>,
+}
+
+impl<'a> BorrowedDecoder<'a> {
+    /// Create a decoder with the configuration of the given decoder
+    pub(crate) fn new(decoder: &AMF0Decoder) -> Self {
+        Self {
+            encoding: decoder.string_encoding(),
+            duplicate_keys: decoder.duplicate_keys(),
+            limits: decoder.limits(),
+            depth: 0,
+            #[cfg(feature = "amf3")]
+            amf3_decoder: amf3::borrowed::BorrowedDecoder::new(decoder.amf3_decoder()),
+            #[cfg(feature = "amf3")]
+            unwrap_amf3: decoder.unwrap_amf3,
+            #[cfg(not(feature = "amf3"))]
+            _input: core::marker::PhantomData,
+        }
+    }
+
+    fn take_string(&self, i: &'a [u8], length: usize) -> AMFResult<'a, Cow<'a, str>> {
+        let (j, bytes) = take(length)(i)?;
+        let s = self
+            .encoding
+            .decode(bytes)
+            .ok_or_else(|| Err::Error(make_error(i, ErrorKind::MapRes)))?;
+        Ok((j, s))
+    }
+
+    fn parse_string(&self, i: &'a [u8]) -> AMFResult<'a, Cow<'a, str>> {
+        let (i, length) = be_u16(i)?;
+        self.take_string(i, length as usize)
+    }
+
+    fn parse_long_string(&self, i: &'a [u8]) -> AMFResult<'a, Cow<'a, str>> {
+        let (i, length) = be_u32(i)?;
+        self.take_string(i, length as usize)
+    }
+
+    fn parse_element_strict_array(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        let (i, length) = be_u32(i)?;
+
+        let length_usize = length
+            .try_into()
+            .map_err(|_| Err::Error(make_error(i, ErrorKind::Digit)))?;
+
+        self.limits.check_length(length_usize)?;
+
+        // There must be at least `length_usize` bytes (u8) to read this, this prevents OOM errors with v.large arrays
+        if i.len() < length_usize {
+            return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
+        }
+
+        let (i, values) = many_m_n(length_usize, length_usize, |i| self.parse_value(i)).parse(i)?;
+
+        Ok((i, ValueRef::StrictArray(ObjectId::INVALID, values)))
+    }
+
+    fn parse_array_element(&mut self, i: &'a [u8]) -> AMFResult<'a, Vec<ElementRef<'a>>> {
+        let mut out = Vec::new();
+
+        let mut i = i;
+        loop {
+            let (k, length) = be_u16(i)?;
+            let (k, _) = take(length)(k)?;
+            let (k, next_type) = read_type_marker(k)?;
+            if next_type == TypeMarker::ObjectEnd {
+                i = k;
+                break;
+            }
+
+            let (j, e) = self.parse_element(i)?;
+            i = j;
+
+            self.limits.check_length(out.len() + 1)?;
+            out.push(e);
+        }
+
+        self.duplicate_keys.apply(&mut out, |e| &e.name)?;
+        Ok((i, out))
+    }
+
+    fn parse_element_amf3(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        #[cfg(feature = "amf3")]
+        {
+            self.amf3_decoder.depth = self.depth;
+            let (i, x) = self.amf3_decoder.parse_value(i)?;
+            if self.unwrap_amf3 {
+                Ok((i, x))
+            } else {
+                Ok((i, ValueRef::AMF3(Box::new(x))))
+            }
+        }
+        #[cfg(not(feature = "amf3"))]
+        {
+            Ok((i, ValueRef::Unsupported))
+        }
+    }
+
+    /// Parse a single AMF0 value
+    fn parse_value(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        self.limits.check_depth(self.depth + 1)?;
+        self.depth += 1;
+        let res = self.parse_marker_value(i);
+        self.depth -= 1;
+        res
+    }
+
+    fn parse_marker_value(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        let (i, type_) = read_type_marker(i)?;
+
+        match type_ {
+            TypeMarker::Number => {
+                let (i, n) = be_f64(i)?;
+                Ok((i, ValueRef::Number(n)))
+            }
+            TypeMarker::Boolean => {
+                let (i, b) = be_u8(i)?;
+                Ok((i, ValueRef::Bool(b > 0)))
+            }
+            TypeMarker::String => {
+                let (i, s) = self.parse_string(i)?;
+                Ok((i, ValueRef::String(s)))
+            }
+            TypeMarker::Object => {
+                let (i, elements) = self.parse_array_element(i)?;
+                Ok((
+                    i,
+                    ValueRef::Object {
+                        id: ObjectId::INVALID,
+                        data: ObjectValueRef {
+                            elements,
+                            class_definition: None,
+                        },
+                    },
+                ))
+            }
+            TypeMarker::Null => Ok((i, ValueRef::Null)),
+            TypeMarker::Undefined => Ok((i, ValueRef::Undefined)),
+            TypeMarker::Reference => {
+                let (i, index) = be_u16(i)?;
+                Ok((i, ValueRef::Reference(Reference(index))))
+            }
+            TypeMarker::ECMAArray => {
+                let (i, array_length) = be_u32(i)?;
+                let (i, elements) = self.parse_array_element(i)?;
+                Ok((
+                    i,
+                    ValueRef::ECMAArray {
+                        id: ObjectId::INVALID,
+                        dense: Vec::new(),
+                        assoc: elements,
+                        declared_len: array_length,
+                    },
+                ))
+            }
+            TypeMarker::StrictArray => self.parse_element_strict_array(i),
+            TypeMarker::Date => {
+                let (i, millis) = be_f64(i)?;
+                let (i, time_zone) = be_u16(i)?;
+                Ok((i, ValueRef::Date(millis, Some(time_zone))))
+            }
+            TypeMarker::LongString => {
+                let (i, s) = self.parse_long_string(i)?;
+                Ok((i, ValueRef::String(s)))
+            }
+            TypeMarker::Unsupported => Ok((i, ValueRef::Unsupported)),
+            TypeMarker::Xml => {
+                let (i, content) = self.parse_long_string(i)?;
+                Ok((i, ValueRef::XML(content, true)))
+            }
+            TypeMarker::TypedObject => {
+                let (i, name) = self.parse_string(i)?;
+                let (i, elements) = self.parse_array_element(i)?;
+                Ok((
+                    i,
+                    ValueRef::Object {
+                        id: ObjectId::INVALID,
+                        data: ObjectValueRef {
+                            elements,
+                            class_definition: Some(ClassDefinitionRef {
+                                name,
+                                attributes: EnumSet::empty(),
+                                static_properties: Vec::new(),
+                            }),
+                        },
+                    },
+                ))
+            }
+            TypeMarker::AMF3 => self.parse_element_amf3(i),
+            TypeMarker::MovieClip | TypeMarker::RecordSet | TypeMarker::ObjectEnd => {
+                Err(Err::Error(Error::UnsupportedType(type_ as u8)))
+            }
+        }
+    }
+
+    fn parse_element(&mut self, i: &'a [u8]) -> AMFResult<'a, ElementRef<'a>> {
+        let (i, name) = self.parse_string(i)?;
+        let (i, value) = self.parse_value(i)?;
+        Ok((i, ElementRef { name, value }))
+    }
+
+    fn parse_element_and_padding(&mut self, i: &'a [u8]) -> AMFResult<'a, ElementRef<'a>> {
+        let (i, e) = self.parse_element(i)?;
+        let (i, _) = tag(PADDING.as_slice())(i)?;
+        Ok((i, e))
+    }
+
+    /// Parse a sequence of `PADDING` delimited elements
+    pub(crate) fn parse_body(&mut self, i: &'a [u8]) -> AMFResult<'a, Vec<ElementRef<'a>>> {
+        let (i, mut body) = many0(|i| self.parse_element_and_padding(i)).parse(i)?;
+        self.duplicate_keys.apply(&mut body, |e| &e.name)?;
+        Ok((i, body))
+    }
+}
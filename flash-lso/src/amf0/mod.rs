@@ -1,3 +1,6 @@
+/// Decoding AMF0 into values that borrow from the input
+pub(crate) mod borrowed;
+
 /// Support for reading AMF0 data
 pub mod read;
 
@@ -1,25 +1,46 @@
 //! Support for decoding AMF0 data
 use crate::amf0::type_marker::TypeMarker;
+use crate::prelude::*;
 use nom::Parser;
 
 use crate::PADDING;
 #[cfg(feature = "amf3")]
 use crate::amf3;
+#[cfg(feature = "amf3")]
+use crate::amf3::read::TableState;
+use crate::encoding::StringEncoding;
 use crate::nom_utils::{AMFResult, take_str};
-use crate::types::{ClassDefinition, Element, ObjectId, ObjectValue, Reference, Value};
+use crate::types::{
+    ClassDefinition, DecodeLimits, DuplicateKeys, EcmaArray, Element, ObjectId, ObjectValue,
+    Reference, Value,
+};
+use core::convert::{TryFrom, TryInto};
 use nom::Err;
 use nom::bytes::complete::{tag, take};
-use nom::combinator::{map, map_res};
+use nom::combinator::map;
 use nom::error::{ErrorKind, make_error};
 use nom::multi::{many_m_n, many0};
 use nom::number::complete::{be_f64, be_u8, be_u16, be_u32};
-use std::convert::{TryFrom, TryInto};
 
 pub(crate) fn parse_string(i: &[u8]) -> AMFResult<'_, &str> {
     let (i, length) = be_u16(i)?;
     take_str(i, length)
 }
 
+/// Parse a string with a u16 length, in the given encoding
+pub(crate) fn parse_encoded_string(i: &[u8], encoding: StringEncoding) -> AMFResult<'_, String> {
+    let (i, length) = be_u16(i)?;
+    take_string(i, length as usize, encoding)
+}
+
+fn take_string(i: &[u8], length: usize, encoding: StringEncoding) -> AMFResult<'_, String> {
+    let (j, bytes) = take(length)(i)?;
+    let s = encoding
+        .decode(bytes)
+        .ok_or_else(|| Err::Error(make_error(i, ErrorKind::MapRes)))?;
+    Ok((j, s.into_owned()))
+}
+
 fn parse_element_number(i: &[u8]) -> AMFResult<'_, Value> {
     let (i, v) = be_f64(i)?;
     Ok((i, Value::Number(v)))
@@ -30,9 +51,9 @@ fn parse_element_bool(i: &[u8]) -> AMFResult<'_, Value> {
     Ok((i, Value::Bool(v > 0)))
 }
 
-fn parse_element_string(i: &[u8]) -> AMFResult<'_, Value> {
-    let (i, v) = parse_string(i)?;
-    Ok((i, Value::String(v.to_string())))
+fn parse_element_string(i: &[u8], encoding: StringEncoding) -> AMFResult<'_, Value> {
+    let (i, v) = parse_encoded_string(i, encoding)?;
+    Ok((i, Value::String(v)))
 }
 
 fn parse_element_date(i: &[u8]) -> AMFResult<'_, Value> {
@@ -42,22 +63,22 @@ fn parse_element_date(i: &[u8]) -> AMFResult<'_, Value> {
     Ok((i, Value::Date(millis, Some(time_zone))))
 }
 
-fn parse_long_string_internal(i: &[u8]) -> AMFResult<'_, &str> {
+fn parse_long_string_internal(i: &[u8], encoding: StringEncoding) -> AMFResult<'_, String> {
     let (i, length) = be_u32(i)?;
-    map_res(take(length), std::str::from_utf8).parse(i)
+    take_string(i, length as usize, encoding)
 }
 
-fn parse_element_long_string(i: &[u8]) -> AMFResult<'_, Value> {
-    let (i, str) = parse_long_string_internal(i)?;
-    Ok((i, Value::String(str.to_string())))
+fn parse_element_long_string(i: &[u8], encoding: StringEncoding) -> AMFResult<'_, Value> {
+    let (i, str) = parse_long_string_internal(i, encoding)?;
+    Ok((i, Value::String(str)))
 }
 
-fn parse_element_xml(i: &[u8]) -> AMFResult<'_, Value> {
-    let (i, content) = parse_long_string_internal(i)?;
-    Ok((i, Value::XML(content.to_string(), true)))
+fn parse_element_xml(i: &[u8], encoding: StringEncoding) -> AMFResult<'_, Value> {
+    let (i, content) = parse_long_string_internal(i, encoding)?;
+    Ok((i, Value::XML(content, true)))
 }
 
-fn read_type_marker(i: &[u8]) -> AMFResult<'_, TypeMarker> {
+pub(crate) fn read_type_marker(i: &[u8]) -> AMFResult<'_, TypeMarker> {
     let (i, type_) = be_u8(i)?;
     Ok((
         i,
@@ -73,9 +94,118 @@ pub struct AMF0Decoder {
 
     #[cfg(feature = "amf3")]
     amf3_decoder: amf3::read::AMF3Decoder,
+
+    /// Return values read through the AVM+ switch marker as they are, instead of wrapped in `Value::AMF3`
+    #[cfg(feature = "amf3")]
+    pub unwrap_amf3: bool,
+
+    string_encoding: StringEncoding,
+
+    duplicate_keys: DuplicateKeys,
+
+    limits: DecodeLimits,
+
+    /// How deeply the value being parsed is nested
+    depth: usize,
 }
 
 impl AMF0Decoder {
+    /// Create a decoder that uses the given configuration, for both AMF0 values and any embedded AMF3 values
+    ///
+    /// This is how the configuration of a decoder is chosen, a `Reader` replaces it with its own on every parse
+    #[cfg(feature = "amf3")]
+    pub fn new(config: amf3::read::DecoderConfig) -> Self {
+        let mut decoder = Self::default();
+        decoder.set_config(config);
+        decoder
+    }
+
+    /// Replace the configuration, including the string encoding and duplicate key policy of AMF0 values
+    #[cfg(feature = "amf3")]
+    pub(crate) fn set_config(&mut self, config: amf3::read::DecoderConfig) {
+        self.string_encoding = config.string_encoding();
+        self.duplicate_keys = config.duplicate_keys();
+        self.limits = config.limits();
+        *self.amf3_decoder.config_mut() = config;
+    }
+
+    /// The encoding of strings, element names and class names
+    pub fn string_encoding(&self) -> StringEncoding {
+        self.string_encoding
+    }
+
+    /// Set the encoding of strings, element names and class names
+    #[cfg(not(feature = "amf3"))]
+    pub(crate) fn set_string_encoding(&mut self, encoding: StringEncoding) {
+        self.string_encoding = encoding;
+    }
+
+    /// How elements with the same name in one object are handled
+    pub fn duplicate_keys(&self) -> DuplicateKeys {
+        self.duplicate_keys
+    }
+
+    /// Set how elements with the same name in one object are handled
+    #[cfg(not(feature = "amf3"))]
+    pub(crate) fn set_duplicate_keys(&mut self, duplicate_keys: DuplicateKeys) {
+        self.duplicate_keys = duplicate_keys;
+    }
+
+    /// The limits on what a single parse may decode
+    pub fn limits(&self) -> DecodeLimits {
+        self.limits
+    }
+
+    /// Set the limits on what a single parse may decode
+    #[cfg(not(feature = "amf3"))]
+    pub(crate) fn set_limits(&mut self, limits: DecodeLimits) {
+        self.limits = limits;
+    }
+
+    /// Clear the state left by previous parses, keeping the configuration
+    pub fn reset(&mut self) {
+        self.cache.clear();
+        self.depth = 0;
+        #[cfg(feature = "amf3")]
+        self.amf3_decoder.reset();
+    }
+
+    /// The decoder used for embedded AMF3 values
+    #[cfg(feature = "amf3")]
+    pub(crate) fn amf3_decoder(&self) -> &amf3::read::AMF3Decoder {
+        &self.amf3_decoder
+    }
+
+    /// The current lengths of the reference tables of embedded AMF3 values
+    #[cfg(feature = "amf3")]
+    pub(crate) fn table_state(&self) -> TableState {
+        self.amf3_decoder.table_state()
+    }
+
+    /// Create a decoder with the same configuration as this one, and the AMF3 reference tables as they were at the given state
+    #[cfg(feature = "amf3")]
+    pub(crate) fn at_state(&self, state: TableState) -> Self {
+        Self {
+            amf3_decoder: self.amf3_decoder.at_state(state),
+            ..self.fresh()
+        }
+    }
+
+    /// Create a decoder with the same configuration as this one, but no cached values
+    pub(crate) fn fresh(&self) -> Self {
+        Self {
+            cache: Vec::new(),
+            #[cfg(feature = "amf3")]
+            amf3_decoder: self.amf3_decoder.fresh(),
+            #[cfg(feature = "amf3")]
+            unwrap_amf3: self.unwrap_amf3,
+            string_encoding: self.string_encoding,
+            duplicate_keys: self.duplicate_keys,
+            limits: self.limits,
+            depth: 0,
+        }
+    }
+
     fn parse_element_reference<'a>(&self, i: &'a [u8]) -> AMFResult<'a, Value> {
         let (i, reference_index) = be_u16(i)?;
 
@@ -87,14 +217,19 @@ impl AMF0Decoder {
         map(
             |i| self.parse_array_element(i),
             move |elms: Vec<Element>| {
-                Value::ECMAArray(ObjectId::INVALID, Vec::new(), elms, array_length)
+                Value::ECMAArray(EcmaArray {
+                    id: ObjectId::INVALID,
+                    dense: Vec::new(),
+                    assoc: elms,
+                    declared_len: array_length,
+                })
             },
         )
         .parse(i)
     }
 
     fn parse_element_typed_object<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Value> {
-        let (i, name) = parse_string(i)?;
+        let (i, name) = parse_encoded_string(i, self.string_encoding)?;
 
         map(
             |i| self.parse_array_element(i),
@@ -102,7 +237,7 @@ impl AMF0Decoder {
                 id: ObjectId::INVALID,
                 data: ObjectValue {
                     elements: elms,
-                    class_definition: Some(ClassDefinition::default_with_name(name.to_string())),
+                    class_definition: Some(ClassDefinition::default_with_name(name.clone())),
                 },
             },
         )
@@ -137,6 +272,8 @@ impl AMF0Decoder {
             .try_into()
             .map_err(|_| Err::Error(make_error(i, ErrorKind::Digit)))?;
 
+        self.limits.check_length(length_usize)?;
+
         // There must be at least `length_usize` bytes (u8) to read this, this prevents OOM errors with v.large arrays
         if i.len() < length_usize {
             return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
@@ -154,7 +291,8 @@ impl AMF0Decoder {
 
         let mut i = i;
         loop {
-            let (k, _) = parse_string(i)?;
+            let (k, length) = be_u16(i)?;
+            let (k, _) = take(length)(k)?;
             let (k, next_type) = read_type_marker(k)?;
             if next_type == TypeMarker::ObjectEnd {
                 i = k;
@@ -164,17 +302,24 @@ impl AMF0Decoder {
             let (j, e) = self.parse_element(i)?;
             i = j;
 
+            self.limits.check_length(out.len() + 1)?;
             out.push(e.clone());
         }
 
+        self.duplicate_keys.apply(&mut out, |e| &e.name)?;
         Ok((i, out))
     }
 
     fn parse_element_amf3<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Value> {
         #[cfg(feature = "amf3")]
         {
+            self.amf3_decoder.depth = self.depth;
             let (i, x) = self.amf3_decoder.parse_single_element(i)?;
-            Ok((i, Value::AMF3(Box::new(x))))
+            if self.unwrap_amf3 {
+                Ok((i, x))
+            } else {
+                Ok((i, Value::AMF3(Box::new(x))))
+            }
         }
         #[cfg(not(feature = "amf3"))]
         {
@@ -184,6 +329,14 @@ impl AMF0Decoder {
 
     /// Parse a single AMF0 element
     pub fn parse_single_element<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Value> {
+        self.limits.check_depth(self.depth + 1)?;
+        self.depth += 1;
+        let res = self.parse_value(i);
+        self.depth -= 1;
+        res
+    }
+
+    fn parse_value<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Value> {
         // Get the type of the next element
         let (i, type_) = read_type_marker(i)?;
 
@@ -192,7 +345,7 @@ impl AMF0Decoder {
         let (i, v) = match type_ {
             TypeMarker::Number => parse_element_number(i),
             TypeMarker::Boolean => parse_element_bool(i),
-            TypeMarker::String => parse_element_string(i),
+            TypeMarker::String => parse_element_string(i, self.string_encoding),
             TypeMarker::Object => {
                 let (i, v) = self.parse_element_object(i)?;
                 Ok((i, v))
@@ -212,9 +365,9 @@ impl AMF0Decoder {
                 Ok((i, v))
             }
             TypeMarker::Date => parse_element_date(i),
-            TypeMarker::LongString => parse_element_long_string(i),
+            TypeMarker::LongString => parse_element_long_string(i, self.string_encoding),
             TypeMarker::Unsupported => Ok((i, (Value::Unsupported))),
-            TypeMarker::Xml => parse_element_xml(i),
+            TypeMarker::Xml => parse_element_xml(i, self.string_encoding),
             TypeMarker::TypedObject => {
                 let (i, v) = self.parse_element_typed_object(i)?;
                 Ok((i, v))
@@ -229,19 +382,20 @@ impl AMF0Decoder {
     }
 
     fn parse_element<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Element> {
-        let (i, name) = parse_string(i)?;
+        let (i, name) = parse_encoded_string(i, self.string_encoding)?;
 
         map(
             |i| self.parse_single_element(i),
             move |v| Element {
-                name: name.to_string(),
+                name: name.clone(),
                 value: v,
             },
         )
         .parse(i)
     }
 
-    fn parse_element_and_padding<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Element> {
+    /// Parse a single element of an AMF0 body, followed by its padding
+    pub(crate) fn parse_element_and_padding<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Element> {
         let (i, e) = self.parse_element(i)?;
         let (i, _) = tag(PADDING.as_slice())(i)?;
 
@@ -250,6 +404,8 @@ impl AMF0Decoder {
 
     /// Parse a sequence of `PADDING` delimited `Values`
     pub fn parse_body<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Vec<Element>> {
-        many0(|i| self.parse_element_and_padding(i)).parse(i)
+        let (i, mut body) = many0(|i| self.parse_element_and_padding(i)).parse(i)?;
+        self.duplicate_keys.apply(&mut body, |e| &e.name)?;
+        Ok((i, body))
     }
 }
@@ -1,87 +1,142 @@
 use crate::PADDING;
+use crate::prelude::*;
 /// Support for encoding AMF0
-use crate::types::{Element, Reference, Value};
-use std::io::Write;
+use crate::types::{EcmaArray, Element, Reference, Value};
 
 use crate::amf0::type_marker::TypeMarker;
+use crate::errors::{EncodeError, ValidationError};
 use crate::nom_utils::write_string;
-use crate::write::WriteExt;
-use std::io::Result;
+use crate::validate::Validator;
+use crate::write::{EcmaArrayLength, EncodeOptions, LongStrings, Sink, WriteExt};
+
+type Result<T> = core::result::Result<T, EncodeError>;
 
 #[cfg(feature = "amf3")]
 use crate::amf3::write::AMF3Encoder;
 
-fn write_type_marker<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, type_: TypeMarker) -> Result<()> {
-    writer.write_u8(type_ as u8)
+/// The state shared by the values of an AMF0 body
+pub(crate) struct AMF0Encoder {
+    pub(crate) options: EncodeOptions,
+
+    /// The encoder for values written through the AVM+ switch marker
+    #[cfg(feature = "amf3")]
+    pub(crate) amf3_encoder: AMF3Encoder,
+}
+
+impl AMF0Encoder {
+    /// Create an encoder that uses the given options
+    pub(crate) fn new(options: EncodeOptions) -> Self {
+        Self {
+            options,
+            #[cfg(feature = "amf3")]
+            amf3_encoder: AMF3Encoder::new(options),
+        }
+    }
+
+    /// Create an encoder that writes AMF3 values with the given encoder, and uses its options
+    #[cfg(feature = "amf3")]
+    pub(crate) fn with_amf3_encoder(amf3_encoder: AMF3Encoder) -> Self {
+        Self {
+            options: *amf3_encoder.options(),
+            amf3_encoder,
+        }
+    }
+
+    /// Clear the reference tables, keeping the options and registered encoders
+    pub(crate) fn reset(&mut self) {
+        #[cfg(feature = "amf3")]
+        {
+            self.amf3_encoder = self.amf3_encoder.fresh();
+        }
+    }
+
+    /// A validator for values written by this encoder
+    pub(crate) fn validator<'v>(&self) -> Validator<'v> {
+        #[cfg(feature = "amf3")]
+        return Validator::for_encoder(&self.amf3_encoder, self.options);
+        #[cfg(not(feature = "amf3"))]
+        return Validator::new(self.options);
+    }
+}
+
+fn write_type_marker<'a, 'b: 'a, W: Sink + 'a>(writer: &mut W, type_: TypeMarker) -> Result<()> {
+    writer.write_u8(type_ as u8)?;
+    Ok(())
 }
 
-fn write_reference_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, r: &Reference) -> Result<()> {
+fn write_reference_element<'a, 'b: 'a, W: Sink + 'a>(writer: &mut W, r: &Reference) -> Result<()> {
     write_type_marker(writer, TypeMarker::Reference)?;
     writer.write_u16(r.0)?;
     Ok(())
 }
 
-fn write_number_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, s: f64) -> Result<()> {
+fn write_number_element<'a, 'b: 'a, W: Sink + 'a>(writer: &mut W, s: f64) -> Result<()> {
     write_type_marker(writer, TypeMarker::Number)?;
     writer.write_f64(s)?;
     Ok(())
 }
 
-fn write_bool_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, s: bool) -> Result<()> {
+fn write_bool_element<'a, 'b: 'a, W: Sink + 'a>(writer: &mut W, s: bool) -> Result<()> {
     write_type_marker(writer, TypeMarker::Boolean)?;
     writer.write_u8(u8::from(s))?;
     Ok(())
 }
 
-fn write_long_string_content<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, s: &'b str) -> Result<()> {
+fn write_long_string_content<'a, 'b: 'a, W: Sink + 'a>(writer: &mut W, s: &[u8]) -> Result<()> {
     writer.write_u32(s.len() as u32)?;
-    writer.write_all(s.as_bytes())?;
+    writer.write_all(s)?;
     Ok(())
 }
 
-fn write_long_string_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, s: &'b str) -> Result<()> {
+fn write_long_string_element<'a, 'b: 'a, W: Sink + 'a>(writer: &mut W, s: &[u8]) -> Result<()> {
     write_type_marker(writer, TypeMarker::LongString)?;
     write_long_string_content(writer, s)?;
     Ok(())
 }
 
-fn write_string_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, s: &'b str) -> Result<()> {
+fn write_string_element<'a, 'b: 'a, W: Sink + 'a>(writer: &mut W, s: &[u8]) -> Result<()> {
     write_type_marker(writer, TypeMarker::String)?;
-    write_string(writer, s)?;
+    writer.write_u16(s.len() as u16)?;
+    writer.write_all(s)?;
     Ok(())
 }
 
-fn write_object_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, o: &'b [Element]) -> Result<()> {
+fn write_object_element<'a, 'b: 'a, W: Sink + 'a>(
+    writer: &mut W,
+    encoder: &mut AMF0Encoder,
+    o: &'b [Element],
+) -> Result<()> {
     write_type_marker(writer, TypeMarker::Object)?;
     for element in o {
-        write_element(writer, element)?;
+        write_element(writer, encoder, element)?;
     }
     writer.write_u16(0)?;
     write_type_marker(writer, TypeMarker::ObjectEnd)?;
     Ok(())
 }
 
-fn write_null_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W) -> Result<()> {
+fn write_null_element<'a, 'b: 'a, W: Sink + 'a>(writer: &mut W) -> Result<()> {
     write_type_marker(writer, TypeMarker::Null)
 }
 
-fn write_undefined_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W) -> Result<()> {
+fn write_undefined_element<'a, 'b: 'a, W: Sink + 'a>(writer: &mut W) -> Result<()> {
     write_type_marker(writer, TypeMarker::Undefined)
 }
 
-fn write_strict_array_element<'a, 'b: 'a, W: Write + 'a>(
+fn write_strict_array_element<'a, 'b: 'a, W: Sink + 'a>(
     writer: &mut W,
+    encoder: &mut AMF0Encoder,
     elements: &'b [Value],
 ) -> Result<()> {
     write_type_marker(writer, TypeMarker::StrictArray)?;
     writer.write_u32(elements.len() as u32)?;
     for element in elements {
-        write_value(writer, element)?;
+        write_value(writer, encoder, element)?;
     }
     Ok(())
 }
 
-fn write_date_element<'a, 'b: 'a, W: Write + 'a>(
+fn write_date_element<'a, 'b: 'a, W: Sink + 'a>(
     writer: &mut W,
     date: f64,
     tz: Option<u16>,
@@ -92,33 +147,35 @@ fn write_date_element<'a, 'b: 'a, W: Write + 'a>(
     Ok(())
 }
 
-fn write_unsupported_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W) -> Result<()> {
+fn write_unsupported_element<'a, 'b: 'a, W: Sink + 'a>(writer: &mut W) -> Result<()> {
     write_type_marker(writer, TypeMarker::Unsupported)
 }
 
-fn write_xml_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, content: &'b str) -> Result<()> {
+fn write_xml_element<'a, 'b: 'a, W: Sink + 'a>(writer: &mut W, content: &[u8]) -> Result<()> {
     write_type_marker(writer, TypeMarker::Xml)?;
     write_long_string_content(writer, content)?;
     Ok(())
 }
 
-fn write_typed_object_element<'a, 'b: 'a, W: Write + 'a>(
+fn write_typed_object_element<'a, 'b: 'a, W: Sink + 'a>(
     writer: &mut W,
+    encoder: &mut AMF0Encoder,
     name: &'b str,
     elements: &'b [Element],
 ) -> Result<()> {
     write_type_marker(writer, TypeMarker::TypedObject)?;
-    write_string(writer, name)?;
+    write_string(writer, name, encoder.options.string_encoding)?;
     for element in elements {
-        write_element(writer, element)?;
+        write_element(writer, encoder, element)?;
     }
     writer.write_u16(0)?;
     write_type_marker(writer, TypeMarker::ObjectEnd)?;
     Ok(())
 }
 
-fn write_dense_element<'a, 'b: 'a, W: Write + 'a>(
+fn write_dense_element<'a, 'b: 'a, W: Sink + 'a>(
     writer: &mut W,
+    encoder: &mut AMF0Encoder,
     index: usize,
     element: &'b Value,
 ) -> Result<()> {
@@ -126,97 +183,119 @@ fn write_dense_element<'a, 'b: 'a, W: Write + 'a>(
 
     writer.write_u16(index_str.len() as u16)?;
     writer.write_all(index_str.as_bytes())?;
-    write_value(writer, element)?;
+    write_value(writer, encoder, element)?;
 
     Ok(())
 }
 
-fn write_ecma_array<'a, 'b: 'a, W: Write + 'a>(
+fn write_ecma_array<'a, 'b: 'a, W: Sink + 'a>(
     writer: &mut W,
-    dense: &'b [Value],
-    elements: &'b [Element],
-    length: u32,
+    encoder: &mut AMF0Encoder,
+    array: &'b EcmaArray,
 ) -> Result<()> {
     //TODO: what is the u16 padding
-    //TODO: sometimes array length is ignored (u32) sometimes its: elements.len() as u32
+
+    let length = match encoder.options.ecma_array_length {
+        EcmaArrayLength::Preserve => array.declared_len,
+        EcmaArrayLength::Recompute => array.length(),
+    };
 
     write_type_marker(writer, TypeMarker::ECMAArray)?;
     writer.write_u32(length)?;
-    for (idx, value) in dense.iter().enumerate() {
-        write_dense_element(writer, idx, value)?
+    for (idx, value) in array.dense.iter().enumerate() {
+        write_dense_element(writer, encoder, idx, value)?
     }
-    for element in elements {
-        write_element(writer, element)?
+    for element in &array.assoc {
+        write_element(writer, encoder, element)?
     }
     writer.write_u16(0)?;
     write_type_marker(writer, TypeMarker::ObjectEnd)?;
     Ok(())
 }
 
-pub(crate) fn write_value<'a, 'b: 'a, W: Write + 'a>(
+pub(crate) fn write_value<'a, 'b: 'a, W: Sink + 'a>(
     writer: &mut W,
+    encoder: &mut AMF0Encoder,
     element: &'b Value,
 ) -> Result<()> {
     match element {
         Value::Number(n) => write_number_element(writer, *n),
         Value::Bool(b) => write_bool_element(writer, *b),
         Value::String(s) => {
-            if s.len() > 65535 {
-                write_long_string_element(writer, s)
+            let bytes = encoder.options.string_encoding.encode_checked(s)?;
+            if bytes.len() <= u16::MAX as usize {
+                write_string_element(writer, &bytes)
+            } else if encoder.options.long_strings == LongStrings::Promote {
+                write_long_string_element(writer, &bytes)
             } else {
-                write_string_element(writer, s)
+                Err(ValidationError::StringTooLong(bytes.len()).into())
             }
         }
         Value::Object { id: _, data } => {
             if let Some(class_def) = &data.class_definition {
-                write_typed_object_element(writer, &class_def.name, &data.elements)
+                write_typed_object_element(writer, encoder, &class_def.name, &data.elements)
             } else {
-                write_object_element(writer, &data.elements)
+                write_object_element(writer, encoder, &data.elements)
             }
         }
         Value::Null => write_null_element(writer),
         Value::Undefined => write_undefined_element(writer),
-        Value::StrictArray(_id, a) => write_strict_array_element(writer, a.as_slice()),
+        Value::StrictArray(_id, a) => write_strict_array_element(writer, encoder, a.as_slice()),
         Value::Date(d, tz) => write_date_element(writer, *d, *tz),
         Value::Unsupported => write_unsupported_element(writer),
-        Value::XML(x, _string) => write_xml_element(writer, x),
-        Value::ECMAArray(_id, dense, elems, elems_length) => {
-            write_ecma_array(writer, dense, elems, *elems_length)
-        }
-        #[cfg(feature = "amf3")]
-        Value::AMF3(e) => {
-            write_type_marker(writer, TypeMarker::AMF3)?;
-            let encoder = AMF3Encoder::default();
-            encoder.write_value_element(writer, e)
+        // The XML marker is read back as `XML(_, true)`, other XML is kept through the AVM+ switch marker
+        Value::XML(x, string) if *string || cfg!(not(feature = "amf3")) => {
+            write_xml_element(writer, &encoder.options.string_encoding.encode_checked(x)?)
         }
+        Value::ECMAArray(array) => write_ecma_array(writer, encoder, array),
         Value::Reference(r) => write_reference_element(writer, r),
-        _ => {
-            write_unsupported_element(writer) /* Not in amf0, TODO: use the amf3 embedding for every thing else */
-        }
+        #[cfg(feature = "amf3")]
+        Value::AMF3(e) => write_amf3_element(writer, encoder, e),
+        #[cfg(feature = "amf3")]
+        _ => write_amf3_element(writer, encoder, element),
+        #[cfg(not(feature = "amf3"))]
+        _ => write_unsupported_element(writer),
     }
 }
 
-fn write_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, element: &'b Element) -> Result<()> {
-    write_string(writer, &element.name)?;
-    write_value(writer, &element.value)?;
+/// Write a value through the AVM+ switch marker, sharing the AMF3 reference tables with the rest of the body
+#[cfg(feature = "amf3")]
+fn write_amf3_element<'a, 'b: 'a, W: Sink + 'a>(
+    writer: &mut W,
+    encoder: &mut AMF0Encoder,
+    element: &'b Value,
+) -> Result<()> {
+    write_type_marker(writer, TypeMarker::AMF3)?;
+    encoder.amf3_encoder.write_value_element(writer, element)
+}
+
+fn write_element<'a, 'b: 'a, W: Sink + 'a>(
+    writer: &mut W,
+    encoder: &mut AMF0Encoder,
+    element: &'b Element,
+) -> Result<()> {
+    write_string(writer, &element.name, encoder.options.string_encoding)?;
+    write_value(writer, encoder, &element.value)?;
     Ok(())
 }
 
-fn write_element_and_padding<'a, 'b: 'a, W: Write + 'a>(
+fn write_element_and_padding<'a, 'b: 'a, W: Sink + 'a>(
     writer: &mut W,
+    encoder: &mut AMF0Encoder,
     element: &'b Element,
 ) -> Result<()> {
-    write_element(writer, element)?;
+    write_element(writer, encoder, element)?;
     writer.write_all(&PADDING)?;
     Ok(())
 }
 
-pub(crate) fn write_body<'a, 'b: 'a, W: Write + 'a>(
+pub(crate) fn write_body<'a, 'b: 'a, W: Sink + 'a>(
     writer: &mut W,
+    encoder: &mut AMF0Encoder,
     elements: &'b [Element],
 ) -> Result<()> {
     for element in elements {
-        write_element_and_padding(writer, element)?;
+        write_element_and_padding(writer, encoder, element)?;
     }
     Ok(())
 }
@@ -1,6 +1,7 @@
 use crate::amf0::writer::strict_array_writer::StrictArrayWriter;
+use crate::prelude::*;
 use crate::types::{AMFVersion, Element, Lso, Reference, Value};
-use std::collections::BTreeMap;
+use alloc::collections::BTreeMap;
 
 use super::{ArrayWriter, CacheKey, ObjWriter, ObjectWriter, TypedObjectWriter};
 
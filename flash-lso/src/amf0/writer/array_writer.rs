@@ -1,5 +1,6 @@
 use crate::amf0::writer::strict_array_writer::StrictArrayWriter;
-use crate::types::{Element, ObjectId, Reference, Value};
+use crate::prelude::*;
+use crate::types::{EcmaArray, Element, ObjectId, Reference, Value};
 
 use super::{CacheKey, ObjWriter, ObjectWriter, TypedObjectWriter};
 
@@ -150,7 +151,12 @@ impl ArrayWriter<'_, '_> {
     pub fn commit<T: AsRef<str>>(self, name: T, length: u32) {
         self.parent.add_element(
             name.as_ref(),
-            Value::ECMAArray(ObjectId::INVALID, Vec::new(), self.elements, length),
+            Value::ECMAArray(EcmaArray {
+                id: ObjectId::INVALID,
+                dense: Vec::new(),
+                assoc: self.elements,
+                declared_len: length,
+            }),
             false,
         );
     }
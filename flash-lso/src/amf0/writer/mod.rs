@@ -16,7 +16,7 @@ pub use typed_object_writer::TypedObjectWriter;
 #[test]
 fn objectwriter_serialization_test() {
     let mut w = Amf0Writer::default();
-    let (aw, _) = w.object(CacheKey::from_ptr(std::ptr::null::<u8>()));
+    let (aw, _) = w.object(CacheKey::from_ptr(core::ptr::null::<u8>()));
     let mut aw = aw.expect("amf0writer should not fail");
     {
         aw.string("asdf", "asfd");
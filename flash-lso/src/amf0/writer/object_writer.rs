@@ -1,4 +1,5 @@
 use crate::amf0::writer::strict_array_writer::StrictArrayWriter;
+use crate::prelude::*;
 use crate::types::{Element, ObjectId, ObjectValue, Reference, Value};
 
 use super::{ArrayWriter, CacheKey, ObjWriter, TypedObjectWriter};
@@ -1,3 +1,4 @@
+use crate::prelude::*;
 use crate::types::{ObjectId, Reference, Value};
 
 use super::{ArrayWriter, CacheKey, ObjWriter, ObjectWriter, TypedObjectWriter};
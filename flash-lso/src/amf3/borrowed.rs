@@ -0,0 +1,563 @@
+use crate::PADDING;
+use crate::amf3::length::Length;
+use crate::amf3::read::{
+    AMF3Decoder, REFERENCE_FLAG, read_int, read_int_signed, read_length, read_type_marker,
+    strip_captured_custom_padding,
+};
+use crate::amf3::type_marker::TypeMarker;
+use crate::encoding::StringEncoding;
+use crate::errors::Error;
+use crate::nom_utils::AMFResult;
+use crate::prelude::*;
+use crate::types::{
+    Attribute, ClassDefinitionRef, DecodeLimits, Element, ElementRef, ObjectId, ObjectValueRef,
+    ValueRef,
+};
+use alloc::borrow::Cow;
+use core::convert::TryInto;
+use enumset::EnumSet;
+use nom::Err;
+use nom::Parser;
+use nom::bytes::complete::{tag, take};
+use nom::error::{ErrorKind, make_error};
+use nom::multi::{many_m_n, separated_list0};
+use nom::number::complete::{be_f64, be_i32, be_u8, be_u32};
+
+/// Decodes AMF3 into `ValueRef`s, keeping reference tables that borrow from the input
+pub(crate) struct BorrowedDecoder<'a> {
+    strings: Vec<Cow<'a, [u8]>>,
+    traits: Vec<ClassDefinitionRef<'a>>,
+    object_id: i64,
+    encoding: StringEncoding,
+    limits: DecodeLimits,
+
+    /// How deeply the value being parsed is nested
+    pub(crate) depth: usize,
+
+    /// Decodes externalized data with the registered decoders, its reference tables are brought up to date before each use
+    external: AMF3Decoder,
+
+    /// The length of the input given to the outermost parse call, used to report error offsets
+    pub(crate) input_len: Option<usize>,
+}
+
+impl<'a> BorrowedDecoder<'a> {
+    /// Create a decoder with the configuration of the given decoder, and empty reference tables
+    pub(crate) fn new(decoder: &AMF3Decoder) -> Self {
+        Self {
+            strings: Vec::new(),
+            traits: Vec::new(),
+            object_id: 0,
+            encoding: decoder.config().string_encoding(),
+            limits: decoder.config().limits(),
+            depth: 0,
+            external: decoder.fresh(),
+            input_len: None,
+        }
+    }
+
+    fn decode_string(
+        &self,
+        i: &'a [u8],
+        bytes: Cow<'a, [u8]>,
+    ) -> Result<Cow<'a, str>, Err<Error<'a>>> {
+        match bytes {
+            Cow::Borrowed(bytes) => self.encoding.decode(bytes),
+            Cow::Owned(bytes) => self.encoding.decode_owned(bytes).map(Cow::Owned),
+        }
+        .ok_or_else(|| Err::Error(make_error(i, ErrorKind::Alpha)))
+    }
+
+    fn parse_byte_stream(&mut self, i: &'a [u8]) -> AMFResult<'a, Cow<'a, [u8]>> {
+        let (i, len) = read_length(i)?;
+
+        match len {
+            Length::Size(0) => Ok((i, Cow::Borrowed(&[]))),
+            Length::Size(len) => {
+                let (i, bytes) = take(len)(i)?;
+                self.limits.check_references(self.strings.len())?;
+                self.strings.push(Cow::Borrowed(bytes));
+                Ok((i, Cow::Borrowed(bytes)))
+            }
+            Length::Reference(index) => {
+                let bytes = self
+                    .strings
+                    .get(index)
+                    .ok_or_else(|| Err::Error(make_error(i, ErrorKind::Digit)))?
+                    .clone();
+                Ok((i, bytes))
+            }
+        }
+    }
+
+    fn parse_string(&mut self, i: &'a [u8]) -> AMFResult<'a, Cow<'a, str>> {
+        let (i, bytes) = self.parse_byte_stream(i)?;
+        let s = self.decode_string(i, bytes)?;
+        Ok((i, s))
+    }
+
+    fn parse_class_def(
+        &mut self,
+        length: u32,
+        i: &'a [u8],
+    ) -> AMFResult<'a, ClassDefinitionRef<'a>> {
+        if length & REFERENCE_FLAG == 0 {
+            let len_usize: usize = (length >> 1)
+                .try_into()
+                .map_err(|_| Err::Error(make_error(i, ErrorKind::Digit)))?;
+
+            let class_def = self
+                .traits
+                .get(len_usize)
+                .ok_or_else(|| Err::Error(make_error(i, ErrorKind::Digit)))?
+                .clone();
+
+            return Ok((i, class_def));
+        }
+        let length = length >> 1;
+
+        let (i, name) = self.parse_byte_stream(i)?;
+        let name = if name.is_empty() {
+            Cow::Borrowed("")
+        } else {
+            self.decode_string(i, name)?
+        };
+
+        let encoding = (length & 0x03) as u8;
+        let attr_count_usize: usize = (length >> 2)
+            .try_into()
+            .map_err(|_| Err::Error(make_error(i, ErrorKind::Digit)))?;
+
+        self.limits.check_length(attr_count_usize)?;
+        let (i, static_properties) =
+            many_m_n(attr_count_usize, attr_count_usize, |i| self.parse_string(i)).parse(i)?;
+
+        let mut attributes = EnumSet::empty();
+        if encoding & 0b1 == 1 {
+            attributes |= Attribute::External;
+        }
+        if encoding & 0b10 == 0b10 {
+            attributes |= Attribute::Dynamic;
+        }
+
+        let class_def = ClassDefinitionRef {
+            name,
+            attributes,
+            static_properties,
+        };
+
+        self.limits.check_references(self.traits.len())?;
+        self.traits.push(class_def.clone());
+        Ok((i, class_def))
+    }
+
+    fn parse_reference_or_val(
+        &mut self,
+        i: &'a [u8],
+        parser: impl FnOnce(&mut Self, &'a [u8], usize, ObjectId) -> AMFResult<'a, ValueRef<'a>>,
+    ) -> AMFResult<'a, ValueRef<'a>> {
+        let (i, len) = read_length(i)?;
+
+        match len {
+            Length::Reference(index) => {
+                Ok((i, ValueRef::Amf3ObjectReference(ObjectId(index as _))))
+            }
+            Length::Size(len) => {
+                let len_usize: usize = len
+                    .try_into()
+                    .map_err(|_| Err::Error(make_error(i, ErrorKind::Digit)))?;
+
+                let id = ObjectId(self.object_id);
+                self.check_object_references()?;
+                self.object_id += 1;
+
+                parser(self, i, len_usize, id)
+            }
+        }
+    }
+
+    /// Check that another object id may be given out
+    fn check_object_references(&self) -> Result<(), Err<Error<'static>>> {
+        let ids = usize::try_from(self.object_id).unwrap_or(usize::MAX);
+        self.limits.check_references(ids)
+    }
+
+    /// Decode externalized data with the owned decoder, sharing the reference tables with it
+    fn parse_external(
+        &mut self,
+        i: &'a [u8],
+        class_def: &ClassDefinitionRef<'a>,
+    ) -> AMFResult<'a, Vec<Element>> {
+        let external = &mut self.external;
+
+        let known = external.string_reference_table.len();
+        external
+            .string_reference_table
+            .extend(self.strings[known..].iter().map(|s| s.to_vec()));
+        let known = external.trait_reference_table.len();
+        external.trait_reference_table.extend(
+            self.traits[known..]
+                .iter()
+                .cloned()
+                .map(ClassDefinitionRef::into_owned),
+        );
+        external.object_id = self.object_id;
+        external.input_len = self.input_len;
+        external.depth = self.depth;
+
+        let res = external.parse_external(i, &class_def.clone().into_owned());
+
+        let known = self.strings.len();
+        self.strings.extend(
+            external.string_reference_table[known..]
+                .iter()
+                .cloned()
+                .map(Cow::Owned),
+        );
+        let known = self.traits.len();
+        self.traits.extend(
+            external.trait_reference_table[known..]
+                .iter()
+                .cloned()
+                .map(ClassDefinitionRef::from),
+        );
+        self.object_id = external.object_id;
+
+        res
+    }
+
+    fn parse_object_static(
+        &mut self,
+        i: &'a [u8],
+        class_def: &ClassDefinitionRef<'a>,
+    ) -> AMFResult<'a, Vec<ElementRef<'a>>> {
+        let mut elements = Vec::new();
+        let mut i = i;
+
+        for name in class_def.static_properties.iter() {
+            let (j, value) = self.parse_value(i)?;
+            elements.push(ElementRef {
+                name: name.clone(),
+                value,
+            });
+            i = j;
+        }
+
+        Ok((i, elements))
+    }
+
+    fn parse_element_object(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        let (i, length) = read_int(i)?;
+
+        if length & REFERENCE_FLAG == 0 {
+            let len_usize: usize = (length >> 1)
+                .try_into()
+                .map_err(|_| Err::Error(make_error(i, ErrorKind::Digit)))?;
+            return Ok((i, ValueRef::Amf3ObjectReference(ObjectId(len_usize as _))));
+        }
+
+        let id = ObjectId(self.object_id);
+        self.check_object_references()?;
+        self.object_id += 1;
+
+        let (mut i, class_def) = self.parse_class_def(length >> 1, i)?;
+
+        if class_def.attributes.contains(Attribute::External) {
+            let (j, custom) = self.parse_external(i, &class_def)?;
+            return Ok((
+                j,
+                ValueRef::Custom {
+                    custom_elements: custom,
+                    elements: Vec::new(),
+                    class_definition: Some(class_def),
+                },
+            ));
+        }
+
+        let mut elements = Vec::new();
+
+        if class_def.attributes.contains(Attribute::Dynamic) {
+            let (j, x) = self.parse_object_static(i, &class_def)?;
+            elements.extend(x);
+
+            let (mut j, mut attr) = self.parse_byte_stream(j)?;
+            while !attr.is_empty() {
+                let name = self.decode_string(i, attr)?;
+                let (k, value) = self.parse_value(j)?;
+                self.limits.check_length(elements.len() + 1)?;
+                elements.push(ElementRef { name, value });
+
+                let (k, attr2) = self.parse_byte_stream(k)?;
+                j = k;
+                attr = attr2;
+            }
+            i = j;
+        }
+        if class_def.attributes.is_empty() {
+            let (j, x) = self.parse_object_static(i, &class_def)?;
+            elements.extend(x);
+            i = j;
+        }
+        self.external
+            .config()
+            .duplicate_keys()
+            .apply(&mut elements, |e| &e.name)?;
+
+        Ok((
+            i,
+            ValueRef::Object {
+                id,
+                data: ObjectValueRef {
+                    elements,
+                    class_definition: Some(class_def),
+                },
+            },
+        ))
+    }
+
+    fn parse_element_byte_array(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        self.parse_reference_or_val(i, |_this, i, len, _| {
+            let (i, bytes) = take(len)(i)?;
+            Ok((i, ValueRef::ByteArray(bytes)))
+        })
+    }
+
+    fn parse_element_vector_int(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        self.parse_reference_or_val(i, |this, i, len, _| {
+            this.limits.check_length(len)?;
+
+            // There must be at least `len * 4` (i32 = 4 bytes) bytes to read this, this prevents OOM errors with v.large vecs
+            if i.len() < len * 4 {
+                return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
+            }
+
+            let (i, fixed_length) = be_u8(i)?;
+            let (i, ints) = many_m_n(len, len, be_i32).parse(i)?;
+            Ok((i, ValueRef::VectorInt(ints, fixed_length == 1)))
+        })
+    }
+
+    fn parse_element_vector_uint(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        self.parse_reference_or_val(i, |this, i, len, _| {
+            this.limits.check_length(len)?;
+
+            // There must be at least `len * 4` (u32 = 4 bytes) bytes to read this, this prevents OOM errors with v.large vecs
+            if i.len() < len * 4 {
+                return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
+            }
+
+            let (i, fixed_length) = be_u8(i)?;
+            let (i, ints) = many_m_n(len, len, be_u32).parse(i)?;
+            Ok((i, ValueRef::VectorUInt(ints, fixed_length == 1)))
+        })
+    }
+
+    fn parse_element_vector_double(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        self.parse_reference_or_val(i, |this, i, len, _| {
+            this.limits.check_length(len)?;
+
+            // There must be at least `len * 8` (f64 = 8 bytes) bytes to read this, this prevents OOM errors with v.large vecs
+            if i.len() < len * 8 {
+                return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
+            }
+
+            let (i, fixed_length) = be_u8(i)?;
+            let (i, numbers) = many_m_n(len, len, be_f64).parse(i)?;
+            Ok((i, ValueRef::VectorDouble(numbers, fixed_length == 1)))
+        })
+    }
+
+    fn parse_element_object_vector(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        self.parse_reference_or_val(i, |this, i, len, id| {
+            this.limits.check_length(len)?;
+
+            let (i, fixed_length) = be_u8(i)?;
+            let (i, type_name) = this.parse_string(i)?;
+            let (i, items) = many_m_n(len, len, |i| this.parse_value(i)).parse(i)?;
+
+            Ok((
+                i,
+                ValueRef::VectorObject {
+                    id,
+                    items,
+                    type_name,
+                    fixed_length: fixed_length == 1,
+                },
+            ))
+        })
+    }
+
+    fn parse_element_array(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        self.parse_reference_or_val(i, |this, i, length_usize, id| {
+            this.limits.check_length(length_usize)?;
+
+            // There must be at least `length_usize` bytes to read this, this prevents OOM errors with v.large dicts
+            if i.len() < length_usize {
+                return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
+            }
+
+            let (i, mut key) = this.parse_byte_stream(i)?;
+
+            if key.is_empty() {
+                let (i, values) =
+                    many_m_n(length_usize, length_usize, |i| this.parse_value(i)).parse(i)?;
+                return Ok((i, ValueRef::StrictArray(id, values)));
+            }
+
+            let mut elements = Vec::with_capacity(length_usize);
+
+            let mut i = i;
+            while !key.is_empty() {
+                let (j, value) = this.parse_value(i)?;
+                let name = this.decode_string(i, key)?;
+                this.limits
+                    .check_length(length_usize + elements.len() + 1)?;
+                elements.push(ElementRef { name, value });
+
+                let (j, k) = this.parse_byte_stream(j)?;
+                i = j;
+                key = k;
+            }
+
+            let (i, dense) =
+                many_m_n(length_usize, length_usize, |i| this.parse_value(i)).parse(i)?;
+
+            this.external
+                .config()
+                .duplicate_keys()
+                .apply(&mut elements, |e| &e.name)?;
+            let elements_len = elements.len() as u32;
+            Ok((
+                i,
+                ValueRef::ECMAArray {
+                    id,
+                    dense,
+                    assoc: elements,
+                    declared_len: elements_len,
+                },
+            ))
+        })
+    }
+
+    fn parse_element_dict(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        self.parse_reference_or_val(i, |this, i, len, id| {
+            this.limits.check_length(len)?;
+
+            let (i, weak_keys) = be_u8(i)?;
+
+            // There must be at least `len * 2` bytes (due to (key,val) pairs) to read this, this prevents OOM errors with v.large dicts
+            if i.len() < len * 2 {
+                return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
+            }
+
+            let mut pairs = Vec::with_capacity(len);
+            let mut i = i;
+            for _ in 0..len {
+                let (j, key) = this.parse_value(i)?;
+                let (j, value) = this.parse_value(j)?;
+                pairs.push((key, value));
+                i = j;
+            }
+
+            Ok((
+                i,
+                ValueRef::Dictionary {
+                    id,
+                    entries: pairs,
+                    weak_keys: weak_keys == 1,
+                },
+            ))
+        })
+    }
+
+    fn parse_element_date(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        self.parse_reference_or_val(i, |_this, i, _len, _| {
+            let (i, ms) = be_f64(i)?;
+            Ok((i, ValueRef::Date(ms, None)))
+        })
+    }
+
+    fn parse_element_xml(&mut self, i: &'a [u8], string: bool) -> AMFResult<'a, ValueRef<'a>> {
+        self.parse_reference_or_val(i, |this, i, len, _| {
+            let (j, data) = take(len as u32)(i)?;
+            let data = this.decode_string(i, Cow::Borrowed(data))?;
+            Ok((j, ValueRef::XML(data, string)))
+        })
+    }
+
+    /// Parse a single AMF3 value
+    pub(crate) fn parse_value(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        self.limits.check_depth(self.depth + 1)?;
+        self.depth += 1;
+        let res = self.parse_marker_value(i);
+        self.depth -= 1;
+        res
+    }
+
+    fn parse_marker_value(&mut self, i: &'a [u8]) -> AMFResult<'a, ValueRef<'a>> {
+        let (i, type_) = read_type_marker(i)?;
+
+        match type_ {
+            TypeMarker::Undefined => Ok((i, ValueRef::Undefined)),
+            TypeMarker::Null => Ok((i, ValueRef::Null)),
+            TypeMarker::False => Ok((i, ValueRef::Bool(false))),
+            TypeMarker::True => Ok((i, ValueRef::Bool(true))),
+            TypeMarker::Integer => {
+                let (i, n) = read_int_signed(i)?;
+                Ok((i, ValueRef::Integer(n)))
+            }
+            TypeMarker::Number => {
+                let (i, n) = be_f64(i)?;
+                Ok((i, ValueRef::Number(n)))
+            }
+            TypeMarker::String => {
+                let (i, s) = self.parse_string(i)?;
+                Ok((i, ValueRef::String(s)))
+            }
+            TypeMarker::Xml => self.parse_element_xml(i, false),
+            TypeMarker::Date => self.parse_element_date(i),
+            TypeMarker::Array => self.parse_element_array(i),
+            TypeMarker::Object => self.parse_element_object(i),
+            TypeMarker::XmlString => self.parse_element_xml(i, true),
+            TypeMarker::ByteArray => self.parse_element_byte_array(i),
+            TypeMarker::VectorObject => self.parse_element_object_vector(i),
+            TypeMarker::VectorInt => self.parse_element_vector_int(i),
+            TypeMarker::VectorUInt => self.parse_element_vector_uint(i),
+            TypeMarker::VectorDouble => self.parse_element_vector_double(i),
+            TypeMarker::Dictionary => self.parse_element_dict(i),
+        }
+    }
+
+    fn parse_element(&mut self, i: &'a [u8]) -> AMFResult<'a, ElementRef<'a>> {
+        let (i, name) = self.parse_string(i)?;
+        let (i, value) = self.parse_value(i)?;
+        Ok((i, ElementRef { name, value }))
+    }
+
+    /// Parse an AMF3 body from a slice into a list of elements
+    pub(crate) fn parse_body(&mut self, i: &'a [u8]) -> AMFResult<'a, Vec<ElementRef<'a>>> {
+        self.external.captured_remaining = false;
+        let (i, mut elements) =
+            separated_list0(tag(PADDING.as_slice()), |i| self.parse_element(i)).parse(i)?;
+
+        // If the last element captured the rest of the body, then it has also captured the final padding
+        let i = if i.is_empty()
+            && self.external.captured_remaining
+            && let Some(ValueRef::Custom {
+                custom_elements: custom,
+                ..
+            }) = elements.last_mut().map(|e| &mut e.value)
+            && strip_captured_custom_padding(custom)
+        {
+            i
+        } else {
+            tag(PADDING.as_slice())(i)?.0
+        };
+
+        self.external
+            .config()
+            .duplicate_keys()
+            .apply(&mut elements, |e| &e.name)?;
+        Ok((i, elements))
+    }
+}
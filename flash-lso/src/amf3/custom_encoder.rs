@@ -1,29 +1,83 @@
+use crate::amf3::decode_context::DecodeContext;
 use crate::amf3::read::AMF3Decoder;
+use crate::prelude::*;
 
 use crate::amf3::write::AMF3Encoder;
 
+use crate::errors::{EncodeError, Error};
 use crate::types::Element;
 use crate::types::*;
 
 use crate::nom_utils::AMFResult;
+use crate::write::Sink;
+use alloc::sync::Arc;
+
+/// The name of the element holding the raw bytes captured by `ExternalizableFallback::CaptureRemaining`
+pub const OPAQUE_ELEMENT_NAME: &str = "opaque";
 
 /// A trait to define encoding for custom types for use with Externalized objects
-pub trait CustomEncoder {
+///
+/// Encoders are shared between threads along with the `AMF3Encoder` they are registered with
+pub trait CustomEncoder: Send + Sync {
     /// This should implement the encoding of a given set of external elements for the given class definition
-    /// Access to the AMF3Encoder is given to allow access to caches
+    /// The encoded data should be written directly into `writer`
+    /// Mutable access to the AMF3Encoder is given so that any nested values share its reference tables
     /// This implements the encoding side of externalized type support
     fn encode(
         &self,
+        writer: &mut dyn Sink,
         elements: &[Element],
         class_def: &Option<ClassDefinition>,
-        encoder: &AMF3Encoder,
-    ) -> Vec<u8>;
+        encoder: &mut AMF3Encoder,
+    ) -> Result<(), EncodeError>;
 }
 
 /// A trait to define decoding for custom types for use with Externalized objects
-pub trait CustomDecoder {
+///
+/// Decoders are shared between threads along with the `DecoderConfig` they are registered with
+pub trait ExternalizableDecoder: Send + Sync {
+    /// This should implement the decoding of a given set of external elements
+    /// The externalized data is read through the given `DecodeContext`, which shares the caches of the AMF3Decoder
+    /// This implements the decoding side of externalized type support
+    fn decode<'a>(&self, ctx: &mut DecodeContext<'a, '_>) -> Result<Vec<Element>, Error<'a>>;
+}
+
+/// A nom based version of `ExternalizableDecoder`
+///
+/// Every `CustomDecoder` is also an `ExternalizableDecoder`, so either can be registered with the AMF3Decoder
+pub trait CustomDecoder: Send + Sync {
     /// This should implement the decoding of a given set of external elements
     /// Access to the AMF3Decoder is given to allow access to caches
     /// This implements the decoding side of externalized type support
     fn decode<'a>(&self, i: &'a [u8], dec: &mut AMF3Decoder) -> AMFResult<'a, Vec<Element>>;
 }
+
+impl<T: CustomDecoder> ExternalizableDecoder for T {
+    fn decode<'a>(&self, ctx: &mut DecodeContext<'a, '_>) -> Result<Vec<Element>, Error<'a>> {
+        ctx.run_with_decoder(|i, dec| CustomDecoder::decode(self, i, dec))
+    }
+}
+
+/// A callback used to decode externalizable classes that have no registered decoder
+///
+/// Unlike `ExternalizableDecoder` this is also given the class definition being decoded
+pub type FallbackDecoder = dyn for<'a> Fn(&mut DecodeContext<'a, '_>, &ClassDefinition) -> Result<Vec<Element>, Error<'a>>
+    + Send
+    + Sync;
+
+/// How the AMF3Decoder should handle an externalizable class with no registered decoder
+#[derive(Clone, Default)]
+pub enum ExternalizableFallback {
+    /// Fail with `Error::UnknownExternalizable`
+    #[default]
+    Error,
+
+    /// Decode the externalized data with the given callback
+    Callback(Arc<FallbackDecoder>),
+
+    /// Capture all of the remaining input as an opaque `Value::ByteArray`, in a single element named `OPAQUE_ELEMENT_NAME`
+    ///
+    /// As the length of externalized data is unknown, this only produces a usable result when the externalizable
+    /// value is the last value in the input
+    CaptureRemaining,
+}
@@ -0,0 +1,160 @@
+use crate::amf0::read::AMF0Decoder;
+use crate::amf3::read::AMF3Decoder;
+use crate::errors::Error;
+use crate::nom_utils::AMFResult;
+use crate::prelude::*;
+use crate::types::Value;
+
+/// A cursor over externalized data, used by `ExternalizableDecoder` implementations
+///
+/// Positions are relative to the start of the externalized data, and all errors use the crate's own `Error` type
+pub struct DecodeContext<'a, 'd> {
+    /// The externalized data, starting from the first byte after the class definition
+    input: &'a [u8],
+
+    /// The current read position within `input`
+    position: usize,
+
+    /// The decoder that is reading the externalizable object, nested values share its reference tables
+    decoder: &'d mut AMF3Decoder,
+
+    /// Decoder used for AMF0 values, created on first use so that AMF0 references are shared within this context
+    amf0_decoder: Option<AMF0Decoder>,
+}
+
+impl<'a, 'd> DecodeContext<'a, 'd> {
+    /// Create a new context reading the given input with the given decoder
+    pub fn new(input: &'a [u8], decoder: &'d mut AMF3Decoder) -> Self {
+        Self {
+            input,
+            position: 0,
+            decoder,
+            amf0_decoder: None,
+        }
+    }
+
+    /// Get the current read position, relative to the start of the externalized data
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Get the input that has not yet been read
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.input[self.position..]
+    }
+
+    /// Get the decoder that is reading the externalizable object
+    pub fn decoder(&mut self) -> &mut AMF3Decoder {
+        self.decoder
+    }
+
+    /// Read the given number of bytes
+    pub fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], Error<'a>> {
+        let bytes = self.remaining().get(..length).ok_or(Error::OutOfBounds)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], Error<'a>> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.read_bytes(N)?);
+        Ok(out)
+    }
+
+    /// Read an unsigned byte (`IDataInput.readUnsignedByte`)
+    pub fn read_u8(&mut self) -> Result<u8, Error<'a>> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    /// Read a signed byte (`IDataInput.readByte`)
+    pub fn read_i8(&mut self) -> Result<i8, Error<'a>> {
+        Ok(i8::from_be_bytes(self.read_array()?))
+    }
+
+    /// Read a boolean (`IDataInput.readBoolean`)
+    pub fn read_bool(&mut self) -> Result<bool, Error<'a>> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    /// Read a big-endian unsigned 16-bit integer (`IDataInput.readUnsignedShort`)
+    pub fn read_u16_be(&mut self) -> Result<u16, Error<'a>> {
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    /// Read a big-endian signed 16-bit integer (`IDataInput.readShort`)
+    pub fn read_i16_be(&mut self) -> Result<i16, Error<'a>> {
+        Ok(i16::from_be_bytes(self.read_array()?))
+    }
+
+    /// Read a big-endian unsigned 32-bit integer (`IDataInput.readUnsignedInt`)
+    pub fn read_u32_be(&mut self) -> Result<u32, Error<'a>> {
+        Ok(u32::from_be_bytes(self.read_array()?))
+    }
+
+    /// Read a big-endian signed 32-bit integer (`IDataInput.readInt`)
+    pub fn read_i32_be(&mut self) -> Result<i32, Error<'a>> {
+        Ok(i32::from_be_bytes(self.read_array()?))
+    }
+
+    /// Read a big-endian 32-bit float (`IDataInput.readFloat`)
+    pub fn read_f32_be(&mut self) -> Result<f32, Error<'a>> {
+        Ok(f32::from_be_bytes(self.read_array()?))
+    }
+
+    /// Read a big-endian 64-bit float (`IDataInput.readDouble`)
+    pub fn read_f64_be(&mut self) -> Result<f64, Error<'a>> {
+        Ok(f64::from_be_bytes(self.read_array()?))
+    }
+
+    /// Read a UTF-8 string prefixed with a u16 length (`IDataInput.readUTF`)
+    pub fn read_utf(&mut self) -> Result<String, Error<'a>> {
+        let length = self.read_u16_be()?;
+        self.read_utf_bytes(length as usize)
+    }
+
+    /// Read a UTF-8 string of the given length in bytes (`IDataInput.readUTFBytes`)
+    pub fn read_utf_bytes(&mut self, length: usize) -> Result<String, Error<'a>> {
+        let bytes = self.read_bytes(length)?;
+        core::str::from_utf8(bytes)
+            .map(str::to_string)
+            .map_err(|_| Error::InvalidString)
+    }
+
+    /// Read an AMF3 encoded value (`IDataInput.readObject` with AMF3 encoding)
+    ///
+    /// This shares the string, trait and object reference tables of the decoder
+    pub fn read_amf3_value(&mut self) -> Result<Value, Error<'a>> {
+        self.run_with_decoder(|i, dec| dec.parse_single_element(i))
+    }
+
+    /// Read an AMF0 encoded value (`IDataInput.readObject` with AMF0 encoding)
+    pub fn read_amf0_value(&mut self) -> Result<Value, Error<'a>> {
+        let config = self.decoder.config();
+        let decoder = self
+            .amf0_decoder
+            .get_or_insert_with(|| AMF0Decoder::new(config.clone()));
+        let input = &self.input[self.position..];
+        let (rest, value) = decoder.parse_single_element(input).map_err(from_nom)?;
+        self.position += input.len() - rest.len();
+        Ok(value)
+    }
+
+    /// Run a nom based parser on the remaining input, advancing past the data that it consumes
+    pub(crate) fn run_with_decoder<T>(
+        &mut self,
+        parser: impl FnOnce(&'a [u8], &mut AMF3Decoder) -> AMFResult<'a, T>,
+    ) -> Result<T, Error<'a>> {
+        let input = self.remaining();
+        let (rest, value) = parser(input, self.decoder).map_err(from_nom)?;
+        self.position += input.len() - rest.len();
+        Ok(value)
+    }
+}
+
+/// Convert a nom error into the inner crate error
+fn from_nom(e: nom::Err<Error<'_>>) -> Error<'_> {
+    match e {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e,
+        nom::Err::Incomplete(_) => Error::OutOfBounds,
+    }
+}
@@ -1,19 +1,17 @@
 use crate::amf3::length::Length;
-use std::cell::RefCell;
-use std::fmt::Debug;
+use crate::prelude::*;
+use core::fmt::Debug;
 
 /// Abstraction over the Amf3 caching mechanism
 #[derive(Clone, Debug)]
 pub struct ElementCache<T> {
-    cache: RefCell<Vec<T>>,
+    cache: Vec<T>,
 }
 
 impl<T> Default for ElementCache<T> {
     /// Create a new ElementCache
     fn default() -> Self {
-        ElementCache {
-            cache: RefCell::new(Vec::new()),
-        }
+        ElementCache { cache: Vec::new() }
     }
 }
 
@@ -21,27 +19,27 @@ impl<T: PartialEq + Clone + Debug> ElementCache<T> {
     /// Check if the cache contains a given element
     #[inline]
     pub(crate) fn has(&self, val: &T) -> bool {
-        self.cache.borrow().contains(val)
+        self.cache.contains(val)
     }
 
     /// Add the given item to the cache, if the item already exists will do nothing
     #[inline]
-    pub(crate) fn store(&self, val: T) {
+    pub(crate) fn store(&mut self, val: T) {
         if !self.has(&val) {
-            self.cache.borrow_mut().push(val);
+            self.cache.push(val);
         }
     }
 
     /// Retrieve the item at the given index from the cache
     #[inline]
     pub fn get_element(&self, index: usize) -> Option<T> {
-        self.cache.borrow().get(index).cloned()
+        self.cache.get(index).cloned()
     }
 
     /// Retrieve the index for the given value
     #[inline]
     pub(crate) fn get_index(&self, val: T) -> Option<usize> {
-        self.cache.borrow().iter().position(|i| *i == val)
+        self.cache.iter().position(|i| *i == val)
     }
 
     /// Get a Length reference to an item in the cache
@@ -58,8 +56,8 @@ impl<T: PartialEq + Clone + Debug> ElementCache<T> {
     /// See #to_length, except will store the given value via #add after retrieving the index (if it does not already exist)
     /// FIXME - this is currently unused, since our implementation is incorrect (we need to compare 'Rc's by pointer,
     /// to avoid combining two distinct objects into the same reference).
-    #[allow(unused)]
-    pub(crate) fn to_length_store(&self, val: T, length: u32) -> Length {
+    #[allow(unused, clippy::wrong_self_convention)]
+    pub(crate) fn to_length_store(&mut self, val: T, length: u32) -> Length {
         let len = self.to_length(val.clone(), length);
         self.store(val);
         len
@@ -70,7 +68,7 @@ impl<T: PartialEq + Clone + Debug> ElementCache<Vec<T>> {
     /// See #store, will convert slices of &\[T\] into Vec<T> before storing
     #[inline]
     #[allow(unused)]
-    pub(crate) fn store_slice(&self, val: &[T]) {
+    pub(crate) fn store_slice(&mut self, val: &[T]) {
         self.store(val.to_vec());
     }
 
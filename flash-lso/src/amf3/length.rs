@@ -1,5 +1,9 @@
 use crate::amf3::write::AMF3Encoder;
-use std::io::Write;
+use crate::errors::{EncodeError, ValidationError};
+use crate::write::Sink;
+
+/// The largest size or reference that fits in the 28 bits a U29 leaves after the flag bit
+pub(crate) const MAX_LENGTH: u32 = (1 << 28) - 1;
 
 #[derive(Copy, Clone, Debug, Eq, Ord, PartialOrd, PartialEq)]
 pub(crate) enum Length {
@@ -23,25 +27,31 @@ impl Length {
         }
     }
 
-    pub(crate) fn write<'a, 'b: 'a, W: Write + 'a>(
+    pub(crate) fn write<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
         &self,
         writer: &mut W,
         amf3: &AMF3Encoder,
-    ) -> std::io::Result<()> {
+    ) -> Result<(), EncodeError> {
         write_length(writer, amf3, self)
     }
 }
 
-fn write_length<'a, 'b: 'a, W: Write + 'a>(
+fn write_length<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
     writer: &mut W,
     amf3: &AMF3Encoder,
     s: &Length,
-) -> std::io::Result<()> {
+) -> Result<(), EncodeError> {
     match s {
+        Length::Size(x) if *x > MAX_LENGTH => {
+            Err(ValidationError::LengthOutOfRange(*x as usize).into())
+        }
         Length::Size(x) => {
             // With the last bit set
             amf3.write_int(writer, ((x << 1) | 0b1) as i32)
         }
+        Length::Reference(x) if *x > MAX_LENGTH as usize => {
+            Err(ValidationError::LengthOutOfRange(*x).into())
+        }
         Length::Reference(x) => amf3.write_int(writer, (x << 1) as i32),
     }
 }
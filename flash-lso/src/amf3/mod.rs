@@ -1,5 +1,9 @@
+/// Decoding AMF3 into values that borrow from the input
+pub(crate) mod borrowed;
 /// Support for custom encoders / decoders
 pub mod custom_encoder;
+/// Cursor-style decoding context for custom decoders
+pub mod decode_context;
 /// Cache pool for the 3 amf3 cache types
 pub mod element_cache;
 /// Abstraction over the AMF3 length and reference types
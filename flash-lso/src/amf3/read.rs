@@ -1,25 +1,32 @@
-use crate::amf3::custom_encoder::CustomDecoder;
+use crate::alias::{AmfExternalizable, ExternalizableCodec};
+use crate::amf3::custom_encoder::{
+    ExternalizableDecoder, ExternalizableFallback, OPAQUE_ELEMENT_NAME,
+};
+use crate::amf3::decode_context::DecodeContext;
 use crate::amf3::type_marker::TypeMarker;
+use crate::prelude::*;
 use nom::Parser;
 
 use crate::PADDING;
 use crate::amf3::length::Length;
+use crate::encoding::StringEncoding;
+use crate::errors::Error;
 use crate::nom_utils::AMFResult;
 use crate::types::*;
 use crate::types::{Element, Value};
+use alloc::collections::BTreeMap;
 use enumset::EnumSet;
 use nom::Err;
 use nom::bytes::complete::{tag, take};
-use nom::combinator::{map, map_res};
+use nom::combinator::map;
 use nom::error::{ErrorKind, make_error};
-use nom::lib::std::collections::HashMap;
 use nom::multi::{many_m_n, separated_list0};
 use nom::number::complete::{be_f64, be_i32, be_u8, be_u32};
 
-use std::convert::{TryFrom, TryInto};
-use std::rc::Rc;
+use alloc::sync::Arc;
+use core::convert::{TryFrom, TryInto};
 
-const REFERENCE_FLAG: u32 = 0x01;
+pub(crate) const REFERENCE_FLAG: u32 = 0x01;
 
 #[cfg(fuzzing)]
 /// For fuzzing
@@ -28,7 +35,7 @@ pub fn fuzz_read_int_signed(i: &[u8]) -> AMFResult<'_, i32> {
 }
 
 #[allow(clippy::unusual_byte_groupings)]
-fn read_int_signed(i: &[u8]) -> AMFResult<'_, i32> {
+pub(crate) fn read_int_signed(i: &[u8]) -> AMFResult<'_, i32> {
     // Read the first byte of the number
     let (mut i, num) = be_u8(i)?;
     let mut value = (num & 0b01111111) as i32;
@@ -64,7 +71,7 @@ pub fn fuzz_read_int(i: &[u8]) -> AMFResult<'_, u32> {
 }
 
 #[allow(clippy::unusual_byte_groupings)]
-fn read_int(i: &[u8]) -> AMFResult<'_, u32> {
+pub(crate) fn read_int(i: &[u8]) -> AMFResult<'_, u32> {
     // Read the first byte of the number
     let (mut i, num) = be_u8(i)?;
     let mut value = (num & 0b01111111) as u32;
@@ -139,7 +146,7 @@ mod read_number_tests {
     }
 }
 
-fn read_length(i: &[u8]) -> AMFResult<'_, Length> {
+pub(crate) fn read_length(i: &[u8]) -> AMFResult<'_, Length> {
     let (i, val) = read_int(i)?;
     Ok((
         i,
@@ -155,7 +162,104 @@ fn parse_element_int(i: &[u8]) -> AMFResult<'_, Value> {
     Ok((i, (s)))
 }
 
+/// Configuration shared by every parse, such as the decoders registered for externalized types
+///
+/// This is cheap to clone and can be shared between threads, clones share their contents until one of them is modified
+#[derive(Clone, Default)]
+pub struct DecoderConfig(Arc<DecoderConfigInner>);
+
+#[derive(Clone, Default)]
+struct DecoderConfigInner {
+    /// Decoders used for handling externalized types
+    external_decoders: BTreeMap<String, Arc<dyn ExternalizableDecoder>>,
+
+    /// How to handle externalized types that have no registered decoder
+    externalizable_fallback: ExternalizableFallback,
+
+    /// The encoding of strings, element names and class names
+    string_encoding: StringEncoding,
+
+    /// How elements with the same name in one object are handled
+    duplicate_keys: DuplicateKeys,
+
+    /// Limits on what a single parse may decode
+    limits: DecodeLimits,
+}
+
+impl DecoderConfig {
+    /// Register a custom decoder for external data formats
+    ///
+    /// This accepts both `ExternalizableDecoder` and the nom based `CustomDecoder`
+    pub fn register_custom_decoder<T: ExternalizableDecoder + Default + 'static>(
+        &mut self,
+        name: &str,
+    ) {
+        Arc::make_mut(&mut self.0)
+            .external_decoders
+            .insert(name.to_string(), Arc::new(T::default()));
+    }
+
+    /// Register the decoder for an `AmfExternalizable` class, under its class alias
+    pub fn register_externalizable<T: AmfExternalizable>(&mut self) {
+        self.register_custom_decoder::<ExternalizableCodec<T>>(T::ALIAS);
+    }
+
+    /// Set how externalized types that have no registered decoder should be handled
+    pub fn set_externalizable_fallback(&mut self, fallback: ExternalizableFallback) {
+        Arc::make_mut(&mut self.0).externalizable_fallback = fallback;
+    }
+
+    /// Check if a custom decoder is registered for the given class name
+    pub fn has_custom_decoder(&self, name: &str) -> bool {
+        self.0.external_decoders.contains_key(name)
+    }
+
+    /// The encoding of strings, element names and class names
+    pub fn string_encoding(&self) -> StringEncoding {
+        self.0.string_encoding
+    }
+
+    /// Set the encoding of strings, element names and class names
+    pub fn set_string_encoding(&mut self, encoding: StringEncoding) {
+        Arc::make_mut(&mut self.0).string_encoding = encoding;
+    }
+
+    /// How elements with the same name in one object are handled
+    pub fn duplicate_keys(&self) -> DuplicateKeys {
+        self.0.duplicate_keys
+    }
+
+    /// Set how elements with the same name in one object are handled
+    pub fn set_duplicate_keys(&mut self, duplicate_keys: DuplicateKeys) {
+        Arc::make_mut(&mut self.0).duplicate_keys = duplicate_keys;
+    }
+
+    /// The limits on what a single parse may decode
+    pub fn limits(&self) -> DecodeLimits {
+        self.0.limits
+    }
+
+    /// Set the limits on what a single parse may decode
+    pub fn set_limits(&mut self, limits: DecodeLimits) {
+        Arc::make_mut(&mut self.0).limits = limits;
+    }
+
+    fn custom_decoder(&self, name: &str) -> Option<Arc<dyn ExternalizableDecoder>> {
+        self.0.external_decoders.get(name).map(Arc::clone)
+    }
+}
+
+/// The lengths of the reference tables of a decoder at a point in a body
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct TableState {
+    strings: usize,
+    traits: usize,
+    object_id: i64,
+}
+
 /// Handles decoding AMF3
+///
+/// The reference tables are per-parse state, the registered decoders are kept in a `DecoderConfig`
 #[derive(Default)]
 pub struct AMF3Decoder {
     /// The table used to cache repeated byte strings
@@ -164,12 +268,21 @@ pub struct AMF3Decoder {
     /// The table used to cache repeated trait definitions
     pub trait_reference_table: Vec<ClassDefinition>,
 
-    /// Encoders used for handling externalized types
-    external_decoders: HashMap<String, Rc<dyn CustomDecoder>>,
+    /// The configuration used for every parse
+    config: DecoderConfig,
 
     /// Tracks the id of the last object we have read, used to generate `ObjectId`s for `Amf3Reference`
     /// Not an `ObjectId` itself as they don't impl `Default`
-    object_id: i64,
+    pub(crate) object_id: i64,
+
+    /// Set when `ExternalizableFallback::CaptureRemaining` has consumed the rest of the input
+    pub(crate) captured_remaining: bool,
+
+    /// The length of the input given to the outermost parse call, used to report error offsets
+    pub(crate) input_len: Option<usize>,
+
+    /// How deeply the value being parsed is nested
+    pub(crate) depth: usize,
 }
 
 fn parse_element_number(i: &[u8]) -> AMFResult<'_, Value> {
@@ -178,10 +291,166 @@ fn parse_element_number(i: &[u8]) -> AMFResult<'_, Value> {
 }
 
 impl AMF3Decoder {
-    /// Register a custom decoder for external data formats
-    pub fn register_custom_decoder<T: CustomDecoder + Default + 'static>(&mut self, name: &str) {
-        self.external_decoders
-            .insert(name.to_string(), Rc::new(T::default()));
+    /// Create a decoder with the given configuration
+    pub fn new(config: DecoderConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// The configuration used by this decoder
+    pub fn config(&self) -> &DecoderConfig {
+        &self.config
+    }
+
+    /// The configuration used by this decoder, modifying this does not affect any clones of it
+    pub fn config_mut(&mut self) -> &mut DecoderConfig {
+        &mut self.config
+    }
+
+    /// Clear the reference tables, object ids and other state left by previous parses, keeping the configuration
+    pub fn reset(&mut self) {
+        self.string_reference_table.clear();
+        self.trait_reference_table.clear();
+        self.object_id = 0;
+        self.captured_remaining = false;
+        self.input_len = None;
+        self.depth = 0;
+    }
+
+    /// Create a decoder with the same configuration as this one, but empty reference tables
+    pub(crate) fn fresh(&self) -> Self {
+        Self::new(self.config.clone())
+    }
+
+    /// The current lengths of the reference tables
+    pub(crate) fn table_state(&self) -> TableState {
+        TableState {
+            strings: self.string_reference_table.len(),
+            traits: self.trait_reference_table.len(),
+            object_id: self.object_id,
+        }
+    }
+
+    /// Create a decoder with the same configuration as this one, and the reference tables as they were at the given state
+    pub(crate) fn at_state(&self, state: TableState) -> Self {
+        Self {
+            string_reference_table: self.string_reference_table[..state.strings].to_vec(),
+            trait_reference_table: self.trait_reference_table[..state.traits].to_vec(),
+            object_id: state.object_id,
+            ..self.fresh()
+        }
+    }
+
+    /// Register a custom decoder for external data formats, see `DecoderConfig::register_custom_decoder`
+    pub fn register_custom_decoder<T: ExternalizableDecoder + Default + 'static>(
+        &mut self,
+        name: &str,
+    ) {
+        self.config.register_custom_decoder::<T>(name);
+    }
+
+    /// Register the decoder for an `AmfExternalizable` class, under its class alias
+    pub fn register_externalizable<T: AmfExternalizable>(&mut self) {
+        self.config.register_externalizable::<T>();
+    }
+
+    /// Set how externalized types that have no registered decoder should be handled
+    pub fn set_externalizable_fallback(&mut self, fallback: ExternalizableFallback) {
+        self.config.set_externalizable_fallback(fallback);
+    }
+
+    /// Set the encoding of strings, element names and class names
+    pub fn set_string_encoding(&mut self, encoding: StringEncoding) {
+        self.config.set_string_encoding(encoding);
+    }
+
+    /// Decode the bytes of a string with the configured encoding
+    fn decode_string<'a>(&self, i: &'a [u8], bytes: Vec<u8>) -> Result<String, Err<Error<'a>>> {
+        self.config
+            .string_encoding()
+            .decode_owned(bytes)
+            .ok_or_else(|| Err::Error(make_error(i, ErrorKind::Alpha)))
+    }
+
+    /// Run the given parser, recording the input length if this is the outermost parse call
+    fn with_input<'a, T>(
+        &mut self,
+        i: &'a [u8],
+        parser: impl FnOnce(&mut Self, &'a [u8]) -> AMFResult<'a, T>,
+    ) -> AMFResult<'a, T> {
+        if self.input_len.is_some() {
+            return parser(self, i);
+        }
+
+        self.input_len = Some(i.len());
+        let res = parser(self, i);
+        self.input_len = None;
+        res
+    }
+
+    /// Check that another object id may be given out
+    fn check_object_references(&self) -> Result<(), Err<Error<'static>>> {
+        let ids = usize::try_from(self.object_id).unwrap_or(usize::MAX);
+        self.config.limits().check_references(ids)
+    }
+
+    /// Get the offset of the given remaining input from the start of the outermost input
+    fn offset_of(&self, i: &[u8]) -> usize {
+        self.input_len.map_or(0, |len| len.saturating_sub(i.len()))
+    }
+
+    /// Decode externalized data through a `DecodeContext`
+    ///
+    /// Errors are reported as failures, as the position of the externalized data has already been committed to
+    fn decode_external<'a>(
+        &mut self,
+        i: &'a [u8],
+        decode: impl FnOnce(&mut DecodeContext<'a, '_>) -> Result<Vec<Element>, Error<'a>>,
+    ) -> AMFResult<'a, Vec<Element>> {
+        let mut ctx = DecodeContext::new(i, self);
+        let elements = decode(&mut ctx).map_err(Err::Failure)?;
+        Ok((ctx.remaining(), elements))
+    }
+
+    fn parse_unknown_external<'a>(
+        &mut self,
+        i: &'a [u8],
+        class_def: &ClassDefinition,
+    ) -> AMFResult<'a, Vec<Element>> {
+        match self.config.0.externalizable_fallback.clone() {
+            ExternalizableFallback::Error => Err(Err::Failure(Error::UnknownExternalizable {
+                class_name: class_def.name.clone(),
+                offset: self.offset_of(i),
+            })),
+            ExternalizableFallback::Callback(callback) => {
+                self.decode_external(i, |ctx| callback(ctx, class_def))
+            }
+            ExternalizableFallback::CaptureRemaining => {
+                self.captured_remaining = true;
+                self.decode_external(i, |ctx| {
+                    let rest = ctx.read_bytes(ctx.remaining().len())?;
+                    Ok(vec![Element::new(
+                        OPAQUE_ELEMENT_NAME,
+                        Value::ByteArray(rest.to_vec()),
+                    )])
+                })
+            }
+        }
+    }
+
+    /// Parse the externalized data of an object, with the registered decoder for its class or the fallback
+    pub(crate) fn parse_external<'a>(
+        &mut self,
+        i: &'a [u8],
+        class_def: &ClassDefinition,
+    ) -> AMFResult<'a, Vec<Element>> {
+        if let Some(decoder) = self.config.custom_decoder(&class_def.name) {
+            self.decode_external(i, |ctx| decoder.decode(ctx))
+        } else {
+            self.parse_unknown_external(i, class_def)
+        }
     }
 
     fn parse_element_string<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Value> {
@@ -197,8 +466,7 @@ impl AMF3Decoder {
 
     fn parse_string<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, String> {
         let (i, bytes) = self.parse_byte_stream(i)?;
-        let bytes_str =
-            String::from_utf8(bytes).map_err(|_| Err::Error(make_error(i, ErrorKind::Alpha)))?;
+        let bytes_str = self.decode_string(i, bytes)?;
         Ok((i, bytes_str))
     }
 
@@ -223,7 +491,7 @@ impl AMF3Decoder {
         let name_str = if name.is_empty() {
             "".to_string()
         } else {
-            String::from_utf8(name).map_err(|_| Err::Error(make_error(i, ErrorKind::Alpha)))?
+            self.decode_string(i, name)?
         };
 
         let encoding = (length & 0x03) as u8;
@@ -234,6 +502,8 @@ impl AMF3Decoder {
             .try_into()
             .map_err(|_| Err::Error(make_error(i, ErrorKind::Digit)))?;
 
+        self.config.limits().check_length(attr_count_usize)?;
+
         // Read static attributes if they exist
         let (i, static_props) =
             many_m_n(attr_count_usize, attr_count_usize, |i| self.parse_string(i)).parse(i)?;
@@ -256,6 +526,9 @@ impl AMF3Decoder {
             static_properties: static_props,
         };
 
+        self.config
+            .limits()
+            .check_references(self.trait_reference_table.len())?;
         self.trait_reference_table.push(class_def.clone());
         Ok((i, class_def))
     }
@@ -275,6 +548,7 @@ impl AMF3Decoder {
                     .map_err(|_| Err::Error(make_error(i, ErrorKind::Digit)))?;
 
                 let index = self.object_id;
+                self.check_object_references()?;
                 self.object_id += 1;
 
                 let (i, res) = parser(self, i, len_usize, index as _)?;
@@ -293,6 +567,9 @@ impl AMF3Decoder {
                     Ok((i, Vec::new()))
                 } else {
                     let (i, bytes) = take(len)(i)?;
+                    self.config
+                        .limits()
+                        .check_references(self.string_reference_table.len())?;
                     self.string_reference_table.push(bytes.to_vec());
                     Ok((i, bytes.to_vec()))
                 }
@@ -349,6 +626,7 @@ impl AMF3Decoder {
                 class_definition: None,
             },
         };
+        self.check_object_references()?;
         self.object_id += 1;
 
         // Class def
@@ -363,18 +641,17 @@ impl AMF3Decoder {
 
         let mut i = i;
         if class_def.attributes.contains(Attribute::External) {
-            let dec = self.external_decoders.get(&class_def.name).map(Rc::clone);
-            return if let Some(decoder) = dec {
-                let (j, v) = decoder.decode(i, self)?;
-                external_elements = v;
-                i = j;
-                //TODO: should it be possible to have both dynamic and external together
-                let value = Value::Custom(external_elements, Vec::new(), Some(class_def.clone()));
+            let (j, v) = self.parse_external(i, &class_def)?;
+            external_elements = v;
+            i = j;
+            //TODO: should it be possible to have both dynamic and external together
+            let value = Value::Custom(CustomObject {
+                custom_elements: external_elements,
+                elements: Vec::new(),
+                class_definition: Some(class_def.clone()),
+            });
 
-                Ok((i, value))
-            } else {
-                Err(Err::Error(make_error(i, ErrorKind::Tag)))
-            };
+            return Ok((i, value));
         }
 
         if class_def.attributes.contains(Attribute::Dynamic) {
@@ -384,9 +661,9 @@ impl AMF3Decoder {
             // Read dynamic
             let (mut j, mut attr) = self.parse_byte_stream(j)?;
             while !attr.is_empty() {
-                let attr_str = String::from_utf8(attr)
-                    .map_err(|_| Err::Error(make_error(i, ErrorKind::Alpha)))?;
+                let attr_str = self.decode_string(i, attr)?;
                 let (k, val) = self.parse_single_element(j)?;
+                self.config.limits().check_length(elements.len() + 1)?;
                 elements.push(Element {
                     name: attr_str,
                     value: val,
@@ -405,6 +682,9 @@ impl AMF3Decoder {
             i = j;
         }
 
+        self.config
+            .duplicate_keys()
+            .apply(&mut elements, |e| &e.name)?;
         if let Value::Object { id: _, data } = &mut obj {
             data.elements = elements;
         }
@@ -420,7 +700,9 @@ impl AMF3Decoder {
     }
 
     fn parse_element_vector_int<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Value> {
-        self.parse_reference_or_val(i, |_this, i, len, _| {
+        self.parse_reference_or_val(i, |this, i, len, _| {
+            this.config.limits().check_length(len)?;
+
             // There must be at least `len * 4` (i32 = 4 bytes) bytes to read this, this prevents OOM errors with v.large vecs
             if i.len() < len * 4 {
                 return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
@@ -435,7 +717,9 @@ impl AMF3Decoder {
     }
 
     fn parse_element_vector_uint<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Value> {
-        self.parse_reference_or_val(i, |_this, i, len, _| {
+        self.parse_reference_or_val(i, |this, i, len, _| {
+            this.config.limits().check_length(len)?;
+
             // There must be at least `len * 4` (u32 = 4 bytes) bytes to read this, this prevents OOM errors with v.large vecs
             if i.len() < len * 4 {
                 return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
@@ -449,7 +733,9 @@ impl AMF3Decoder {
     }
 
     fn parse_element_vector_double<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Value> {
-        self.parse_reference_or_val(i, |_this, i, len, _| {
+        self.parse_reference_or_val(i, |this, i, len, _| {
+            this.config.limits().check_length(len)?;
+
             // There must be at least `len * 8` (f64 = 8 bytes) bytes to read this, this prevents OOM errors with v.large dicts
             if i.len() < len * 8 {
                 return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
@@ -464,6 +750,8 @@ impl AMF3Decoder {
 
     fn parse_element_object_vector<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Value> {
         self.parse_reference_or_val(i, |this, i, len, ofi| {
+            this.config.limits().check_length(len)?;
+
             let (i, fixed_length) = be_u8(i)?;
 
             let (i, object_type_name) = this.parse_string(i)?;
@@ -475,13 +763,20 @@ impl AMF3Decoder {
 
             Ok((
                 i,
-                Value::VectorObject(id, elems, object_type_name, fixed_length == 1),
+                Value::VectorObject(ObjectVector {
+                    id,
+                    items: elems,
+                    type_name: object_type_name,
+                    fixed_length: fixed_length == 1,
+                }),
             ))
         })
     }
 
     fn parse_element_array<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Value> {
         self.parse_reference_or_val(i, |this, i, length_usize, ofi| {
+            this.config.limits().check_length(length_usize)?;
+
             // There must be at least `length_usize` bytes to read this, this prevents OOM errors with v.large dicts
             if i.len() < length_usize {
                 return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
@@ -505,8 +800,10 @@ impl AMF3Decoder {
             let mut i = i;
             while !key.is_empty() {
                 let (j, e) = this.parse_single_element(i)?;
-                let key_str = String::from_utf8(key)
-                    .map_err(|_| Err::Error(make_error(i, ErrorKind::Alpha)))?;
+                let key_str = this.decode_string(i, key)?;
+                this.config
+                    .limits()
+                    .check_length(length_usize + elements.len() + 1)?;
 
                 elements.push(Element {
                     name: key_str,
@@ -521,16 +818,29 @@ impl AMF3Decoder {
             let (i, el) =
                 many_m_n(length_usize, length_usize, |i| this.parse_single_element(i)).parse(i)?;
 
+            this.config
+                .duplicate_keys()
+                .apply(&mut elements, |e| &e.name)?;
             let elements_len = elements.len() as u32;
 
             let id = ObjectId(ofi as _);
 
-            Ok((i, Value::ECMAArray(id, el, elements, elements_len)))
+            Ok((
+                i,
+                Value::ECMAArray(EcmaArray {
+                    id,
+                    dense: el,
+                    assoc: elements,
+                    declared_len: elements_len,
+                }),
+            ))
         })
     }
 
     fn parse_element_dict<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Value> {
         self.parse_reference_or_val(i, |this, i, len, ofi| {
+            this.config.limits().check_length(len)?;
+
             //TODO: implications of this
             let (i, weak_keys) = be_u8(i)?;
 
@@ -549,7 +859,14 @@ impl AMF3Decoder {
 
             let id = ObjectId(ofi as _);
 
-            Ok((i, Value::Dictionary(id, pairs, weak_keys == 1)))
+            Ok((
+                i,
+                Value::Dictionary(Dictionary {
+                    id,
+                    entries: pairs,
+                    weak_keys: weak_keys == 1,
+                }),
+            ))
         })
     }
 
@@ -561,24 +878,28 @@ impl AMF3Decoder {
     }
 
     fn parse_element_xml<'a>(&mut self, i: &'a [u8], string: bool) -> AMFResult<'a, Value> {
-        self.parse_reference_or_val(i, |_this, i, len, _| {
-            let (i, data) = map_res(take(len as u32), std::str::from_utf8).parse(i)?;
-            Ok((i, Value::XML(data.into(), string)))
+        self.parse_reference_or_val(i, |this, i, len, _| {
+            let (j, data) = take(len as u32)(i)?;
+            let data = this.decode_string(i, data.to_vec())?;
+            Ok((j, Value::XML(data, string)))
         })
     }
 
-    fn read_type_marker<'a>(&self, i: &'a [u8]) -> AMFResult<'a, TypeMarker> {
-        let (i, type_) = be_u8(i)?;
-        if let Ok(type_) = TypeMarker::try_from(type_) {
-            Ok((i, type_))
-        } else {
-            Err(Err::Error(crate::errors::Error::UnsupportedType(type_)))
-        }
-    }
-
     /// Parse a single AMF3 element from the input
     pub fn parse_single_element<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Value> {
-        let (i, type_) = self.read_type_marker(i)?;
+        self.with_input(i, Self::parse_value)
+    }
+
+    fn parse_value<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Value> {
+        self.config.limits().check_depth(self.depth + 1)?;
+        self.depth += 1;
+        let res = self.parse_marker_value(i);
+        self.depth -= 1;
+        res
+    }
+
+    fn parse_marker_value<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Value> {
+        let (i, type_) = read_type_marker(i)?;
 
         match type_ {
             TypeMarker::Undefined => Ok((i, (Value::Undefined))),
@@ -615,11 +936,77 @@ impl AMF3Decoder {
         .parse(i)
     }
 
+    /// Parse a single element of an AMF3 body, followed by its padding
+    pub(crate) fn parse_body_element<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Element> {
+        self.with_input(i, |this, i| {
+            this.captured_remaining = false;
+            let (i, mut element) = this.parse_element(i)?;
+
+            if i.is_empty()
+                && this.captured_remaining
+                && strip_captured_padding(core::slice::from_mut(&mut element))
+            {
+                return Ok((i, element));
+            }
+
+            let (i, _) = tag(PADDING.as_slice())(i)?;
+            Ok((i, element))
+        })
+    }
+
     /// Parse an AMF3 body from a slice into a list of elements
     pub fn parse_body<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Vec<Element>> {
-        let (i, elements) =
-            separated_list0(tag(PADDING.as_slice()), |i| self.parse_element(i)).parse(i)?;
-        let (i, _) = tag(PADDING.as_slice())(i)?;
-        Ok((i, elements))
+        self.with_input(i, |this, i| {
+            this.captured_remaining = false;
+            let (i, mut elements) =
+                separated_list0(tag(PADDING.as_slice()), |i| this.parse_element(i)).parse(i)?;
+
+            // If the last element captured the rest of the body, then it has also captured the final padding
+            let i =
+                if i.is_empty() && this.captured_remaining && strip_captured_padding(&mut elements)
+                {
+                    i
+                } else {
+                    tag(PADDING.as_slice())(i)?.0
+                };
+
+            this.config
+                .duplicate_keys()
+                .apply(&mut elements, |e| &e.name)?;
+            Ok((i, elements))
+        })
     }
 }
+
+pub(crate) fn read_type_marker(i: &[u8]) -> AMFResult<'_, TypeMarker> {
+    let (i, type_) = be_u8(i)?;
+    if let Ok(type_) = TypeMarker::try_from(type_) {
+        Ok((i, type_))
+    } else {
+        Err(Err::Error(Error::UnsupportedType(type_)))
+    }
+}
+
+/// Remove the trailing body padding from an opaque externalized value captured as the last element of a body
+/// Returns false if the last element was not such a value
+fn strip_captured_padding(elements: &mut [Element]) -> bool {
+    if let Some(Value::Custom(custom)) = elements.last_mut().map(|e| &mut e.value) {
+        return strip_captured_custom_padding(&mut custom.custom_elements);
+    }
+    false
+}
+
+/// Remove the trailing body padding from the externalized elements of an opaque externalized value
+/// Returns false if the elements are not those of such a value
+pub(crate) fn strip_captured_custom_padding(custom: &mut [Element]) -> bool {
+    if let Some(Value::ByteArray(bytes)) = custom
+        .iter_mut()
+        .find(|e| e.name == OPAQUE_ELEMENT_NAME)
+        .map(|e| &mut e.value)
+        && bytes.ends_with(&PADDING)
+    {
+        bytes.truncate(bytes.len() - PADDING.len());
+        return true;
+    }
+    false
+}
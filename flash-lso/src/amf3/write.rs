@@ -1,16 +1,35 @@
 //! Handles encoding AMF3
 
 use crate::PADDING;
+use crate::alias::{AmfExternalizable, ExternalizableCodec};
 use crate::amf3::custom_encoder::CustomEncoder;
 use crate::amf3::element_cache::ElementCache;
 use crate::amf3::length::Length;
 use crate::amf3::type_marker::TypeMarker;
-use crate::types::{Attribute, ClassDefinition, Element, ObjectId, Value};
-use crate::write::WriteExt;
-use std::cell::RefCell;
-use std::collections::{BTreeMap, HashMap};
-use std::io::Result;
-use std::io::Write;
+use crate::errors::{EncodeError, ValidationError, ValidationErrors};
+use crate::prelude::*;
+use crate::types::{
+    AMFVersion, Attribute, ClassDefinition, Dictionary, Element, ObjectId, ObjectVector, Value,
+};
+use crate::validate::Validator;
+use crate::write::{EncodeOptions, IntegerOverflow, Sink, SinkRef, WriteExt};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::sync::Arc;
+
+type Result<T> = core::result::Result<T, EncodeError>;
+
+/// The smallest value of an AMF3 integer
+pub(crate) const MIN_INTEGER: i32 = -(1 << 28);
+
+/// The largest value of an AMF3 integer
+pub(crate) const MAX_INTEGER: i32 = (1 << 28) - 1;
+
+/// Whether a number can be written as an AMF3 integer without changing its value
+fn fits_in_integer(x: f64) -> bool {
+    (f64::from(MIN_INTEGER)..=f64::from(MAX_INTEGER)).contains(&x)
+        && f64::from(x as i32) == x
+        && !(x == 0.0 && x.is_sign_negative())
+}
 
 /// Handles encoding AMF3
 #[derive(Default)]
@@ -19,20 +38,74 @@ pub struct AMF3Encoder {
     string_reference_table: ElementCache<Vec<u8>>,
 
     /// The table used to cache repeated trait definitions
-    trait_reference_table: RefCell<Vec<ClassDefinition>>,
+    trait_reference_table: Vec<ClassDefinition>,
 
     /// The table used to cache repeated objects
     object_reference_table: ElementCache<Value>,
 
     /// Encoders used for handling externalized types
-    pub external_encoders: HashMap<String, Box<dyn CustomEncoder>>,
+    pub external_encoders: BTreeMap<String, Arc<dyn CustomEncoder>>,
+
+    object_id_to_reference: BTreeMap<ObjectId, (TypeMarker, usize)>,
 
-    object_id_to_reference: RefCell<BTreeMap<ObjectId, (TypeMarker, usize)>>,
+    options: EncodeOptions,
 }
 
 impl AMF3Encoder {
+    /// Create an encoder that uses the given options
+    pub fn new(options: EncodeOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// The options used by this encoder
+    pub fn options(&self) -> &EncodeOptions {
+        &self.options
+    }
+
+    /// Get a mutable reference to the options used by this encoder
+    pub fn options_mut(&mut self) -> &mut EncodeOptions {
+        &mut self.options
+    }
+
+    /// Create an encoder with the same registered encoders and options as this one, but empty reference tables
+    pub(crate) fn fresh(&self) -> Self {
+        Self {
+            external_encoders: self.external_encoders.clone(),
+            options: self.options,
+            ..Self::default()
+        }
+    }
+
+    /// The ids of the objects written so far, which can be referenced by `Value::Amf3ObjectReference`
+    pub(crate) fn written_objects(&self) -> BTreeSet<ObjectId> {
+        self.object_id_to_reference.keys().copied().collect()
+    }
+
+    /// Check that a value can be written by this encoder, reporting every problem found
+    ///
+    /// This is run by `Writer` and `ValueStreamWriter` before writing
+    pub fn validate(&self, value: &Value) -> core::result::Result<(), ValidationErrors> {
+        let mut validator = Validator::for_encoder(self, self.options);
+        validator.value(value, AMFVersion::AMF3);
+        validator.finish()
+    }
+
+    /// Register a custom encoder for external data formats
+    pub fn register_custom_encoder<T: CustomEncoder + Default + 'static>(&mut self, name: &str) {
+        self.external_encoders
+            .insert(name.to_string(), Arc::new(T::default()));
+    }
+
+    /// Register the encoder for an `AmfExternalizable` class, under its class alias
+    pub fn register_externalizable<T: AmfExternalizable>(&mut self) {
+        self.register_custom_encoder::<ExternalizableCodec<T>>(T::ALIAS);
+    }
+
     #[allow(clippy::unusual_byte_groupings)]
-    pub(crate) fn write_int<'a, 'b: 'a, W: Write + 'a>(
+    pub(crate) fn write_int<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
         &self,
         writer: &mut W,
         i: i32,
@@ -62,16 +135,16 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    fn write_byte_string<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_byte_string<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         s: &'b [u8],
     ) -> Result<()> {
-        let len = if !s.is_empty() {
+        let len = if !s.is_empty() && self.options.string_references {
             self.string_reference_table
                 .to_length(s.to_vec(), s.len() as u32)
         } else {
-            Length::Size(0)
+            Length::Size(s.len() as u32)
         };
 
         let only_length = len.is_reference() && !s.is_empty();
@@ -89,19 +162,26 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    fn write_string<'a, 'b: 'a, W: Write + 'a>(&'a self, writer: &mut W, s: &'b str) -> Result<()> {
-        self.write_byte_string(writer, s.as_bytes())
+    /// Write a string, using the string reference table if it has been written before
+    pub fn write_string<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
+        writer: &mut W,
+        s: &'b str,
+    ) -> Result<()> {
+        let bytes = self.options.string_encoding.encode_checked(s)?;
+        self.write_byte_string(writer, &bytes)
     }
 
-    fn write_type_marker<'a, 'b: 'a, W: Write + 'a>(
+    fn write_type_marker<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
         &self,
         writer: &mut W,
         s: TypeMarker,
     ) -> Result<()> {
-        writer.write_u8(s as u8)
+        writer.write_u8(s as u8)?;
+        Ok(())
     }
 
-    fn write_number_element<'a, 'b: 'a, W: Write + 'a>(
+    fn write_number_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
         &self,
         writer: &mut W,
         i: f64,
@@ -111,7 +191,7 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    fn write_boolean_element<'a, 'b: 'a, W: Write + 'a>(
+    fn write_boolean_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
         &self,
         writer: &mut W,
         b: bool,
@@ -123,26 +203,29 @@ impl AMF3Encoder {
         }
     }
 
-    fn write_string_element<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_string_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         s: &'b str,
     ) -> Result<()> {
         self.write_type_marker(writer, TypeMarker::String)?;
-        self.write_byte_string(writer, s.as_bytes())?;
+        self.write_string(writer, s)?;
         Ok(())
     }
 
-    fn write_null_element<'a, 'b: 'a, W: Write + 'a>(&self, writer: &mut W) -> Result<()> {
+    fn write_null_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(&self, writer: &mut W) -> Result<()> {
         self.write_type_marker(writer, TypeMarker::Null)
     }
 
-    fn write_undefined_element<'a, 'b: 'a, W: Write + 'a>(&self, writer: &mut W) -> Result<()> {
+    fn write_undefined_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &self,
+        writer: &mut W,
+    ) -> Result<()> {
         self.write_type_marker(writer, TypeMarker::Undefined)
     }
 
-    fn write_int_vector<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_int_vector<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         items: &'b [i32],
         fixed_length: bool,
@@ -165,8 +248,8 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    fn write_uint_vector<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_uint_vector<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         items: &'b [u32],
         fixed_length: bool,
@@ -189,8 +272,8 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    fn write_number_vector<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_number_vector<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         items: &'b [f64],
         fixed_length: bool,
@@ -213,8 +296,8 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    fn write_date_element<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_date_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         time: f64,
     ) -> Result<()> {
@@ -230,18 +313,25 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    fn write_integer_element<'a, 'b: 'a, W: Write + 'a>(
+    fn write_integer_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
         &self,
         writer: &mut W,
         i: i32,
     ) -> Result<()> {
+        if !(MIN_INTEGER..=MAX_INTEGER).contains(&i) {
+            return match self.options.integer_overflow {
+                IntegerOverflow::Promote => self.write_number_element(writer, f64::from(i)),
+                IntegerOverflow::Reject => Err(ValidationError::IntegerOutOfRange(i).into()),
+            };
+        }
+
         self.write_type_marker(writer, TypeMarker::Integer)?;
         self.write_int(writer, i)?;
         Ok(())
     }
 
-    fn write_byte_array_element<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_byte_array_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         bytes: &'b [u8],
     ) -> Result<()> {
@@ -257,12 +347,13 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    fn write_xml_element<'a, 'b: 'a, W: Write + 'a>(
+    fn write_xml_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
         &self,
         writer: &mut W,
-        bytes: &'b str,
+        content: &'b str,
         string: bool,
     ) -> Result<()> {
+        let bytes = self.options.string_encoding.encode_checked(content)?;
         let len = Length::Size(bytes.len() as u32);
 
         if string {
@@ -273,26 +364,41 @@ impl AMF3Encoder {
 
         len.write(writer, self)?;
         if len.is_size() {
-            writer.write_all(bytes.as_bytes())?;
+            writer.write_all(&bytes)?;
         }
         Ok(())
     }
 
-    fn write_class_definition<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_class_definition<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         class_def: &'b ClassDefinition,
     ) -> Result<()> {
-        self.write_byte_string(writer, class_def.name.as_bytes())?;
+        self.write_string(writer, &class_def.name)?;
         for p in &class_def.static_properties {
             self.write_string(writer, p)?;
         }
         Ok(())
     }
 
+    fn write_external<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
+        writer: &mut W,
+        custom_props: &'b [Element],
+        def: &'b ClassDefinition,
+    ) -> Result<()> {
+        let encoder = self
+            .external_encoders
+            .get(&def.name)
+            .map(Arc::clone)
+            .ok_or_else(|| ValidationError::MissingCustomEncoder(def.name.clone()))?;
+
+        encoder.encode(&mut SinkRef(writer), custom_props, &Some(def.clone()), self)
+    }
+
     //TODO: conds should be common somehwere
-    fn write_trait_reference<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_trait_reference<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         index: u32,
         children: &'b [Element],
@@ -304,15 +410,7 @@ impl AMF3Encoder {
 
         self.write_int(writer, size as i32)?;
         if def.attributes.contains(Attribute::External) {
-            if let Some(encoder) = self.external_encoders.get(&def.name) {
-                writer.write_all(&encoder.encode(
-                    custom_props.expect("Custom encoder, missing custom props"),
-                    &Some(def.clone()),
-                    self,
-                ))?;
-            } else {
-                unimplemented!();
-            }
+            self.write_external(writer, custom_props.unwrap_or_default(), def)?;
         }
 
         if !def.attributes.contains(Attribute::External) {
@@ -333,7 +431,7 @@ impl AMF3Encoder {
 
                 for c in children {
                     if !def.static_properties.contains(&c.name) {
-                        self.write_byte_string(writer, c.name.as_bytes())?;
+                        self.write_string(writer, &c.name)?;
                         self.write_value_element(writer, &c.value)?;
                     }
                 }
@@ -344,8 +442,8 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    fn write_object_reference<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_object_reference<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         index: u32,
     ) -> Result<()> {
@@ -354,8 +452,8 @@ impl AMF3Encoder {
         self.write_int(writer, size as i32)
     }
 
-    fn write_object_full<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_object_full<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         custom_props: Option<&'b [Element]>,
         children: &'b [Element],
@@ -379,19 +477,11 @@ impl AMF3Encoder {
             << 1)
             | 1u32;
 
-        self.trait_reference_table.borrow_mut().push(def.clone());
+        self.trait_reference_table.push(def.clone());
         self.write_int(writer, size as i32)?;
         self.write_class_definition(writer, def)?;
         if def.attributes.contains(Attribute::External) {
-            if let Some(encoder) = self.external_encoders.get(&def.name) {
-                writer.write_all(&encoder.encode(
-                    custom_props.expect("Custom encode but missing custom props"),
-                    &Some(def.clone()),
-                    self,
-                ))?;
-            } else {
-                unimplemented!();
-            }
+            self.write_external(writer, custom_props.unwrap_or_default(), def)?;
         }
         if !def.attributes.contains(Attribute::External) {
             if def.attributes.is_empty() {
@@ -410,7 +500,7 @@ impl AMF3Encoder {
                 }
                 for c in children {
                     if !def.static_properties.contains(&c.name) {
-                        self.write_byte_string(writer, c.name.as_bytes())?;
+                        self.write_string(writer, &c.name)?;
                         self.write_value_element(writer, &c.value)?;
                     }
                 }
@@ -420,8 +510,8 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    fn write_object_element<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_object_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         id: ObjectId,
         children: &'b [Element],
@@ -441,18 +531,22 @@ impl AMF3Encoder {
         self.object_reference_table.store(obj.clone());
         if let Length::Reference(r) = self.object_reference_table.to_length(obj, 0) {
             self.object_id_to_reference
-                .borrow_mut()
                 .insert(id, (TypeMarker::Object, r));
         }
 
-        let def = class_def.clone().unwrap_or_default();
+        // Objects without a class, such as those from AMF0, are written as anonymous dynamic objects
+        let def = class_def.clone().unwrap_or_else(|| ClassDefinition {
+            name: String::new(),
+            attributes: Attribute::Dynamic.into(),
+            static_properties: Vec::new(),
+        });
         let def2 = def.clone();
 
-        let has_trait = self
-            .trait_reference_table
-            .borrow()
-            .iter()
-            .position(|cd| *cd == def);
+        let has_trait = if self.options.trait_references {
+            self.trait_reference_table.iter().position(|cd| *cd == def)
+        } else {
+            None
+        };
 
         self.write_type_marker(writer, TypeMarker::Object)?;
         if had_object.is_reference() {
@@ -481,8 +575,8 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    fn write_strict_array_element<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_strict_array_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         children: &'b [Value],
     ) -> Result<()> {
@@ -508,8 +602,8 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    fn write_ecma_array_element<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_ecma_array_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         dense: &'b [Value],
         assoc: &'b [Element],
@@ -532,62 +626,54 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    fn write_object_vector_element<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_object_vector_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
-        id: ObjectId,
-        items: &'b [Value],
-        type_name: &'b str,
-        fixed_length: bool,
+        vector: &'b ObjectVector,
     ) -> Result<()> {
-        let vo = Value::VectorObject(id, items.to_vec(), type_name.to_string(), fixed_length);
-        let len = self.object_reference_table.to_length(
-            Value::VectorObject(id, items.to_vec(), type_name.to_string(), fixed_length),
-            items.len() as u32,
-        );
+        let vo = Value::VectorObject(vector.clone());
+        let len = self
+            .object_reference_table
+            .to_length(vo.clone(), vector.items.len() as u32);
         self.object_reference_table.store(vo.clone());
         if let Length::Reference(r) = self.object_reference_table.to_length(vo, 0) {
             self.object_id_to_reference
-                .borrow_mut()
-                .insert(id, (TypeMarker::VectorObject, r));
+                .insert(vector.id, (TypeMarker::VectorObject, r));
         }
 
         self.write_type_marker(writer, TypeMarker::VectorObject)?;
         len.write(writer, self)?;
         if len.is_size() {
-            writer.write_u8(fixed_length as u8)?;
-            self.write_string(writer, type_name)?;
-            for i in items {
+            writer.write_u8(vector.fixed_length as u8)?;
+            self.write_string(writer, &vector.type_name)?;
+            for i in &vector.items {
                 self.write_value_element(writer, i)?;
             }
         }
         Ok(())
     }
 
-    fn write_dictionary_element<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
+    fn write_dictionary_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
-        id: ObjectId,
-        items: &'b [(Value, Value)],
-        weak_keys: bool,
+        dictionary: &'b Dictionary,
     ) -> Result<()> {
-        let dict = Value::Dictionary(id, items.to_vec(), weak_keys);
+        let dict = Value::Dictionary(dictionary.clone());
 
         let len = self
             .object_reference_table
-            .to_length(dict.clone(), items.len() as u32);
+            .to_length(dict.clone(), dictionary.entries.len() as u32);
         self.object_reference_table.store(dict.clone());
         if let Length::Reference(r) = self.object_reference_table.to_length(dict, 0) {
             self.object_id_to_reference
-                .borrow_mut()
-                .insert(id, (TypeMarker::Dictionary, r));
+                .insert(dictionary.id, (TypeMarker::Dictionary, r));
         }
 
         self.write_type_marker(writer, TypeMarker::Dictionary)?;
         len.write(writer, self)?;
         if len.is_size() {
-            writer.write_u8(weak_keys as u8)?;
-            for i in items {
+            writer.write_u8(dictionary.weak_keys as u8)?;
+            for i in &dictionary.entries {
                 self.write_value_element(writer, &i.0)?;
                 self.write_value_element(writer, &i.1)?;
             }
@@ -595,20 +681,24 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    pub(crate) fn write_value_element<'a, 'b: 'a, W: Write + 'a>(
-        &'b self,
+    /// Write a single AMF3 value, sharing the reference tables of this encoder
+    pub fn write_value_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         s: &'b Value,
     ) -> Result<()> {
         self.write_value(writer, s)
     }
 
-    fn write_value<'a, 'b: 'a, W: Write + 'a>(
-        &'b self,
+    fn write_value<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         s: &'b Value,
     ) -> Result<()> {
         match s {
+            Value::Number(x) if self.options.integer_numbers && fits_in_integer(*x) => {
+                self.write_integer_element(writer, *x as i32)
+            }
             Value::Number(x) => self.write_number_element(writer, *x),
             Value::Bool(b) => self.write_boolean_element(writer, *b),
             Value::String(s) => self.write_string_element(writer, s),
@@ -617,21 +707,19 @@ impl AMF3Encoder {
             }
             Value::Null => self.write_null_element(writer),
             Value::Undefined => self.write_undefined_element(writer),
-            Value::ECMAArray(id, dense, elements, _) => {
+            Value::ECMAArray(array) => {
                 self.object_reference_table.store(s.clone());
                 if let Length::Reference(r) = self.object_reference_table.to_length(s.clone(), 0) {
                     self.object_id_to_reference
-                        .borrow_mut()
-                        .insert(*id, (TypeMarker::Array, r));
+                        .insert(array.id, (TypeMarker::Array, r));
                 }
 
-                self.write_ecma_array_element(writer, dense, elements)
+                self.write_ecma_array_element(writer, &array.dense, &array.assoc)
             }
             Value::StrictArray(id, children) => {
                 self.object_reference_table.store(s.clone());
                 if let Length::Reference(r) = self.object_reference_table.to_length(s.clone(), 0) {
                     self.object_id_to_reference
-                        .borrow_mut()
                         .insert(*id, (TypeMarker::Array, r));
                 }
 
@@ -650,37 +738,34 @@ impl AMF3Encoder {
             Value::VectorDouble(items, fixed_length) => {
                 self.write_number_vector(writer, items, *fixed_length)
             }
-            Value::VectorObject(id, items, type_name, fixed_length) => {
-                self.write_object_vector_element(writer, *id, items, type_name, *fixed_length)
-            }
-            Value::Dictionary(id, kv, weak_keys) => {
-                self.write_dictionary_element(writer, *id, kv, *weak_keys)
-            }
+            Value::VectorObject(vector) => self.write_object_vector_element(writer, vector),
+            Value::Dictionary(dictionary) => self.write_dictionary_element(writer, dictionary),
 
-            Value::Custom(elements, dynamic_elements, def) => self.write_object_element(
+            Value::Custom(custom) => self.write_object_element(
                 writer,
                 ObjectId::INVALID,
-                dynamic_elements,
-                Some(elements),
-                def,
+                &custom.elements,
+                Some(&custom.custom_elements),
+                &custom.class_definition,
             ),
             Value::AMF3(e) => self.write_value_element(writer, e),
             Value::Unsupported => self.write_undefined_element(writer),
-            Value::Reference(_) => unimplemented!(),
+            Value::Reference(_) => {
+                Err(ValidationError::UnsupportedType("reference", AMFVersion::AMF3).into())
+            }
             Value::Amf3ObjectReference(id) => {
                 let (ty, r) = *self
                     .object_id_to_reference
-                    .borrow()
                     .get(id)
-                    .expect("Invalid reference");
+                    .ok_or(ValidationError::DanglingObjectReference(*id))?;
                 self.write_type_marker(writer, ty)?;
                 self.write_object_reference(writer, r as u32)
             }
         }
     }
 
-    fn write_element<'a, 'b: 'a, W: Write + 'a>(
-        &'b self,
+    fn write_element<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         element: &'b Element,
     ) -> Result<()> {
@@ -689,8 +774,8 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    fn write_element_and_padding<'a, 'b: 'a, W: Write + 'a>(
-        &'b self,
+    fn write_element_and_padding<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         element: &'b Element,
     ) -> Result<()> {
@@ -699,8 +784,8 @@ impl AMF3Encoder {
         Ok(())
     }
 
-    pub(crate) fn write_body<'a, 'b: 'a, W: Write + 'a>(
-        &'b self,
+    pub(crate) fn write_body<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+        &mut self,
         writer: &mut W,
         elements: &'b [Element],
     ) -> Result<()> {
@@ -714,6 +799,7 @@ impl AMF3Encoder {
 #[cfg(test)]
 mod write_number_tests {
     use crate::amf3::write::AMF3Encoder;
+    use crate::prelude::*;
 
     #[test]
     fn test_write_1byte_number() {
@@ -738,4 +824,17 @@ mod write_number_tests {
         e.write_int(&mut v, -268435455).expect("Test fail");
         assert_eq!(v, &[192, 128, 128, 1]);
     }
+
+    #[test]
+    fn write_length_out_of_range() {
+        use crate::amf3::length::{Length, MAX_LENGTH};
+
+        let e = AMF3Encoder::default();
+        let mut v = Vec::new();
+        Length::Size(MAX_LENGTH)
+            .write(&mut v, &e)
+            .expect("Test fail");
+        assert_eq!(v, &[0xFF, 0xFF, 0xFF, 0xFF]);
+        assert!(Length::Size(MAX_LENGTH + 1).write(&mut v, &e).is_err());
+    }
 }
@@ -0,0 +1,627 @@
+//! Converting an `Lso` between AMF0 and AMF3
+//!
+//! Values are mapped to the closest type of the other version, anything that can't be kept exactly is reported with
+//! the path to the value, such as `inventory.items[2].created`
+
+use crate::prelude::*;
+use crate::types::{
+    AMFVersion, Attribute, ClassDefinition, CustomObject, Dictionary, EcmaArray, Element, Lso,
+    ObjectId, ObjectValue, ObjectVector, Reference, Value,
+};
+use crate::validate::{Segment, is_amf0_type, path_string};
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+/// The result of converting an `Lso` to another version
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conversion {
+    /// The converted Lso
+    pub lso: Lso,
+
+    /// Every value that could not be converted exactly
+    pub losses: Vec<Loss>,
+}
+
+/// A value that could not be converted exactly, and the path to it
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Loss {
+    /// The element names and indices leading to the value
+    pub path: String,
+
+    /// What was lost
+    pub kind: LossKind,
+}
+
+impl fmt::Display for Loss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.kind)
+    }
+}
+
+/// What was lost when converting a value
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum LossKind {
+    /// The time zone of a date, AMF3 dates are always UTC
+    TimeZone(u16),
+
+    /// The length of an ECMA array that does not match its numbered elements, AMF3 arrays have no declared length
+    ArrayLength(u32),
+
+    /// The AMF0 unsupported type, converted to undefined
+    Unsupported,
+
+    /// Whether the class is dynamic and which properties are sealed, AMF0 typed objects only have a name
+    ClassTraits(String),
+
+    /// A reference that has no equivalent in the new version, converted to null
+    Reference,
+}
+
+impl fmt::Display for LossKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LossKind::TimeZone(offset) => write!(f, "Time zone offset {offset} was dropped"),
+            LossKind::ArrayLength(length) => write!(f, "Array length {length} was dropped"),
+            LossKind::Unsupported => write!(f, "Unsupported value was converted to undefined"),
+            LossKind::ClassTraits(name) => {
+                write!(f, "Traits of class `{name}` were reduced to its name")
+            }
+            LossKind::Reference => {
+                write!(f, "Reference could not be mapped and was converted to null")
+            }
+        }
+    }
+}
+
+impl Lso {
+    /// Convert the body of this Lso to the given version, reporting every value that could not be converted exactly
+    ///
+    /// AMF0 references become object references, typed objects become sealed classes and integers become numbers.
+    /// AMF3 only types are kept in AMF0 through the AVM+ switch marker
+    pub fn convert_to(&self, version: AMFVersion) -> Conversion {
+        let mut lso = self.clone();
+        lso.header.format_version = version;
+
+        let mut report = Report::default();
+        match (self.header.format_version, version) {
+            (AMFVersion::AMF0, AMFVersion::AMF3) => {
+                lso.body = ToAmf3::new(&mut report).elements(&self.body, true)
+            }
+            (AMFVersion::AMF3, AMFVersion::AMF0) => {
+                lso.body = ToAmf0::new(&mut report).elements(&self.body)
+            }
+            _ => {}
+        }
+
+        Conversion {
+            lso,
+            losses: report.losses,
+        }
+    }
+}
+
+/// The path to the value being converted, and the losses found so far
+#[derive(Default)]
+struct Report<'v> {
+    path: Vec<Segment<'v>>,
+    losses: Vec<Loss>,
+}
+
+impl Report<'_> {
+    fn loss(&mut self, kind: LossKind) {
+        let path = path_string(&self.path);
+        self.losses.push(Loss { path, kind });
+    }
+}
+
+/// What a value in the AMF0 reference table was converted to
+enum Resolved {
+    /// An object, referenced by id
+    Object(ObjectId),
+
+    /// A value without an id, which is copied
+    Copy(Value),
+}
+
+/// The id of a converted value, if it can be the target of an object reference
+fn object_id(value: &Value) -> Option<ObjectId> {
+    match value {
+        Value::Object { id, .. } | Value::StrictArray(id, _) | Value::Amf3ObjectReference(id) => {
+            Some(*id)
+        }
+        Value::ECMAArray(array) => Some(array.id),
+        Value::VectorObject(vector) => Some(vector.id),
+        Value::Dictionary(dictionary) => Some(dictionary.id),
+        _ => None,
+    }
+}
+
+/// Whether a value takes a place in the AMF3 object table without having an id of its own
+fn is_unnamed_object(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::Date(..)
+            | Value::XML(..)
+            | Value::ByteArray(_)
+            | Value::VectorInt(..)
+            | Value::VectorUInt(..)
+            | Value::VectorDouble(..)
+            | Value::Custom(_)
+    )
+}
+
+/// Converts AMF0 values, and AMF3 values embedded in them, to AMF3
+///
+/// New ids are given in the order of the AMF3 object table, as they are when the result is read back
+struct ToAmf3<'r, 'v> {
+    report: &'r mut Report<'v>,
+
+    /// What each value in the AMF0 reference table was converted to, in the order they are read
+    table: Vec<Option<Resolved>>,
+
+    /// The new ids of objects from embedded AMF3 values
+    ids: BTreeMap<ObjectId, ObjectId>,
+
+    next_id: i64,
+}
+
+impl<'r, 'v> ToAmf3<'r, 'v> {
+    fn new(report: &'r mut Report<'v>) -> Self {
+        Self {
+            report,
+            table: Vec::new(),
+            ids: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    fn elements(&mut self, elements: &'v [Element], amf0: bool) -> Vec<Element> {
+        elements
+            .iter()
+            .map(|element| {
+                self.report.path.push(Segment::Name(&element.name));
+                let value = self.value(&element.value, amf0);
+                self.report.path.pop();
+                Element::new(element.name.clone(), value)
+            })
+            .collect()
+    }
+
+    fn values(&mut self, values: &'v [Value], amf0: bool) -> Vec<Value> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                self.report.path.push(Segment::Index(index));
+                let value = self.value(value, amf0);
+                self.report.path.pop();
+                value
+            })
+            .collect()
+    }
+
+    /// Give an object a new id, recording it before its children so that they can reference it
+    fn object(&mut self, old: ObjectId, amf0: bool, index: Option<usize>) -> ObjectId {
+        let id = ObjectId(self.next_id);
+        self.next_id += 1;
+        if !amf0 && old != ObjectId::INVALID {
+            self.ids.insert(old, id);
+        }
+        if let Some(index) = index {
+            self.table[index] = Some(Resolved::Object(id));
+        }
+        id
+    }
+
+    fn reference(&mut self, resolved: Option<Value>) -> Value {
+        match resolved {
+            Some(value) => {
+                if is_unnamed_object(&value) {
+                    self.next_id += 1;
+                }
+                value
+            }
+            None => {
+                self.report.loss(LossKind::Reference);
+                Value::Null
+            }
+        }
+    }
+
+    fn value(&mut self, value: &'v Value, amf0: bool) -> Value {
+        // Values embedded through the AVM+ switch marker take a single place in the AMF0 reference table
+        let index = amf0.then(|| {
+            self.table.push(None);
+            self.table.len() - 1
+        });
+        let (value, amf0) = match value {
+            Value::AMF3(inner) => (&**inner, false),
+            value => (value, amf0 && is_amf0_type(value)),
+        };
+        if is_unnamed_object(value) {
+            self.next_id += 1;
+        }
+
+        let converted = match value {
+            Value::Object { id, data } => {
+                let id = self.object(*id, amf0, index);
+                let class_definition = match &data.class_definition {
+                    _ if !amf0 => data.class_definition.clone(),
+                    Some(def) => Some(ClassDefinition {
+                        static_properties: data.elements.iter().map(|e| e.name.clone()).collect(),
+                        ..ClassDefinition::default_with_name(def.name.clone())
+                    }),
+                    None => Some(ClassDefinition {
+                        attributes: Attribute::Dynamic.into(),
+                        ..ClassDefinition::default_with_name(String::new())
+                    }),
+                };
+                Value::Object {
+                    id,
+                    data: ObjectValue {
+                        elements: self.elements(&data.elements, amf0),
+                        class_definition,
+                    },
+                }
+            }
+            Value::ECMAArray(array) => {
+                let id = self.object(array.id, amf0, index);
+                let mut dense = self.values(&array.dense, amf0);
+                let mut assoc = array.assoc.as_slice();
+
+                // Flash Player writes the items of an AMF0 array as elements named by their index
+                let length = array.declared_len as usize;
+                if amf0 && length > 0 {
+                    let numbered = assoc.len() >= length
+                        && assoc[..length]
+                            .iter()
+                            .enumerate()
+                            .all(|(i, e)| e.name == i.to_string());
+                    if numbered {
+                        let (items, rest) = assoc.split_at(length);
+                        for item in items {
+                            self.report.path.push(Segment::Index(dense.len()));
+                            dense.push(self.value(&item.value, amf0));
+                            self.report.path.pop();
+                        }
+                        assoc = rest;
+                    } else {
+                        self.report.loss(LossKind::ArrayLength(array.declared_len));
+                    }
+                }
+
+                let assoc = self.elements(assoc, amf0);
+                if !amf0 {
+                    Value::ECMAArray(EcmaArray {
+                        id,
+                        dense,
+                        assoc,
+                        declared_len: array.declared_len,
+                    })
+                } else if assoc.is_empty() {
+                    // Written the same as an AMF3 dense array
+                    Value::StrictArray(id, dense)
+                } else {
+                    Value::ECMAArray(EcmaArray {
+                        id,
+                        dense,
+                        declared_len: assoc.len() as u32,
+                        assoc,
+                    })
+                }
+            }
+            Value::StrictArray(id, values) => {
+                let id = self.object(*id, amf0, index);
+                Value::StrictArray(id, self.values(values, amf0))
+            }
+            Value::VectorObject(vector) => {
+                let id = self.object(vector.id, amf0, index);
+                Value::VectorObject(ObjectVector {
+                    id,
+                    items: self.values(&vector.items, amf0),
+                    type_name: vector.type_name.clone(),
+                    fixed_length: vector.fixed_length,
+                })
+            }
+            Value::Dictionary(dictionary) => {
+                let id = self.object(dictionary.id, amf0, index);
+                let mut entries = Vec::with_capacity(dictionary.entries.len());
+                for (i, (key, value)) in dictionary.entries.iter().enumerate() {
+                    self.report.path.push(Segment::Index(i));
+                    self.report.path.push(Segment::Name("key"));
+                    let key = self.value(key, amf0);
+                    self.report.path.pop();
+                    self.report.path.push(Segment::Name("value"));
+                    let value = self.value(value, amf0);
+                    self.report.path.pop();
+                    self.report.path.pop();
+                    entries.push((key, value));
+                }
+                Value::Dictionary(Dictionary {
+                    id,
+                    entries,
+                    weak_keys: dictionary.weak_keys,
+                })
+            }
+            Value::Custom(custom) => Value::Custom(CustomObject {
+                custom_elements: self.elements(&custom.custom_elements, amf0),
+                elements: self.elements(&custom.elements, amf0),
+                class_definition: custom.class_definition.clone(),
+            }),
+            Value::Date(millis, time_zone) => {
+                if let Some(offset) = time_zone.filter(|offset| *offset != 0) {
+                    self.report.loss(LossKind::TimeZone(offset));
+                }
+                Value::Date(*millis, None)
+            }
+            Value::Unsupported => {
+                self.report.loss(LossKind::Unsupported);
+                Value::Undefined
+            }
+            Value::Reference(r) if amf0 => {
+                let resolved = match self.table.get(usize::from(r.index())) {
+                    Some(Some(Resolved::Object(id))) => Some(Value::Amf3ObjectReference(*id)),
+                    Some(Some(Resolved::Copy(value))) => Some(value.clone()),
+                    _ => None,
+                };
+                self.reference(resolved)
+            }
+            Value::Amf3ObjectReference(old) if !amf0 => {
+                let resolved = self.ids.get(old).map(|id| Value::Amf3ObjectReference(*id));
+                self.reference(resolved)
+            }
+            Value::Reference(_) | Value::Amf3ObjectReference(_) => self.reference(None),
+            value => value.clone(),
+        };
+
+        if let Some(index) = index
+            && self.table[index].is_none()
+        {
+            self.table[index] = Some(match object_id(&converted) {
+                Some(id) => Resolved::Object(id),
+                None => Resolved::Copy(converted.clone()),
+            });
+        }
+        converted
+    }
+}
+
+/// Converts AMF3 values to AMF0, embedding those without an AMF0 type through the AVM+ switch marker
+///
+/// Embedded values share one AMF3 object table, so their objects are given new ids in the order of that table
+struct ToAmf0<'r, 'v> {
+    report: &'r mut Report<'v>,
+
+    /// The number of AMF0 values converted so far, the index of the next in the reference table
+    values: usize,
+
+    /// The reference table index of each object converted to an AMF0 value
+    native: BTreeMap<ObjectId, usize>,
+
+    /// The new ids of objects kept as embedded AMF3 values
+    embedded: BTreeMap<ObjectId, ObjectId>,
+
+    next_id: i64,
+}
+
+impl<'r, 'v> ToAmf0<'r, 'v> {
+    fn new(report: &'r mut Report<'v>) -> Self {
+        Self {
+            report,
+            values: 0,
+            native: BTreeMap::new(),
+            embedded: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    fn elements(&mut self, elements: &'v [Element]) -> Vec<Element> {
+        elements
+            .iter()
+            .map(|element| {
+                self.report.path.push(Segment::Name(&element.name));
+                let value = self.value(&element.value);
+                self.report.path.pop();
+                Element::new(element.name.clone(), value)
+            })
+            .collect()
+    }
+
+    fn values(&mut self, values: &'v [Value]) -> Vec<Value> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                self.report.path.push(Segment::Index(index));
+                let value = self.value(value);
+                self.report.path.pop();
+                value
+            })
+            .collect()
+    }
+
+    fn native(&mut self, id: ObjectId, index: usize) {
+        if id != ObjectId::INVALID {
+            self.native.insert(id, index);
+        }
+    }
+
+    fn null(&mut self, kind: LossKind) -> Value {
+        self.report.loss(kind);
+        Value::Null
+    }
+
+    /// The AMF0 class of an object, which only has a name
+    fn class(
+        &mut self,
+        def: &Option<ClassDefinition>,
+        elements: &[Element],
+    ) -> Option<ClassDefinition> {
+        let def = def.as_ref().filter(|def| !def.name.is_empty())?;
+        let sealed = def
+            .static_properties
+            .iter()
+            .eq(elements.iter().map(|e| &e.name));
+        if !def.attributes.is_empty() || !sealed {
+            self.report.loss(LossKind::ClassTraits(def.name.clone()));
+        }
+        Some(ClassDefinition::default_with_name(def.name.clone()))
+    }
+
+    fn value(&mut self, value: &'v Value) -> Value {
+        let index = self.values;
+        self.values += 1;
+
+        match value {
+            Value::Integer(i) => Value::Number(f64::from(*i)),
+            Value::Object { id, data } => {
+                self.native(*id, index);
+                let class_definition = self.class(&data.class_definition, &data.elements);
+                Value::Object {
+                    id: ObjectId::INVALID,
+                    data: ObjectValue {
+                        elements: self.elements(&data.elements),
+                        class_definition,
+                    },
+                }
+            }
+            Value::ECMAArray(array) => {
+                self.native(array.id, index);
+                // AMF0 arrays store their items as elements named by their index
+                let mut assoc = Vec::with_capacity(array.dense.len() + array.assoc.len());
+                for (i, item) in array.dense.iter().enumerate() {
+                    self.report.path.push(Segment::Index(i));
+                    assoc.push(Element::new(i.to_string(), self.value(item)));
+                    self.report.path.pop();
+                }
+                assoc.extend(self.elements(&array.assoc));
+                Value::ECMAArray(EcmaArray {
+                    id: ObjectId::INVALID,
+                    dense: Vec::new(),
+                    assoc,
+                    declared_len: array.dense.len() as u32,
+                })
+            }
+            Value::StrictArray(id, values) => {
+                self.native(*id, index);
+                Value::StrictArray(ObjectId::INVALID, self.values(values))
+            }
+            Value::Date(millis, time_zone) => Value::Date(*millis, Some(time_zone.unwrap_or(0))),
+            Value::Amf3ObjectReference(id) => {
+                if let Some(&target) = self.native.get(id) {
+                    match u16::try_from(target) {
+                        Ok(target) => Value::Reference(Reference::new(target)),
+                        Err(_) => self.null(LossKind::Reference),
+                    }
+                } else if let Some(&id) = self.embedded.get(id) {
+                    Value::AMF3(Box::new(Value::Amf3ObjectReference(id)))
+                } else {
+                    self.null(LossKind::Reference)
+                }
+            }
+            Value::Reference(_) => self.null(LossKind::Reference),
+            Value::AMF3(inner) => Value::AMF3(Box::new(self.embed(inner))),
+            value if !is_amf0_type(value) => Value::AMF3(Box::new(self.embed(value))),
+            value => value.clone(),
+        }
+    }
+
+    /// Give an embedded object a new id
+    fn object(&mut self, old: ObjectId) -> ObjectId {
+        let id = ObjectId(self.next_id);
+        self.next_id += 1;
+        if old != ObjectId::INVALID {
+            self.embedded.insert(old, id);
+        }
+        id
+    }
+
+    /// Keep an AMF3 value as it is, except for references to objects that were converted to AMF0
+    fn embed(&mut self, value: &'v Value) -> Value {
+        if is_unnamed_object(value) {
+            self.next_id += 1;
+        }
+
+        match value {
+            Value::Object { id, data } => Value::Object {
+                id: self.object(*id),
+                data: ObjectValue {
+                    elements: self.embed_elements(&data.elements),
+                    class_definition: data.class_definition.clone(),
+                },
+            },
+            Value::ECMAArray(array) => Value::ECMAArray(EcmaArray {
+                id: self.object(array.id),
+                dense: self.embed_values(&array.dense),
+                assoc: self.embed_elements(&array.assoc),
+                declared_len: array.declared_len,
+            }),
+            Value::StrictArray(id, values) => {
+                Value::StrictArray(self.object(*id), self.embed_values(values))
+            }
+            Value::VectorObject(vector) => Value::VectorObject(ObjectVector {
+                id: self.object(vector.id),
+                items: self.embed_values(&vector.items),
+                type_name: vector.type_name.clone(),
+                fixed_length: vector.fixed_length,
+            }),
+            Value::Dictionary(dictionary) => {
+                let id = self.object(dictionary.id);
+                let mut entries = Vec::with_capacity(dictionary.entries.len());
+                for (i, (key, value)) in dictionary.entries.iter().enumerate() {
+                    self.report.path.push(Segment::Index(i));
+                    self.report.path.push(Segment::Name("key"));
+                    let key = self.embed(key);
+                    self.report.path.pop();
+                    self.report.path.push(Segment::Name("value"));
+                    let value = self.embed(value);
+                    self.report.path.pop();
+                    self.report.path.pop();
+                    entries.push((key, value));
+                }
+                Value::Dictionary(Dictionary {
+                    id,
+                    entries,
+                    weak_keys: dictionary.weak_keys,
+                })
+            }
+            Value::Custom(custom) => Value::Custom(CustomObject {
+                custom_elements: self.embed_elements(&custom.custom_elements),
+                elements: self.embed_elements(&custom.elements),
+                class_definition: custom.class_definition.clone(),
+            }),
+            Value::Amf3ObjectReference(id) => match self.embedded.get(id) {
+                Some(&id) => Value::Amf3ObjectReference(id),
+                None => self.null(LossKind::Reference),
+            },
+            Value::Reference(_) => self.null(LossKind::Reference),
+            Value::AMF3(inner) => self.embed(inner),
+            value => value.clone(),
+        }
+    }
+
+    fn embed_elements(&mut self, elements: &'v [Element]) -> Vec<Element> {
+        elements
+            .iter()
+            .map(|element| {
+                self.report.path.push(Segment::Name(&element.name));
+                let value = self.embed(&element.value);
+                self.report.path.pop();
+                Element::new(element.name.clone(), value)
+            })
+            .collect()
+    }
+
+    fn embed_values(&mut self, values: &'v [Value]) -> Vec<Value> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                self.report.path.push(Segment::Index(index));
+                let value = self.embed(value);
+                self.report.path.pop();
+                value
+            })
+            .collect()
+    }
+}
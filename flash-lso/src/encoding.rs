@@ -0,0 +1,85 @@
+//! Text encodings of strings, element names and class names
+//!
+//! Flash Player 6 and 7 wrote the strings of AMF0 files in the code page of the system, rather than UTF-8
+
+use crate::errors::{EncodeError, ValidationError};
+use crate::prelude::*;
+use alloc::borrow::Cow;
+
+/// The code pages that can be used with `StringEncoding::Legacy`
+#[cfg(feature = "encoding")]
+pub use encoding_rs;
+
+/// How the bytes of strings, element names and class names are converted to and from text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StringEncoding {
+    /// UTF-8, failing on invalid bytes
+    #[default]
+    Utf8,
+
+    /// UTF-8, replacing invalid bytes with U+FFFD
+    ///
+    /// The replaced bytes are lost when the strings are written
+    Utf8Lossy,
+
+    /// Each byte as the character with the same code point, so any bytes are written back exactly
+    RawBytes,
+
+    /// A legacy code page, such as Shift_JIS or windows-1252, replacing invalid bytes with U+FFFD
+    #[cfg(feature = "encoding")]
+    Legacy(&'static encoding_rs::Encoding),
+}
+
+impl StringEncoding {
+    /// Decode the given bytes, returning `None` if they are invalid in this encoding
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Option<Cow<'a, str>> {
+        match self {
+            StringEncoding::Utf8 => core::str::from_utf8(bytes).ok().map(Cow::Borrowed),
+            StringEncoding::Utf8Lossy => Some(String::from_utf8_lossy(bytes)),
+            StringEncoding::RawBytes => {
+                Some(Cow::Owned(bytes.iter().copied().map(char::from).collect()))
+            }
+            #[cfg(feature = "encoding")]
+            StringEncoding::Legacy(encoding) => Some(encoding.decode_without_bom_handling(bytes).0),
+        }
+    }
+
+    /// Decode the given bytes, reusing the allocation when they are valid UTF-8
+    #[cfg(feature = "amf3")]
+    pub(crate) fn decode_owned(&self, bytes: Vec<u8>) -> Option<String> {
+        match self {
+            StringEncoding::Utf8 => String::from_utf8(bytes).ok(),
+            _ => self.decode(&bytes).map(Cow::into_owned),
+        }
+    }
+
+    /// Encode the given text, returning `None` if it has characters that this encoding can not represent
+    pub fn encode<'a>(&self, text: &'a str) -> Option<Cow<'a, [u8]>> {
+        match self {
+            StringEncoding::Utf8 | StringEncoding::Utf8Lossy => {
+                Some(Cow::Borrowed(text.as_bytes()))
+            }
+            StringEncoding::RawBytes => text
+                .chars()
+                .map(|c| u8::try_from(c).ok())
+                .collect::<Option<Vec<u8>>>()
+                .map(Cow::Owned),
+            #[cfg(feature = "encoding")]
+            StringEncoding::Legacy(encoding) => {
+                let (bytes, _, had_errors) = encoding.encode(text);
+                (!had_errors).then_some(bytes)
+            }
+        }
+    }
+
+    /// Encode the given text, failing with `ValidationError::UnmappableString` if this encoding can not represent it
+    pub(crate) fn encode_checked<'a>(&self, text: &'a str) -> Result<Cow<'a, [u8]>, EncodeError> {
+        self.encode(text)
+            .ok_or_else(|| ValidationError::UnmappableString(text.to_string()).into())
+    }
+
+    /// Whether every string can be encoded
+    pub(crate) fn is_unicode(&self) -> bool {
+        matches!(self, StringEncoding::Utf8 | StringEncoding::Utf8Lossy)
+    }
+}
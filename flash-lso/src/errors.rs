@@ -1,3 +1,5 @@
+use crate::prelude::*;
+use crate::types::{AMFVersion, ObjectId};
 use nom::error::{ErrorKind, FromExternalError, ParseError};
 use thiserror::Error;
 
@@ -30,8 +32,221 @@ pub enum Error<'a> {
     ObjectMissingFromReferenceTable(u64),
 
     /// An unknown IO error occured
+    #[cfg(feature = "std")]
     #[error("IO error: {0}")]
     IoError(String, std::io::ErrorKind),
+
+    /// An externalizable class was read, but no custom decoder is registered for it
+    #[error(
+        "No custom decoder registered for externalizable class `{class_name}` at offset {offset}"
+    )]
+    UnknownExternalizable {
+        /// The name of the externalizable class
+        class_name: String,
+
+        /// The offset of the externalized data, from the start of the input
+        offset: usize,
+    },
+
+    /// A string was not valid UTF-8
+    #[error("Invalid string")]
+    InvalidString,
+
+    /// An object has more than one element with the same name, with `DuplicateKeys::Reject`
+    #[error("Duplicate key: {0}")]
+    DuplicateKey(String),
+
+    /// A limit of `DecodeLimits` was exceeded, named by its field
+    #[error("Decode limit exceeded: {0}")]
+    LimitExceeded(&'static str),
+
+    /// An error occurred while encoding
+    #[error("Encode error: {0}")]
+    Encode(#[from] EncodeError),
+
+    /// A value could not be converted to a registered class
+    #[error("Convert error: {0}")]
+    Convert(#[from] ConvertError),
+}
+
+impl Error<'_> {
+    /// Convert this error into one that does not borrow from the input
+    ///
+    /// Nom errors lose the remaining input that they failed on
+    pub fn into_owned(self) -> Error<'static> {
+        match self {
+            Error::OutOfBounds => Error::OutOfBounds,
+            Error::InvalidReference(r) => Error::InvalidReference(r),
+            Error::UnsupportedType(t) => Error::UnsupportedType(t),
+            Error::Nom(_, kind) => Error::Nom(&[], kind),
+            Error::PacketTooLarge => Error::PacketTooLarge,
+            Error::ObjectMissingFromReferenceTable(id) => {
+                Error::ObjectMissingFromReferenceTable(id)
+            }
+            #[cfg(feature = "std")]
+            Error::IoError(msg, kind) => Error::IoError(msg, kind),
+            Error::UnknownExternalizable { class_name, offset } => {
+                Error::UnknownExternalizable { class_name, offset }
+            }
+            Error::InvalidString => Error::InvalidString,
+            Error::DuplicateKey(name) => Error::DuplicateKey(name),
+            Error::LimitExceeded(limit) => Error::LimitExceeded(limit),
+            Error::Encode(e) => Error::Encode(e),
+            Error::Convert(e) => Error::Convert(e),
+        }
+    }
+}
+
+/// Enum for representing encoding errors
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum EncodeError {
+    /// An IO error occurred while writing the output
+    #[cfg(feature = "std")]
+    #[error("IO error: {0}")]
+    IoError(String, std::io::ErrorKind),
+
+    /// The output sink failed to write the encoded data
+    #[error("Failed to write output: {0}")]
+    Sink(String),
+
+    /// An externalized value is missing an element that its custom encoder requires
+    #[error("Externalized value is missing required element `{0}`")]
+    MissingElement(String),
+
+    /// A value failed validation before it was written
+    #[error("Invalid value: {0}")]
+    Invalid(#[from] ValidationErrors),
+
+    /// A value that can not be encoded was found while writing
+    #[error("Unencodable value: {0}")]
+    Unencodable(#[from] ValidationError),
+
+    /// A typed value was written, but its type is not registered in the class registry
+    #[error("Typed value has no registered class alias")]
+    UnregisteredType,
+
+    /// A value could not be converted to the type required to write it
+    #[error("Convert error: {0}")]
+    Convert(#[from] ConvertError),
+}
+
+/// A problem that stops a value from being encoded
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum ValidationError {
+    /// An AMF0 reference points to an object that is not written before it
+    #[error("Reference to object {0}, which is not written before it")]
+    DanglingReference(u16),
+
+    /// An AMF3 object reference points to an object that is not written before it
+    #[error("Reference to object id {}, which is not written before it", .0.0)]
+    DanglingObjectReference(ObjectId),
+
+    /// The value has a type that can not be written in the given AMF version
+    #[error("{0} values can not be written as {1}")]
+    UnsupportedType(&'static str, AMFVersion),
+
+    /// An AMF3 integer does not fit in 29 bits
+    #[error("Integer {0} does not fit in 29 bits")]
+    IntegerOutOfRange(i32),
+
+    /// A string, array or vector is too long for its length to fit in a U29
+    #[error("Length {0} does not fit in 28 bits")]
+    LengthOutOfRange(usize),
+
+    /// An externalizable class has no custom encoder registered for it
+    #[error("No custom encoder registered for externalizable class `{0}`")]
+    MissingCustomEncoder(String),
+
+    /// An AMF0 string is longer than 65535 bytes, and long strings are disabled
+    #[error("String of {0} bytes is too long to be written")]
+    StringTooLong(usize),
+
+    /// A string has characters that the string encoding can not represent
+    #[error("`{0}` can not be represented in the string encoding")]
+    UnmappableString(String),
+
+    /// The name of an Lso is not accepted by Flash Player
+    #[error("Name `{0}` is not accepted by Flash Player")]
+    InvalidHeaderName(String),
+}
+
+/// A validation problem, and the path to the value it was found in
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ValidationIssue {
+    /// The element names and indices leading to the value, empty for the header
+    pub path: String,
+
+    /// The problem with the value
+    pub error: ValidationError,
+}
+
+impl core::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.error)
+        } else {
+            write!(f, "{}: {}", self.path, self.error)
+        }
+    }
+}
+
+/// Every problem found when validating a value
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ValidationErrors(pub Vec<ValidationIssue>);
+
+impl core::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (i, issue) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{issue}")?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for ValidationErrors {}
+
+/// Enum for representing errors converting between a `Value` and a Rust type
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum ConvertError {
+    /// The value was not of the expected type
+    #[error("Expected a value of type {0}")]
+    UnexpectedType(&'static str),
+
+    /// A required property was not present on an object
+    #[error("Missing property `{0}`")]
+    MissingProperty(String),
+
+    /// The object was not an instance of the expected class
+    #[error("Expected an object of class `{expected}`, found `{found}`")]
+    UnexpectedClass {
+        /// The expected class alias
+        expected: String,
+
+        /// The class name of the object
+        found: String,
+    },
+
+    /// A number was not representable in the target type
+    #[error("Number out of range")]
+    OutOfRange,
+
+    /// A date was NaN or outside of the range of the target type
+    #[error("Invalid date")]
+    InvalidDate,
+
+    /// The content of an XML value could not be parsed
+    #[error("Invalid XML: {0}")]
+    InvalidXml(String),
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for EncodeError {
+    fn from(e: std::io::Error) -> Self {
+        EncodeError::IoError(e.to_string(), e.kind())
+    }
 }
 
 impl<'a> ParseError<&'a [u8]> for Error<'a> {
@@ -49,3 +264,47 @@ impl<'a, E> FromExternalError<&'a [u8], E> for Error<'a> {
         Error::Nom(input, kind)
     }
 }
+
+/// Enum for representing errors when reading or writing asynchronously
+#[cfg(feature = "tokio")]
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum AsyncError {
+    /// An IO error occurred while reading the input or writing the output
+    #[error("IO error: {0}")]
+    IoError(String, std::io::ErrorKind),
+
+    /// The data could not be decoded or encoded
+    #[error("{0}")]
+    Amf(Error<'static>),
+}
+
+#[cfg(feature = "tokio")]
+impl From<std::io::Error> for AsyncError {
+    fn from(e: std::io::Error) -> Self {
+        AsyncError::IoError(e.to_string(), e.kind())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl From<Error<'_>> for AsyncError {
+    fn from(e: Error<'_>) -> Self {
+        AsyncError::Amf(e.into_owned())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl From<nom::Err<Error<'_>>> for AsyncError {
+    fn from(e: nom::Err<Error<'_>>) -> Self {
+        match e {
+            nom::Err::Error(e) | nom::Err::Failure(e) => e.into(),
+            nom::Err::Incomplete(_) => AsyncError::Amf(Error::OutOfBounds),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl From<EncodeError> for AsyncError {
+    fn from(e: EncodeError) -> Self {
+        AsyncError::Amf(Error::Encode(e))
+    }
+}
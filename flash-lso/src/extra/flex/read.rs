@@ -1,14 +1,16 @@
 //! Handles decoding of flex types
 
 use crate::amf3::custom_encoder::CustomDecoder;
-use crate::amf3::read::AMF3Decoder;
+use crate::amf3::read::{AMF3Decoder, DecoderConfig};
 use crate::extra::flex::{
     BODY_FLAG, CLIENT_ID_BYTES_FLAG, CLIENT_ID_FLAG, CORRELATION_ID_BYTES_FLAG,
     CORRELATION_ID_FLAG, DESTINATION_ID_FLAG, HEADERS_FLAG, MESSAGE_ID_BYTES_FLAG, MESSAGE_ID_FLAG,
     NEXT_FLAG, OPERATION_FLAG, TIMESTAMP_FLAG, TTL_FLAG,
 };
 use crate::nom_utils::AMFResult;
+use crate::prelude::*;
 use crate::types::Element;
+use alloc::format;
 use nom::number::complete::be_u8;
 
 fn parse_abstract_message_flags(i: &[u8]) -> AMFResult<'_, Vec<u8>> {
@@ -276,6 +278,11 @@ fn parse_object_proxy<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<'a,
 /// Register the flex decoders into the given AMF3Decoder
 #[inline]
 pub fn register_decoders(decoder: &mut AMF3Decoder) {
+    register_config_decoders(decoder.config_mut());
+}
+
+/// Register the flex decoders into the given DecoderConfig
+pub fn register_config_decoders(decoder: &mut DecoderConfig) {
     #[derive(Default)]
     struct FlexAbstractMessageParser;
     impl CustomDecoder for FlexAbstractMessageParser {
@@ -2,72 +2,57 @@
 
 use crate::amf3::custom_encoder::CustomEncoder;
 use crate::amf3::write::AMF3Encoder;
+use crate::errors::EncodeError;
 use crate::extra::flex::{
     BODY_FLAG, CLIENT_ID_BYTES_FLAG, CLIENT_ID_FLAG, CORRELATION_ID_BYTES_FLAG,
     CORRELATION_ID_FLAG, DESTINATION_ID_FLAG, HEADERS_FLAG, MESSAGE_ID_BYTES_FLAG, MESSAGE_ID_FLAG,
     NEXT_FLAG, OPERATION_FLAG, TIMESTAMP_FLAG, TTL_FLAG,
 };
+use crate::prelude::*;
 use crate::types::{ClassDefinition, Element};
-use crate::write::WriteExt;
-use std::io::Write;
+use crate::write::{Sink, WriteExt};
+use alloc::format;
 
+#[derive(Default)]
 struct ArrayCollection;
 
 impl CustomEncoder for ArrayCollection {
     fn encode(
         &self,
+        writer: &mut dyn Sink,
         elements: &[Element],
         _class_def: &Option<ClassDefinition>,
-        encoder: &AMF3Encoder,
-    ) -> Vec<u8> {
-        let mut v = Vec::new();
-        self.do_encode(&mut v, elements, encoder)
-            .expect("Failed to encode");
-        v
-    }
-}
-
-impl ArrayCollection {
-    fn do_encode<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
-        writer: &mut W,
-        elements: &'b [Element],
-        encoder: &'a AMF3Encoder,
-    ) -> std::io::Result<()> {
-        let data = elements.first().expect("No elements to encode");
+        encoder: &mut AMF3Encoder,
+    ) -> Result<(), EncodeError> {
+        let data = elements
+            .first()
+            .ok_or_else(|| EncodeError::MissingElement("data".to_string()))?;
         encoder.write_value_element(writer, &data.value)
     }
 }
 
+#[derive(Default)]
 struct ObjectProxy;
 
 impl CustomEncoder for ObjectProxy {
     fn encode(
         &self,
+        writer: &mut dyn Sink,
         elements: &[Element],
         _class_def: &Option<ClassDefinition>,
-        encoder: &AMF3Encoder,
-    ) -> Vec<u8> {
-        let mut v = Vec::new();
-        self.do_encode(&mut v, elements, encoder)
-            .expect("Failed to encode ObjectProxy");
-        v
-    }
-}
-
-impl ObjectProxy {
-    fn do_encode<'a, 'b: 'a, W: Write + 'a>(
-        &'a self,
-        writer: &mut W,
-        elements: &'b [Element],
-        encoder: &'a AMF3Encoder,
-    ) -> std::io::Result<()> {
-        let data = elements.first().expect("No element to encode");
+        encoder: &mut AMF3Encoder,
+    ) -> Result<(), EncodeError> {
+        let data = elements
+            .first()
+            .ok_or_else(|| EncodeError::MissingElement("object".to_string()))?;
         encoder.write_value_element(writer, &data.value)
     }
 }
 
-fn write_flags<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, flags: &'a [u8]) -> std::io::Result<()> {
+fn write_flags<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
+    writer: &mut W,
+    flags: &'a [u8],
+) -> Result<(), EncodeError> {
     for (index, flag) in flags.iter().enumerate() {
         if index == flags.len() {
             writer.write_u8(*flag & !NEXT_FLAG)?;
@@ -78,26 +63,26 @@ fn write_flags<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, flags: &'a [u8]) -> st
     Ok(())
 }
 
+#[derive(Default)]
 struct AbstractMessage;
 
 impl CustomEncoder for AbstractMessage {
     fn encode(
         &self,
+        writer: &mut dyn Sink,
         elements: &[Element],
         _class_def: &Option<ClassDefinition>,
-        encoder: &AMF3Encoder,
-    ) -> Vec<u8> {
-        let mut v = Vec::new();
-        write_abstract_message(&mut v, elements, encoder).expect("Failed to write AbstractMessage");
-        v
+        encoder: &mut AMF3Encoder,
+    ) -> Result<(), EncodeError> {
+        write_abstract_message(writer, elements, encoder)
     }
 }
 
-fn write_abstract_message<'a, 'b: 'a, W: Write + 'a>(
+fn write_abstract_message<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
     writer: &mut W,
     elements: &'b [Element],
-    encoder: &'a AMF3Encoder,
-) -> std::io::Result<()> {
+    encoder: &mut AMF3Encoder,
+) -> Result<(), EncodeError> {
     let mut flags = Vec::new();
     let mut new_elements = Vec::new();
     {
@@ -242,26 +227,26 @@ fn write_abstract_message<'a, 'b: 'a, W: Write + 'a>(
     Ok(())
 }
 
+#[derive(Default)]
 struct AsyncMessage;
 
 impl CustomEncoder for AsyncMessage {
     fn encode(
         &self,
+        writer: &mut dyn Sink,
         elements: &[Element],
         _class_def: &Option<ClassDefinition>,
-        encoder: &AMF3Encoder,
-    ) -> Vec<u8> {
-        let mut v = Vec::new();
-        write_async_message(&mut v, elements, encoder).expect("Failed to write AsyncMessage");
-        v
+        encoder: &mut AMF3Encoder,
+    ) -> Result<(), EncodeError> {
+        write_async_message(writer, elements, encoder)
     }
 }
 
-fn write_async_message<'a, 'b: 'a, W: Write + 'a>(
+fn write_async_message<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
     writer: &mut W,
     elements: &'b [Element],
-    encoder: &'a AMF3Encoder,
-) -> std::io::Result<()> {
+    encoder: &mut AMF3Encoder,
+) -> Result<(), EncodeError> {
     let mut flags = Vec::new();
     let mut new_elements = Vec::new();
     {
@@ -336,27 +321,26 @@ fn write_async_message<'a, 'b: 'a, W: Write + 'a>(
     Ok(())
 }
 
+#[derive(Default)]
 struct AcknowledgeMessage;
 
 impl CustomEncoder for AcknowledgeMessage {
     fn encode(
         &self,
+        writer: &mut dyn Sink,
         elements: &[Element],
         _class_def: &Option<ClassDefinition>,
-        encoder: &AMF3Encoder,
-    ) -> Vec<u8> {
-        let mut v = Vec::new();
-        write_acknowledge_message(&mut v, elements, encoder)
-            .expect("Failed to write acknowledge message");
-        v
+        encoder: &mut AMF3Encoder,
+    ) -> Result<(), EncodeError> {
+        write_acknowledge_message(writer, elements, encoder)
     }
 }
 
-fn write_acknowledge_message<'a, 'b: 'a, W: Write + 'a>(
+fn write_acknowledge_message<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
     writer: &mut W,
     elements: &'b [Element],
-    encoder: &'a AMF3Encoder,
-) -> std::io::Result<()> {
+    encoder: &mut AMF3Encoder,
+) -> Result<(), EncodeError> {
     let mut flags = Vec::new();
     let mut new_elements = Vec::new();
     {
@@ -396,26 +380,26 @@ fn write_acknowledge_message<'a, 'b: 'a, W: Write + 'a>(
     Ok(())
 }
 
+#[derive(Default)]
 struct CommandMessage;
 
 impl CustomEncoder for CommandMessage {
     fn encode(
         &self,
+        writer: &mut dyn Sink,
         elements: &[Element],
         _class_def: &Option<ClassDefinition>,
-        encoder: &AMF3Encoder,
-    ) -> Vec<u8> {
-        let mut v = Vec::new();
-        write_command_message(&mut v, elements, encoder).expect("Failed to encode cmd message");
-        v
+        encoder: &mut AMF3Encoder,
+    ) -> Result<(), EncodeError> {
+        write_command_message(writer, elements, encoder)
     }
 }
 
-fn write_command_message<'a, 'b: 'a, W: Write + 'a>(
+fn write_command_message<'a, 'b: 'a, W: Sink + ?Sized + 'a>(
     writer: &mut W,
     elements: &'b [Element],
-    encoder: &'a AMF3Encoder,
-) -> std::io::Result<()> {
+    encoder: &mut AMF3Encoder,
+) -> Result<(), EncodeError> {
     let mut flags = Vec::new();
     let mut new_elements = Vec::new();
     {
@@ -483,68 +467,18 @@ fn write_command_message<'a, 'b: 'a, W: Write + 'a>(
 /// Register the flex encoders into the given AMF3Encoder
 #[inline]
 pub fn register_encoders(encoder: &mut AMF3Encoder) {
-    encoder.external_encoders.insert(
-        "flex.messaging.io.ArrayCollection".to_string(),
-        Box::new(ArrayCollection {}),
-    );
-
-    encoder.external_encoders.insert(
-        "flex.messaging.io.ArrayList".to_string(),
-        Box::new(ArrayCollection {}),
-    );
-
-    encoder.external_encoders.insert(
-        "flex.messaging.io.AbstractMessage".to_string(),
-        Box::new(AbstractMessage {}),
-    );
-
-    encoder.external_encoders.insert(
-        "flex.messaging.io.AsyncMessage".to_string(),
-        Box::new(AsyncMessage {}),
-    );
-
-    encoder.external_encoders.insert(
-        "flex.messaging.io.AsyncMessageExt".to_string(),
-        Box::new(AsyncMessage {}),
-    );
-
-    encoder.external_encoders.insert(
-        "flex.messaging.io.AcknowledgeMessage".to_string(),
-        Box::new(AcknowledgeMessage {}),
-    );
-
-    encoder.external_encoders.insert(
-        "flex.messaging.io.AcknowledgeMessageExt".to_string(),
-        Box::new(AcknowledgeMessage {}),
-    );
-
-    encoder.external_encoders.insert(
-        "flex.messaging.io.CommandMessage".to_string(),
-        Box::new(CommandMessage {}),
-    );
-
-    encoder.external_encoders.insert(
-        "flex.messaging.io.CommandMessageExt".to_string(),
-        Box::new(CommandMessage {}),
-    );
-
-    encoder.external_encoders.insert(
-        "flex.messaging.io.ErrorMessage".to_string(),
-        Box::new(AcknowledgeMessage {}),
-    );
-
-    encoder.external_encoders.insert(
-        "flex.messaging.io.ObjectProxy".to_string(),
-        Box::new(ObjectProxy {}),
-    );
-
-    encoder.external_encoders.insert(
-        "flex.messaging.io.ManagedObjectProxy".to_string(),
-        Box::new(ObjectProxy {}),
-    );
-
-    encoder.external_encoders.insert(
-        "flex.messaging.io.SerializationProxy".to_string(),
-        Box::new(ObjectProxy {}),
-    );
+    encoder.register_custom_encoder::<ArrayCollection>("flex.messaging.io.ArrayCollection");
+    encoder.register_custom_encoder::<ArrayCollection>("flex.messaging.io.ArrayList");
+    encoder.register_custom_encoder::<AbstractMessage>("flex.messaging.io.AbstractMessage");
+    encoder.register_custom_encoder::<AsyncMessage>("flex.messaging.io.AsyncMessage");
+    encoder.register_custom_encoder::<AsyncMessage>("flex.messaging.io.AsyncMessageExt");
+    encoder.register_custom_encoder::<AcknowledgeMessage>("flex.messaging.io.AcknowledgeMessage");
+    encoder
+        .register_custom_encoder::<AcknowledgeMessage>("flex.messaging.io.AcknowledgeMessageExt");
+    encoder.register_custom_encoder::<CommandMessage>("flex.messaging.io.CommandMessage");
+    encoder.register_custom_encoder::<CommandMessage>("flex.messaging.io.CommandMessageExt");
+    encoder.register_custom_encoder::<AcknowledgeMessage>("flex.messaging.io.ErrorMessage");
+    encoder.register_custom_encoder::<ObjectProxy>("flex.messaging.io.ObjectProxy");
+    encoder.register_custom_encoder::<ObjectProxy>("flex.messaging.io.ManagedObjectProxy");
+    encoder.register_custom_encoder::<ObjectProxy>("flex.messaging.io.SerializationProxy");
 }
@@ -0,0 +1,221 @@
+//! Decoding the top level elements of an Lso on demand
+//!
+//! Opening an Lso only records the name and position of each element, so a few elements can be read from a large file
+//! without keeping every value in memory
+
+use crate::amf0::read::AMF0Decoder;
+#[cfg(feature = "amf3")]
+use crate::amf3::read::{AMF3Decoder, TableState};
+use crate::errors::Error;
+use crate::nom_utils::AMFResult;
+use crate::prelude::*;
+use crate::read::Reader;
+use crate::types::{AMFVersion, Element, Header, Lso, Value};
+use crate::write::{Sink, Writer};
+use core::ops::Range;
+use nom::Err;
+
+/// A top level element, and where it is decoded from
+struct LazyElement {
+    name: String,
+
+    /// The bytes of the element in the input, including its name and padding
+    range: Range<usize>,
+
+    /// The reference tables as they were before the element, which it is decoded with
+    #[cfg(feature = "amf3")]
+    tables: TableState,
+
+    /// The value that has replaced the encoded one
+    replacement: Option<Value>,
+}
+
+/// An Lso whose elements are decoded when they are accessed, see `Reader::parse_lazy`
+pub struct LazyLso<'a> {
+    /// The header of the Lso
+    pub header: Header,
+
+    input: &'a [u8],
+    elements: Vec<LazyElement>,
+    amf0_decoder: AMF0Decoder,
+
+    /// Holds every entry of the reference tables of the body, which elements are decoded with a prefix of
+    #[cfg(feature = "amf3")]
+    amf3_decoder: AMF3Decoder,
+}
+
+impl Reader {
+    /// Read a given slice as an Lso, recording where each top level element is instead of keeping its value
+    ///
+    /// Every element is decoded once to find where it ends and what it adds to the reference tables.
+    /// This function will return an error if the slice could not be parsed or if the entire slice was not consumed
+    pub fn parse_lazy<'a>(&mut self, input: &'a [u8]) -> Result<LazyLso<'a>, Err<Error<'a>>> {
+        self.reset();
+        let (mut i, header) = self.parse_header(input)?;
+
+        let mut lso = LazyLso {
+            header,
+            input,
+            elements: Vec::new(),
+            amf0_decoder: self.amf0_decoder.fresh(),
+            #[cfg(feature = "amf3")]
+            amf3_decoder: self.amf3_decoder.fresh(),
+        };
+
+        while !i.is_empty() {
+            let start = input.len() - i.len();
+            #[cfg(feature = "amf3")]
+            let tables = lso.table_state();
+
+            let (j, element) = lso.scan(i)?;
+            i = j;
+
+            lso.elements.push(LazyElement {
+                name: element.name,
+                range: start..input.len() - i.len(),
+                #[cfg(feature = "amf3")]
+                tables,
+                replacement: None,
+            });
+        }
+
+        self.amf0_decoder
+            .duplicate_keys()
+            .apply(&mut lso.elements, |e| &e.name)?;
+        Ok(lso)
+    }
+}
+
+impl<'a> LazyLso<'a> {
+    /// Parse the next element of the body, adding to the reference tables
+    fn scan(&mut self, i: &'a [u8]) -> AMFResult<'a, Element> {
+        match self.header.format_version {
+            AMFVersion::AMF0 => self.amf0_decoder.parse_element_and_padding(i),
+            #[cfg(feature = "amf3")]
+            AMFVersion::AMF3 => {
+                self.amf3_decoder.input_len = Some(self.input.len());
+                let res = self.amf3_decoder.parse_body_element(i);
+                self.amf3_decoder.input_len = None;
+                res
+            }
+        }
+    }
+
+    #[cfg(feature = "amf3")]
+    fn table_state(&self) -> TableState {
+        match self.header.format_version {
+            AMFVersion::AMF0 => self.amf0_decoder.table_state(),
+            AMFVersion::AMF3 => self.amf3_decoder.table_state(),
+        }
+    }
+
+    fn decode(&self, element: &LazyElement) -> Result<Element, Err<Error<'a>>> {
+        if let Some(value) = &element.replacement {
+            return Ok(Element::new(element.name.clone(), value.clone()));
+        }
+
+        let i = &self.input[element.range.start..];
+        let (_, decoded) = match self.header.format_version {
+            AMFVersion::AMF0 => {
+                #[cfg(feature = "amf3")]
+                let mut decoder = self.amf0_decoder.at_state(element.tables);
+                #[cfg(not(feature = "amf3"))]
+                let mut decoder = self.amf0_decoder.fresh();
+
+                decoder.parse_element_and_padding(i)
+            }
+            #[cfg(feature = "amf3")]
+            AMFVersion::AMF3 => {
+                let mut decoder = self.amf3_decoder.at_state(element.tables);
+                decoder.input_len = Some(self.input.len());
+                decoder.parse_body_element(i)
+            }
+        }?;
+        Ok(decoded)
+    }
+
+    /// The number of top level elements
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Whether there are no top level elements
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// The names of the top level elements, in the order they are stored
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.elements.iter().map(|e| e.name.as_str())
+    }
+
+    /// The range of bytes in the input that the element at the given index is stored in, including its name
+    ///
+    /// Returns `None` if there is no element at the index
+    pub fn byte_range(&self, index: usize) -> Option<Range<usize>> {
+        self.elements.get(index).map(|e| e.range.clone())
+    }
+
+    /// Decode the element at the given index
+    ///
+    /// Returns `None` if there is no element at the index
+    pub fn element(&self, index: usize) -> Option<Result<Element, Err<Error<'a>>>> {
+        self.elements.get(index).map(|e| self.decode(e))
+    }
+
+    /// Decode the value of the last element with the given name, which is the one Flash Player keeps
+    ///
+    /// Returns `None` if there is no element with the name
+    pub fn get(&self, name: &str) -> Option<Result<Value, Err<Error<'a>>>> {
+        self.elements
+            .iter()
+            .rfind(|e| e.name == name)
+            .map(|e| self.decode(e).map(|e| e.value))
+    }
+
+    /// Replace the value of the last element with the given name, keeping its position
+    ///
+    /// Returns false if there is no element with the name
+    pub fn replace(&mut self, name: &str, value: Value) -> bool {
+        match self.elements.iter_mut().rfind(|e| e.name == name) {
+            Some(element) => {
+                element.replacement = Some(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether any element has been replaced
+    pub fn is_modified(&self) -> bool {
+        self.elements.iter().any(|e| e.replacement.is_some())
+    }
+
+    /// Decode every element into an `Lso`
+    pub fn to_lso(&self) -> Result<Lso, Err<Error<'a>>> {
+        let body = self
+            .elements
+            .iter()
+            .map(|e| self.decode(e))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Lso {
+            header: self.header.clone(),
+            body,
+        })
+    }
+
+    /// Write this Lso with the given writer
+    ///
+    /// If no element has been replaced the input is written back unchanged, otherwise every element is decoded and
+    /// written again, as AMF3 references in later elements depend on the ones before them
+    pub fn write<W: Sink>(&self, writer: &mut Writer, sink: &mut W) -> Result<(), Err<Error<'a>>> {
+        if self.is_modified() {
+            let mut lso = self.to_lso()?;
+            writer.write_full(sink, &mut lso)
+        } else {
+            sink.write_all(self.input)
+        }
+        .map_err(|e| Err::Failure(Error::Encode(e)))
+    }
+}
@@ -1,4 +1,6 @@
 //! Library for reading and writing the Adobe Flash Local Shared Object (LSO) file format and the contained AMF0/AMF3 data
+//!
+//! With the `std` feature disabled this only requires `alloc`, output is written to a `write::Sink` instead of `std::io::Write`
 
 #![deny(
     anonymous_parameters,
@@ -16,12 +18,17 @@
     unused_crate_dependencies,
     clippy::unwrap_used
 )]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 // Used only during benchmarks and tests
 #[cfg(test)]
 use criterion as _;
 #[cfg(test)]
 use serde_json as _;
+#[cfg(test)]
+use tokio as _;
 
 const HEADER_VERSION: [u8; 2] = [0x00, 0xbf];
 const HEADER_SIGNATURE: [u8; 10] = [0x54, 0x43, 0x53, 0x4f, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00];
@@ -36,6 +43,9 @@ const FORMAT_VERSION_AMF3: u8 = 0x3;
 #[macro_use]
 extern crate serde;
 
+/// The `amf!` macro for building values
+mod macros;
+
 /// Reading and Writing of the AMF0 file format
 pub mod amf0;
 
@@ -48,6 +58,9 @@ pub mod errors;
 
 /// Private internal utils for reading
 mod nom_utils;
+
+/// The parts of the std prelude that are not in the core prelude
+mod prelude;
 pub use nom_utils::AMFResult;
 
 /// Reading of the Lso container format
@@ -64,3 +77,26 @@ pub mod extra;
 
 /// Reading and Writing of AMF Self Contained Packets
 pub mod packet;
+
+/// Mapping of AMF class aliases to Rust types
+pub mod alias;
+
+/// Reading and writing streams of consecutive values
+pub mod stream;
+
+/// Checking values before they are written
+pub mod validate;
+
+/// Text encodings of strings
+pub mod encoding;
+
+/// Decoding the top level elements of an Lso on demand
+pub mod lazy;
+
+/// Decoding AMF values nested inside of byte arrays
+#[cfg(feature = "amf3")]
+pub mod nested;
+
+/// Converting an Lso between AMF0 and AMF3
+#[cfg(feature = "amf3")]
+pub mod convert;
@@ -0,0 +1,99 @@
+/// Build a `Value` from a JSON like literal
+///
+/// ```
+/// use flash_lso::amf;
+/// use flash_lso::types::Value;
+///
+/// let player = amf!({
+///     "name": "Bob",
+///     "level": 3,
+///     "health": num(100),
+///     "tags": ["a", "b"],
+///     "created": date(1_600_000_000_000.0),
+///     "save": bytes([1, 2, 3]),
+///     "position": class("com.game.Point") { "x": 1.5, "y": -2.0 },
+///     "guild": null,
+/// });
+/// assert_eq!(player.as_object().and_then(|o| o.get("level")), Some(&Value::Integer(3)));
+/// ```
+///
+/// - `{ "key": value, .. }` is an anonymous object, keys are a literal, variable or parenthesized expression that converts into a `String`
+/// - `[value, ..]` is a strict array
+/// - `class(name) { "key": value, .. }` is an object of a sealed class, declaring each key as a static property
+/// - `int(x)` is an `Integer` and `num(x)` is a `Number`
+/// - `date(millis)` and `date(millis, time_zone)` are a `Date`
+/// - `bytes(x)` is a `ByteArray` copied from an array, slice or `Vec` of bytes
+/// - `null` and `undefined` are `Null` and `Undefined`
+/// - anything else is converted with `Value::from`, so integer literals are an `Integer` and float literals a `Number`
+#[macro_export]
+macro_rules! amf {
+    // Split the entries of an array on commas
+    (@array [$($out:expr),*] []) => {
+        [$($out),*]
+    };
+    (@array [$($out:expr),*] [$($cur:tt)+] , $($rest:tt)*) => {
+        $crate::amf!(@array [$($out,)* $crate::amf!($($cur)+)] [] $($rest)*)
+    };
+    (@array [$($out:expr),*] [$($cur:tt)+]) => {
+        [$($out,)* $crate::amf!($($cur)+)]
+    };
+    (@array [$($out:expr),*] [$($cur:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::amf!(@array [$($out),*] [$($cur)* $next] $($rest)*)
+    };
+
+    // Split the entries of an object on commas, after the key and colon of each
+    (@object [$($out:expr),*] () []) => {
+        [$($out),*]
+    };
+    (@object [$($out:expr),*] () [] $key:tt : $($rest:tt)*) => {
+        $crate::amf!(@object [$($out),*] ($key) [] $($rest)*)
+    };
+    (@object [$($out:expr),*] ($key:tt) [$($cur:tt)+] , $($rest:tt)*) => {
+        $crate::amf!(
+            @object [$($out,)* $crate::types::Element::new($key, $crate::amf!($($cur)+))] () [] $($rest)*
+        )
+    };
+    (@object [$($out:expr),*] ($key:tt) [$($cur:tt)+]) => {
+        [$($out,)* $crate::types::Element::new($key, $crate::amf!($($cur)+))]
+    };
+    (@object [$($out:expr),*] ($key:tt) [$($cur:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::amf!(@object [$($out),*] ($key) [$($cur)* $next] $($rest)*)
+    };
+
+    (null) => {
+        $crate::types::Value::Null
+    };
+    (undefined) => {
+        $crate::types::Value::Undefined
+    };
+    (int($value:expr)) => {
+        $crate::types::Value::Integer($value)
+    };
+    (num($value:expr)) => {
+        $crate::types::Value::Number(f64::from($value))
+    };
+    (date($millis:expr)) => {
+        $crate::types::Value::Date(f64::from($millis), None)
+    };
+    (date($millis:expr, $time_zone:expr)) => {
+        $crate::types::Value::Date(f64::from($millis), Some($time_zone))
+    };
+    (bytes($bytes:expr)) => {
+        $crate::types::Value::ByteArray(<[u8]>::to_vec(&$bytes[..]))
+    };
+    (class($name:expr) { $($entries:tt)* }) => {
+        $crate::types::Value::typed_object($name, $crate::amf!(@object [] () [] $($entries)*).into())
+    };
+    ([ $($entries:tt)* ]) => {
+        $crate::types::Value::StrictArray(
+            $crate::types::ObjectId::INVALID,
+            $crate::amf!(@array [] [] $($entries)*).into(),
+        )
+    };
+    ({ $($entries:tt)* }) => {
+        $crate::types::Value::object($crate::amf!(@object [] () [] $($entries)*).into())
+    };
+    ($other:expr) => {
+        $crate::types::Value::from($other)
+    };
+}
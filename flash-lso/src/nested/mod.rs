@@ -0,0 +1,219 @@
+//! Detection and decoding of AMF values nested inside of `Value::ByteArray` and `Value::String`
+//!
+//! Games often call `ByteArray.writeObject()` and then `ByteArray.compress()` before storing the result, sometimes
+//! also encoding the bytes as a base64 or hex string. This unwraps those layers so that the inner values can be
+//! edited and then wrapped back up in the same way
+
+use crate::amf3::read::AMF3Decoder;
+use crate::amf3::write::AMF3Encoder;
+use crate::errors::EncodeError;
+use crate::prelude::*;
+use crate::stream::{ResetPolicy, ValueStreamReader, ValueStreamWriter};
+use crate::types::{AMFVersion, Value};
+
+mod text;
+pub use text::{TextEncodedAmf, TextEncoding};
+
+/// The largest decompressed size that will be accepted while unwrapping a layer
+pub const MAX_DECOMPRESSED_LEN: usize = 64 * 1024 * 1024;
+
+/// The most layers of compression that will be unwrapped
+const MAX_DEPTH: usize = 4;
+
+/// A compression layer, as applied by `ByteArray.compress()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    /// `CompressionAlgorithm.ZLIB`, the default
+    #[cfg(feature = "zlib")]
+    Zlib,
+
+    /// `CompressionAlgorithm.DEFLATE`, raw deflate without a header
+    #[cfg(feature = "zlib")]
+    Deflate,
+
+    /// `CompressionAlgorithm.LZMA`, with a 5 byte properties header and 8 byte little-endian uncompressed length
+    #[cfg(feature = "lzma")]
+    Lzma,
+}
+
+// With no compression features enabled there are no layers, and so nothing uses the data
+#[cfg_attr(not(any(feature = "zlib", feature = "lzma")), allow(unused_variables))]
+impl Layer {
+    /// Decompress data compressed with this layer
+    ///
+    /// Returns `None` if the data is not valid or would be larger than `MAX_DECOMPRESSED_LEN`
+    pub fn decompress(self, data: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            #[cfg(feature = "zlib")]
+            Layer::Zlib => {
+                // The compression method must be deflate, and the check bits must be valid
+                let header = data.get(..2)?;
+                if header[0] & 0x0F != 8 || u16::from_be_bytes([header[0], header[1]]) % 31 != 0 {
+                    return None;
+                }
+                miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(data, MAX_DECOMPRESSED_LEN)
+                    .ok()
+            }
+            #[cfg(feature = "zlib")]
+            Layer::Deflate => {
+                miniz_oxide::inflate::decompress_to_vec_with_limit(data, MAX_DECOMPRESSED_LEN).ok()
+            }
+            #[cfg(feature = "lzma")]
+            Layer::Lzma => {
+                // Flash always writes the length, so the unknown length marker is not accepted
+                let length = u64::from_le_bytes(data.get(5..13)?.try_into().ok()?);
+                if data[0] >= 9 * 5 * 5 || length > MAX_DECOMPRESSED_LEN as u64 {
+                    return None;
+                }
+
+                let mut output = Vec::with_capacity(length as usize);
+                let options = lzma_rs::decompress::Options {
+                    memlimit: Some(MAX_DECOMPRESSED_LEN),
+                    ..Default::default()
+                };
+                lzma_rs::lzma_decompress_with_options(&mut &data[..], &mut output, &options)
+                    .ok()?;
+                Some(output)
+            }
+        }
+    }
+
+    /// Compress data with this layer, in the same format as Flash Player
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>, EncodeError> {
+        match self {
+            #[cfg(feature = "zlib")]
+            Layer::Zlib => Ok(miniz_oxide::deflate::compress_to_vec_zlib(data, 6)),
+            #[cfg(feature = "zlib")]
+            Layer::Deflate => Ok(miniz_oxide::deflate::compress_to_vec(data, 6)),
+            #[cfg(feature = "lzma")]
+            Layer::Lzma => {
+                let mut output = Vec::new();
+                let options = lzma_rs::compress::Options {
+                    unpacked_size: lzma_rs::compress::UnpackedSize::WriteToHeader(Some(
+                        data.len() as u64
+                    )),
+                };
+                lzma_rs::lzma_compress_with_options(&mut &data[..], &mut output, &options)?;
+                Ok(output)
+            }
+        }
+    }
+}
+
+/// AMF values found inside of a `Value::ByteArray`
+#[derive(Debug, Clone, PartialEq)]
+pub struct NestedAmf {
+    /// The compression layers that were removed, outermost first
+    pub layers: Vec<Layer>,
+
+    /// The encoding of the values, as set by `ByteArray.objectEncoding`
+    pub version: AMFVersion,
+
+    /// The values, one for each call to `ByteArray.writeObject()`
+    pub values: Vec<Value>,
+}
+
+impl NestedAmf {
+    /// Try to find AMF values in the given bytes
+    ///
+    /// Each enabled compression layer is tried, then the data is decoded as a stream of AMF3 values and then as a
+    /// stream of AMF0 values. Each value is decoded with new reference tables, as Flash does for each call to
+    /// `ByteArray.writeObject()`, using the registered decoders of `decoder`
+    ///
+    /// As this is a heuristic, arbitrary data can be detected as a short AMF stream, so the result should be checked
+    /// against the expected contents
+    pub fn decode(bytes: &[u8], decoder: &AMF3Decoder) -> Option<Self> {
+        Self::decode_layers(bytes, decoder, 0)
+    }
+
+    /// Try to find AMF values in the given `Value::ByteArray`, see `decode`
+    pub fn decode_value(value: &Value, decoder: &AMF3Decoder) -> Option<Self> {
+        match value {
+            Value::ByteArray(bytes) => Self::decode(bytes, decoder),
+            _ => None,
+        }
+    }
+
+    fn decode_layers(bytes: &[u8], decoder: &AMF3Decoder, depth: usize) -> Option<Self> {
+        let unwrap = |layer: &Layer| {
+            if depth >= MAX_DEPTH {
+                return None;
+            }
+            let inner = layer.decompress(bytes)?;
+            let mut nested = Self::decode_layers(&inner, decoder, depth + 1)?;
+            nested.layers.insert(0, *layer);
+            Some(nested)
+        };
+
+        let headered: &[Layer] = &[
+            #[cfg(feature = "zlib")]
+            Layer::Zlib,
+            #[cfg(feature = "lzma")]
+            Layer::Lzma,
+        ];
+        // Raw deflate has no header, so it is only tried once the data has failed to decode as AMF
+        let headerless: &[Layer] = &[
+            #[cfg(feature = "zlib")]
+            Layer::Deflate,
+        ];
+
+        headered
+            .iter()
+            .find_map(unwrap)
+            .or_else(|| Self::decode_stream(bytes, decoder))
+            .or_else(|| headerless.iter().find_map(unwrap))
+    }
+
+    fn decode_stream(bytes: &[u8], decoder: &AMF3Decoder) -> Option<Self> {
+        if bytes.is_empty() {
+            return None;
+        }
+
+        let amf3 =
+            ValueStreamReader::with_amf3_decoder(bytes, ResetPolicy::PerValue, decoder.fresh())
+                .collect::<Result<_, _>>();
+        let (version, values) = match amf3 {
+            Ok(values) => (AMFVersion::AMF3, values),
+            Err(_) => (
+                AMFVersion::AMF0,
+                ValueStreamReader::new(bytes, AMFVersion::AMF0, ResetPolicy::PerValue)
+                    .collect::<Result<_, _>>()
+                    .ok()?,
+            ),
+        };
+
+        Some(Self {
+            layers: Vec::new(),
+            version,
+            values,
+        })
+    }
+
+    /// Encode the values and apply the compression layers, using the registered encoders of `encoder`
+    pub fn encode(&self, encoder: &AMF3Encoder) -> Result<Vec<u8>, EncodeError> {
+        let mut writer = match self.version {
+            AMFVersion::AMF0 => {
+                ValueStreamWriter::new(Vec::new(), AMFVersion::AMF0, ResetPolicy::PerValue)
+            }
+            AMFVersion::AMF3 => ValueStreamWriter::with_amf3_encoder(
+                Vec::new(),
+                ResetPolicy::PerValue,
+                encoder.fresh(),
+            ),
+        };
+        for value in &self.values {
+            writer.write_value(value)?;
+        }
+
+        let mut bytes = writer.into_inner();
+        for layer in self.layers.iter().rev() {
+            bytes = layer.compress(&bytes)?;
+        }
+        Ok(bytes)
+    }
+
+    /// Encode into a `Value::ByteArray`, see `encode`
+    pub fn to_value(&self, encoder: &AMF3Encoder) -> Result<Value, EncodeError> {
+        self.encode(encoder).map(Value::ByteArray)
+    }
+}
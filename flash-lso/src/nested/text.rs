@@ -0,0 +1,254 @@
+use super::NestedAmf;
+use crate::amf3::read::AMF3Decoder;
+use crate::amf3::write::AMF3Encoder;
+use crate::errors::EncodeError;
+use crate::prelude::*;
+use crate::types::Value;
+
+const BASE64_STANDARD: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64_URL_SAFE: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// How binary data was written into a string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// Base64, as written by `mx.utils.Base64Encoder` and most hand written encoders
+    Base64 {
+        /// If `-` and `_` are used in place of `+` and `/`
+        url_safe: bool,
+
+        /// If the output is padded with `=`
+        padded: bool,
+
+        /// The length of each line, if the output is split over multiple lines
+        line_length: Option<usize>,
+
+        /// If lines end with `\r\n` rather than `\n`
+        crlf: bool,
+
+        /// If the last line also ends with a newline
+        trailing_newline: bool,
+    },
+
+    /// Two hex digits per byte
+    Hex {
+        /// If the digits `A` to `F` are uppercase
+        uppercase: bool,
+    },
+}
+
+impl TextEncoding {
+    /// Detect the encoding of the given text and decode it, returning `None` if it isn't base64 or hex
+    ///
+    /// Text that is valid as both is treated as hex. Only text that `encode` writes back unchanged is detected, so
+    /// whitespace around the data, other than a newline after the last line of base64, is not accepted
+    pub fn detect(text: &str) -> Option<(Self, Vec<u8>)> {
+        Self::detect_all(text).next()
+    }
+
+    /// Every encoding that the text is valid in, most likely first
+    fn detect_all(text: &str) -> impl Iterator<Item = (Self, Vec<u8>)> {
+        let uppercase = text.bytes().any(|b| b.is_ascii_uppercase());
+        let lowercase = text.bytes().any(|b| b.is_ascii_lowercase());
+        let hex = decode_hex(text)
+            .filter(|_| !(uppercase && lowercase))
+            .map(|bytes| (TextEncoding::Hex { uppercase }, bytes));
+        hex.into_iter().chain(decode_base64(text))
+    }
+
+    /// Decode text written in this encoding
+    pub fn decode(&self, text: &str) -> Option<Vec<u8>> {
+        match self {
+            TextEncoding::Hex { .. } => decode_hex(text),
+            TextEncoding::Base64 { .. } => decode_base64(text).map(|(_, bytes)| bytes),
+        }
+    }
+
+    /// Encode bytes in this encoding
+    pub fn encode(&self, bytes: &[u8]) -> String {
+        match *self {
+            TextEncoding::Hex { uppercase } => {
+                let digits: &[u8; 16] = if uppercase {
+                    b"0123456789ABCDEF"
+                } else {
+                    b"0123456789abcdef"
+                };
+                bytes
+                    .iter()
+                    .flat_map(|b| [digits[usize::from(b >> 4)], digits[usize::from(b & 0xF)]])
+                    .map(char::from)
+                    .collect()
+            }
+            TextEncoding::Base64 {
+                url_safe,
+                padded,
+                line_length,
+                crlf,
+                trailing_newline,
+            } => {
+                let encoded = encode_base64(bytes, url_safe, padded);
+                let newline = if crlf { "\r\n" } else { "\n" };
+                let mut text = match line_length {
+                    Some(length) if length > 0 => encoded
+                        .as_bytes()
+                        .chunks(length)
+                        .map(|line| core::str::from_utf8(line).unwrap_or_default())
+                        .collect::<Vec<_>>()
+                        .join(newline),
+                    _ => encoded,
+                };
+                if trailing_newline {
+                    text.push_str(newline);
+                }
+                text
+            }
+        }
+    }
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if text.is_empty() || !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let digit = |b: u8| char::from(b).to_digit(16).map(|d| d as u8);
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| Some(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect()
+}
+
+fn encode_base64(bytes: &[u8], url_safe: bool, padded: bool) -> String {
+    let alphabet = if url_safe {
+        BASE64_URL_SAFE
+    } else {
+        BASE64_STANDARD
+    };
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, b)| acc | u32::from(*b) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(char::from(
+                alphabet[(group >> (18 - 6 * i) & 0x3F) as usize],
+            ));
+        }
+        if padded {
+            for _ in chunk.len()..3 {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode_base64(text: &str) -> Option<(TextEncoding, Vec<u8>)> {
+    let crlf = text.contains("\r\n");
+    let newline = if crlf { "\r\n" } else { "\n" };
+    let (text, trailing_newline) = match text.strip_suffix(newline) {
+        Some(text) => (text, true),
+        None => (text, false),
+    };
+    let lines: Vec<&str> = text.split(newline).collect();
+
+    // Every line but the last must have the same length, and the last can't be longer, for the wrapping to be
+    // reproduced. Any other whitespace is rejected by the alphabet below
+    let line_length = match lines.as_slice() {
+        [] => return None,
+        [_] => None,
+        [first, middle @ .., last] => {
+            if middle.iter().any(|line| line.len() != first.len())
+                || last.is_empty()
+                || last.len() > first.len()
+            {
+                return None;
+            }
+            Some(first.len())
+        }
+    };
+
+    let data: Vec<u8> = lines.concat().into_bytes();
+    let padding = data.iter().rev().take_while(|b| **b == b'=').count();
+    if padding > 2 || (padding > 0 && !data.len().is_multiple_of(4)) {
+        return None;
+    }
+    // Unpadded data that is a multiple of 4 long could have been either, so assume the more common padded form
+    let padded = padding > 0 || data.len().is_multiple_of(4);
+    let data = &data[..data.len() - padding];
+    if data.is_empty() {
+        return None;
+    }
+
+    let url_safe = data.iter().any(|b| matches!(b, b'-' | b'_'));
+    let alphabet = if url_safe {
+        BASE64_URL_SAFE
+    } else {
+        BASE64_STANDARD
+    };
+
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    for chunk in data.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut group = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let value = alphabet.iter().position(|a| a == c)? as u32;
+            group |= value << (18 - 6 * i);
+        }
+        bytes.extend_from_slice(&group.to_be_bytes()[1..chunk.len()]);
+    }
+
+    let encoding = TextEncoding::Base64 {
+        url_safe,
+        padded,
+        line_length,
+        crlf,
+        trailing_newline,
+    };
+    Some((encoding, bytes))
+}
+
+/// AMF values found inside of a base64 or hex encoded string
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEncodedAmf {
+    /// How the bytes were written into the string
+    pub encoding: TextEncoding,
+
+    /// The values and any compression layers inside of the bytes
+    pub nested: NestedAmf,
+}
+
+impl TextEncodedAmf {
+    /// Try to find AMF values in the given base64 or hex encoded text, see `NestedAmf::decode`
+    pub fn decode(text: &str, decoder: &AMF3Decoder) -> Option<Self> {
+        TextEncoding::detect_all(text).find_map(|(encoding, bytes)| {
+            Some(Self {
+                encoding,
+                nested: NestedAmf::decode(&bytes, decoder)?,
+            })
+        })
+    }
+
+    /// Try to find AMF values in the given `Value::String`, see `decode`
+    pub fn decode_value(value: &Value, decoder: &AMF3Decoder) -> Option<Self> {
+        match value {
+            Value::String(text) => Self::decode(text, decoder),
+            _ => None,
+        }
+    }
+
+    /// Encode the values and write them into a string in the same encoding
+    pub fn encode(&self, encoder: &AMF3Encoder) -> Result<String, EncodeError> {
+        Ok(self.encoding.encode(&self.nested.encode(encoder)?))
+    }
+
+    /// Encode into a `Value::String`, see `encode`
+    pub fn to_value(&self, encoder: &AMF3Encoder) -> Result<Value, EncodeError> {
+        self.encode(encoder).map(Value::String)
+    }
+}
@@ -1,24 +1,26 @@
-use crate::errors::Error;
+use crate::encoding::StringEncoding;
+use crate::errors::{EncodeError, Error};
 use nom::Parser;
 use nom::bytes::complete::take;
 use nom::combinator::map_res;
 
-use crate::write::WriteExt;
+use crate::write::{Sink, WriteExt};
 use nom::IResult;
-use std::io::Write;
 
 /// The result type of all AMF parsers
 pub type AMFResult<'a, T> = IResult<&'a [u8], T, Error<'a>>;
 
-pub(crate) fn write_string<'a, 'b: 'a, W: Write + 'a>(
+pub(crate) fn write_string<'a, 'b: 'a, W: Sink + 'a>(
     writer: &mut W,
     s: &'b str,
-) -> std::io::Result<()> {
-    writer.write_u16(s.len() as u16)?;
-    writer.write_all(s.as_bytes())?;
+    encoding: StringEncoding,
+) -> Result<(), EncodeError> {
+    let bytes = encoding.encode_checked(s)?;
+    writer.write_u16(bytes.len() as u16)?;
+    writer.write_all(&bytes)?;
     Ok(())
 }
 
 pub(crate) fn take_str(i: &[u8], length: u16) -> AMFResult<'_, &str> {
-    map_res(take(length), std::str::from_utf8).parse(i)
+    map_res(take(length), core::str::from_utf8).parse(i)
 }
@@ -1,3 +1,4 @@
+use crate::prelude::*;
 use crate::types::{AMFVersion, Value};
 
 /// Reading of AMF Packets
@@ -1,7 +1,8 @@
+use crate::prelude::*;
+use core::convert::TryInto;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::number::complete::{be_u8, be_u16, be_u32};
-use std::convert::TryInto;
 
 use crate::amf0;
 use crate::amf0::read::AMF0Decoder;
@@ -83,3 +84,17 @@ pub fn parse(i: &[u8]) -> Result<Packet, nom::Err<Error<'_>>> {
     let (_, packet) = all_consuming(|i| parse_incomplete(i)).parse(i)?;
     Ok(packet)
 }
+
+/// Read a packet from an async reader
+///
+/// Packets are not length prefixed, so this reads until the end of the input and then decodes it with `parse`
+#[cfg(feature = "tokio")]
+pub async fn read_async<R: tokio::io::AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+) -> Result<Packet, crate::errors::AsyncError> {
+    use tokio::io::AsyncReadExt;
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).await?;
+    Ok(parse(&data)?)
+}
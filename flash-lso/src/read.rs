@@ -1,18 +1,23 @@
-use std::convert::TryInto;
+use core::convert::TryInto;
 
 use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::number::complete::be_u32;
 
+use crate::alias::{ClassRegistry, TypedLso};
 use crate::amf0;
 use crate::amf0::read::AMF0Decoder;
 #[cfg(feature = "amf3")]
-use crate::amf3::read::AMF3Decoder;
+use crate::amf3;
+#[cfg(feature = "amf3")]
+use crate::amf3::read::{AMF3Decoder, DecoderConfig};
+use crate::encoding::StringEncoding;
 use crate::errors::Error;
 use crate::nom_utils::AMFResult;
-use crate::types::{AMFVersion, Header, Lso};
+use crate::types::{AMFVersion, DecodeLimits, DuplicateKeys, Header, Lso, LsoRef};
 use nom::Parser;
 use nom::combinator::all_consuming;
+use nom::error::{ErrorKind, make_error};
 
 const HEADER_VERSION: [u8; 2] = [0x00, 0xbf];
 const HEADER_SIGNATURE: [u8; 10] = [0x54, 0x43, 0x53, 0x4f, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00];
@@ -40,20 +45,93 @@ const FORMAT_VERSION_AMF3: u8 = 0x3;
 pub struct Reader {
     #[cfg(feature = "amf3")]
     /// Handles reading Amf3 data
+    ///
+    /// Its configuration is the configuration of the reader, used for AMF0 bodies as well
     pub amf3_decoder: AMF3Decoder,
 
     /// Handles reading Amf0 data
+    ///
+    /// With the `amf3` feature its configuration is replaced by that of `amf3_decoder` at the start of every parse,
+    /// so the reader is configured through `config_mut` and the setters of `Reader`
     pub amf0_decoder: AMF0Decoder,
+
+    /// The classes that `parse_typed` converts to Rust types
+    pub class_registry: ClassRegistry,
 }
 
 impl Reader {
+    /// Create a reader that decodes with the given configuration
+    ///
+    /// The configuration is shared by every file that is parsed, while the reference tables are reset for each one
+    #[cfg(feature = "amf3")]
+    pub fn new(config: DecoderConfig) -> Self {
+        Self {
+            amf3_decoder: AMF3Decoder::new(config.clone()),
+            amf0_decoder: AMF0Decoder::new(config),
+            class_registry: ClassRegistry::default(),
+        }
+    }
+
+    /// The configuration used for both AMF0 and AMF3 bodies
+    #[cfg(feature = "amf3")]
+    pub fn config(&self) -> &DecoderConfig {
+        self.amf3_decoder.config()
+    }
+
+    /// The configuration used for both AMF0 and AMF3 bodies, for modification
+    #[cfg(feature = "amf3")]
+    pub fn config_mut(&mut self) -> &mut DecoderConfig {
+        self.amf3_decoder.config_mut()
+    }
+
+    /// Set the encoding of strings, element names and class names, for both AMF0 and AMF3
+    pub fn set_string_encoding(&mut self, encoding: StringEncoding) {
+        #[cfg(feature = "amf3")]
+        self.config_mut().set_string_encoding(encoding);
+        #[cfg(not(feature = "amf3"))]
+        self.amf0_decoder.set_string_encoding(encoding);
+    }
+
+    /// Set how elements with the same name in one object, or at the root of the body, are handled, for both AMF0 and AMF3
+    pub fn set_duplicate_keys(&mut self, duplicate_keys: DuplicateKeys) {
+        #[cfg(feature = "amf3")]
+        self.config_mut().set_duplicate_keys(duplicate_keys);
+        #[cfg(not(feature = "amf3"))]
+        self.amf0_decoder.set_duplicate_keys(duplicate_keys);
+    }
+
+    /// Set the limits on what a single parse may decode, for both AMF0 and AMF3
+    pub fn set_limits(&mut self, limits: DecodeLimits) {
+        #[cfg(feature = "amf3")]
+        self.config_mut().set_limits(limits);
+        #[cfg(not(feature = "amf3"))]
+        self.amf0_decoder.set_limits(limits);
+    }
+
+    /// Clear the state left by a previous parse, and give the AMF0 decoder the configuration of the reader
+    ///
+    /// This is done at the start of every parse
+    pub fn reset(&mut self) {
+        #[cfg(feature = "amf3")]
+        {
+            self.amf3_decoder.reset();
+            let config = self.amf3_decoder.config().clone();
+            self.amf0_decoder.set_config(config);
+        }
+        self.amf0_decoder.reset();
+    }
+
     /// Read a Lso header from the given slice
     pub fn parse_header<'a>(&self, i: &'a [u8]) -> AMFResult<'a, Header> {
         let (i, _) = tag(HEADER_VERSION.as_slice())(i)?;
         let (i, l) = be_u32(i)?;
         let (i, _) = tag(HEADER_SIGNATURE.as_slice())(i)?;
 
-        let (i, name) = amf0::read::parse_string(i)?;
+        #[cfg(feature = "amf3")]
+        let encoding = self.config().string_encoding();
+        #[cfg(not(feature = "amf3"))]
+        let encoding = self.amf0_decoder.string_encoding();
+        let (i, name) = amf0::read::parse_encoded_string(i, encoding)?;
 
         let (i, _) = tag(PADDING.as_slice())(i)?;
         let (i, _) = tag(PADDING.as_slice())(i)?;
@@ -73,7 +151,7 @@ impl Reader {
             i,
             Header {
                 length: l,
-                name: name.to_string(),
+                name,
                 format_version,
             },
         ))
@@ -83,8 +161,9 @@ impl Reader {
     ///
     /// Unlike parse, this function will not error if the entire slice isn't consumed
     /// and will return the data that was not parsed
-    pub fn parse_incomplete<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Lso> {
-        let (i, header) = self.parse_header(i)?;
+    pub fn parse_incomplete<'a>(&mut self, input: &'a [u8]) -> AMFResult<'a, Lso> {
+        self.reset();
+        let (i, header) = self.parse_header(input)?;
         match header.format_version {
             AMFVersion::AMF0 => {
                 let (i, body) = self.amf0_decoder.parse_body(i)?;
@@ -93,7 +172,12 @@ impl Reader {
 
             #[cfg(feature = "amf3")]
             AMFVersion::AMF3 => {
-                let (i, body) = self.amf3_decoder.parse_body(i)?;
+                // Report error offsets relative to the start of the file, rather than the body
+                self.amf3_decoder.input_len = Some(input.len());
+                let res = self.amf3_decoder.parse_body(i);
+                self.amf3_decoder.input_len = None;
+
+                let (i, body) = res?;
                 Ok((i, Lso { header, body }))
             }
         }
@@ -107,4 +191,72 @@ impl Reader {
         let (_, lso) = all_consuming(|i| self.parse_incomplete(i)).parse(i)?;
         Ok(lso)
     }
+
+    /// Read a given slice as an Lso whose strings, element names and byte arrays borrow from it where possible
+    ///
+    /// This function will return an error if the slice could not be parsed or if the entire slice
+    /// was not consumed
+    pub fn parse_borrowed<'a>(
+        &mut self,
+        input: &'a [u8],
+    ) -> Result<LsoRef<'a>, nom::Err<Error<'a>>> {
+        self.reset();
+        let (i, header) = self.parse_header(input)?;
+        let (i, body) = match header.format_version {
+            AMFVersion::AMF0 => {
+                amf0::borrowed::BorrowedDecoder::new(&self.amf0_decoder).parse_body(i)?
+            }
+            #[cfg(feature = "amf3")]
+            AMFVersion::AMF3 => {
+                let mut decoder = amf3::borrowed::BorrowedDecoder::new(&self.amf3_decoder);
+                // Report error offsets relative to the start of the file, rather than the body
+                decoder.input_len = Some(input.len());
+                decoder.parse_body(i)?
+            }
+        };
+
+        if !i.is_empty() {
+            return Err(nom::Err::Error(make_error(i, ErrorKind::Eof)));
+        }
+        Ok(LsoRef { header, body })
+    }
+
+    /// Read a given slice as an Lso, converting root level objects of registered classes into their Rust types
+    ///
+    /// Objects of classes that are not registered are left as `Value`, as are objects of registered classes nested
+    /// in arrays, dictionaries or unregistered objects
+    pub fn parse_typed<'a>(&mut self, i: &'a [u8]) -> Result<TypedLso, nom::Err<Error<'a>>> {
+        let lso = self.parse(i)?;
+        self.class_registry
+            .decode_lso(lso)
+            .map_err(|e| nom::Err::Failure(Error::Convert(e)))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Reader {
+    /// Read an Lso from an async reader
+    ///
+    /// This reads exactly the length given in the header and then decodes it with `parse`
+    pub async fn read_async<R: tokio::io::AsyncRead + Unpin + ?Sized>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<Lso, crate::errors::AsyncError> {
+        use tokio::io::AsyncReadExt;
+
+        // The version followed by the length of the rest of the file
+        let mut data = vec![0u8; 6];
+        reader.read_exact(&mut data).await?;
+        let length = u32::from_be_bytes([data[2], data[3], data[4], data[5]]);
+
+        let read = reader
+            .take(u64::from(length))
+            .read_to_end(&mut data)
+            .await?;
+        if read != length as usize {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+
+        Ok(self.parse(&data)?)
+    }
 }
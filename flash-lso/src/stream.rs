@@ -0,0 +1,206 @@
+//! Reading and writing streams of consecutive AMF values
+//!
+//! These are produced by repeated calls to `ByteArray.writeObject()` or `Socket.writeObject()`, and by FLV and RTMP
+//! data messages. Whether the reference tables are shared between values is controlled by a `ResetPolicy`
+
+use crate::amf0;
+use crate::amf0::read::AMF0Decoder;
+use crate::amf0::write::AMF0Encoder;
+#[cfg(feature = "amf3")]
+use crate::amf3::read::AMF3Decoder;
+#[cfg(feature = "amf3")]
+use crate::amf3::write::AMF3Encoder;
+use crate::errors::{EncodeError, Error};
+use crate::types::{AMFVersion, Value};
+use crate::write::{EncodeOptions, Sink};
+
+/// When the reference tables of a value stream are reset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResetPolicy {
+    /// Every value starts with empty reference tables, as with each call to `ByteArray.writeObject()`
+    #[default]
+    PerValue,
+
+    /// The tables are shared by every value in the stream, until they are reset manually
+    PerStream,
+}
+
+/// Reads consecutive values from a slice
+///
+/// Iteration stops at the end of the input, or after the first error
+pub struct ValueStreamReader<'a> {
+    input: &'a [u8],
+    version: AMFVersion,
+    policy: ResetPolicy,
+    amf0_decoder: AMF0Decoder,
+    #[cfg(feature = "amf3")]
+    amf3_decoder: AMF3Decoder,
+    failed: bool,
+}
+
+impl<'a> ValueStreamReader<'a> {
+    /// Create a reader for a stream of values in the given version
+    pub fn new(input: &'a [u8], version: AMFVersion, policy: ResetPolicy) -> Self {
+        Self {
+            input,
+            version,
+            policy,
+            amf0_decoder: AMF0Decoder::default(),
+            #[cfg(feature = "amf3")]
+            amf3_decoder: AMF3Decoder::default(),
+            failed: false,
+        }
+    }
+
+    /// Create a reader for a stream of AMF3 values, using the given decoder
+    ///
+    /// Any custom decoders registered on the decoder are kept when the tables are reset
+    #[cfg(feature = "amf3")]
+    pub fn with_amf3_decoder(input: &'a [u8], policy: ResetPolicy, decoder: AMF3Decoder) -> Self {
+        Self {
+            amf3_decoder: decoder,
+            ..Self::new(input, AMFVersion::AMF3, policy)
+        }
+    }
+
+    /// The input that has not been read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.input
+    }
+
+    /// Clear the reference tables, so that the next value is read as if it was the first
+    pub fn reset(&mut self) {
+        self.amf0_decoder.reset();
+        #[cfg(feature = "amf3")]
+        self.amf3_decoder.reset();
+    }
+
+    /// Read the next value, returning `None` at the end of the input
+    pub fn read_value(&mut self) -> Option<Result<Value, nom::Err<Error<'a>>>> {
+        if self.input.is_empty() || self.failed {
+            return None;
+        }
+
+        if self.policy == ResetPolicy::PerValue {
+            self.reset();
+        }
+
+        let result = match self.version {
+            AMFVersion::AMF0 => self.amf0_decoder.parse_single_element(self.input),
+            #[cfg(feature = "amf3")]
+            AMFVersion::AMF3 => self.amf3_decoder.parse_single_element(self.input),
+        };
+
+        Some(match result {
+            Ok((rest, value)) => {
+                self.input = rest;
+                Ok(value)
+            }
+            Err(e) => {
+                self.failed = true;
+                Err(e)
+            }
+        })
+    }
+}
+
+impl<'a> Iterator for ValueStreamReader<'a> {
+    type Item = Result<Value, nom::Err<Error<'a>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_value()
+    }
+}
+
+/// Writes consecutive values to a sink
+pub struct ValueStreamWriter<W: Sink> {
+    writer: W,
+    version: AMFVersion,
+    policy: ResetPolicy,
+    amf0_encoder: AMF0Encoder,
+    #[cfg(feature = "amf3")]
+    amf3_encoder: AMF3Encoder,
+}
+
+impl<W: Sink> ValueStreamWriter<W> {
+    /// Create a writer for a stream of values in the given version
+    pub fn new(writer: W, version: AMFVersion, policy: ResetPolicy) -> Self {
+        Self::with_options(writer, version, policy, EncodeOptions::default())
+    }
+
+    /// Create a writer for a stream of values in the given version, using the given options
+    pub fn with_options(
+        writer: W,
+        version: AMFVersion,
+        policy: ResetPolicy,
+        options: EncodeOptions,
+    ) -> Self {
+        Self {
+            writer,
+            version,
+            policy,
+            amf0_encoder: AMF0Encoder::new(options),
+            #[cfg(feature = "amf3")]
+            amf3_encoder: AMF3Encoder::new(options),
+        }
+    }
+
+    /// Create a writer for a stream of AMF3 values, using the given encoder
+    ///
+    /// Any custom encoders registered on the encoder are kept when the tables are reset
+    #[cfg(feature = "amf3")]
+    pub fn with_amf3_encoder(writer: W, policy: ResetPolicy, encoder: AMF3Encoder) -> Self {
+        Self {
+            amf3_encoder: encoder,
+            ..Self::new(writer, AMFVersion::AMF3, policy)
+        }
+    }
+
+    /// Clear the reference tables, so that the next value is written as if it was the first
+    pub fn reset(&mut self) {
+        self.amf0_encoder.reset();
+        #[cfg(feature = "amf3")]
+        {
+            self.amf3_encoder = self.amf3_encoder.fresh();
+        }
+    }
+
+    /// Write a value to the stream
+    ///
+    /// The value is validated first, so nothing is written if it can not be encoded
+    pub fn write_value(&mut self, value: &Value) -> Result<(), EncodeError> {
+        if self.policy == ResetPolicy::PerValue {
+            self.reset();
+        }
+
+        match self.version {
+            AMFVersion::AMF0 => {
+                let mut validator = self.amf0_encoder.validator();
+                validator.value(value, AMFVersion::AMF0);
+                validator.finish()?;
+            }
+            #[cfg(feature = "amf3")]
+            AMFVersion::AMF3 => self.amf3_encoder.validate(value)?,
+        }
+
+        match self.version {
+            AMFVersion::AMF0 => {
+                amf0::write::write_value(&mut self.writer, &mut self.amf0_encoder, value)
+            }
+            #[cfg(feature = "amf3")]
+            AMFVersion::AMF3 => self
+                .amf3_encoder
+                .write_value_element(&mut self.writer, value),
+        }
+    }
+
+    /// Get a reference to the underlying sink
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get the underlying sink
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
@@ -0,0 +1,300 @@
+use super::{
+    Attribute, ClassDefinition, CustomObject, Dictionary, EcmaArray, Element, Header, Lso,
+    ObjectId, ObjectValue, ObjectVector, Reference, Value,
+};
+use crate::prelude::*;
+use alloc::borrow::Cow;
+use enumset::EnumSet;
+
+/// A class definition (trait) whose names borrow from the input, see `ClassDefinition`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClassDefinitionRef<'a> {
+    /// The name of the class definition
+    pub name: Cow<'a, str>,
+
+    /// The attributes on this trait
+    pub attributes: EnumSet<Attribute>,
+
+    /// The name of the static properties defined in this definition
+    pub static_properties: Vec<Cow<'a, str>>,
+}
+
+impl ClassDefinitionRef<'_> {
+    /// Copy the borrowed names, creating a `ClassDefinition`
+    pub fn into_owned(self) -> ClassDefinition {
+        ClassDefinition {
+            name: self.name.into_owned(),
+            attributes: self.attributes,
+            static_properties: self
+                .static_properties
+                .into_iter()
+                .map(Cow::into_owned)
+                .collect(),
+        }
+    }
+}
+
+impl From<ClassDefinition> for ClassDefinitionRef<'_> {
+    fn from(def: ClassDefinition) -> Self {
+        Self {
+            name: Cow::Owned(def.name),
+            attributes: def.attributes,
+            static_properties: def.static_properties.into_iter().map(Cow::Owned).collect(),
+        }
+    }
+}
+
+/// A named element whose name and value borrow from the input, see `Element`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElementRef<'a> {
+    /// The name of the element
+    pub name: Cow<'a, str>,
+
+    /// The value of the element
+    pub value: ValueRef<'a>,
+}
+
+impl ElementRef<'_> {
+    /// Copy the borrowed data, creating an `Element`
+    pub fn into_owned(self) -> Element {
+        Element {
+            name: self.name.into_owned(),
+            value: self.value.into_owned(),
+        }
+    }
+}
+
+/// The data contained within a `ValueRef` of type Object
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectValueRef<'a> {
+    /// The child elements of this Object
+    pub elements: Vec<ElementRef<'a>>,
+
+    /// The class definition for this object, if it exists
+    pub class_definition: Option<ClassDefinitionRef<'a>>,
+}
+
+/// A value whose strings and byte arrays borrow from the input where the string encoding allows, see `Value`
+///
+/// Strings are only copied when they are not valid in the input as they are, such as with a legacy code page
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueRef<'a> {
+    /// See `Value::Number`
+    Number(f64),
+
+    /// See `Value::Bool`
+    Bool(bool),
+
+    /// See `Value::String`
+    String(Cow<'a, str>),
+
+    /// See `Value::Object`
+    Object {
+        /// The unique id for this object, referenced by `Amf3ObjectReference` instances
+        id: ObjectId,
+
+        /// The data within this value
+        data: ObjectValueRef<'a>,
+    },
+
+    /// See `Value::Null`
+    Null,
+
+    /// See `Value::Undefined`
+    Undefined,
+
+    /// See `Value::ECMAArray` and `EcmaArray`
+    ECMAArray {
+        /// The unique id for this array
+        id: ObjectId,
+
+        /// The values at the dense indices
+        dense: Vec<ValueRef<'a>>,
+
+        /// The named entries
+        assoc: Vec<ElementRef<'a>>,
+
+        /// The length that the array was declared with
+        declared_len: u32,
+    },
+
+    /// See `Value::StrictArray`
+    StrictArray(ObjectId, Vec<ValueRef<'a>>),
+
+    /// See `Value::Date`
+    Date(f64, Option<u16>),
+
+    /// See `Value::Unsupported`
+    Unsupported,
+
+    /// See `Value::XML`
+    XML(Cow<'a, str>, bool),
+
+    /// See `Value::AMF3`
+    #[cfg(feature = "amf3")]
+    AMF3(Box<ValueRef<'a>>),
+
+    /// See `Value::Integer`
+    Integer(i32),
+
+    /// See `Value::ByteArray`
+    ByteArray(&'a [u8]),
+
+    /// See `Value::VectorInt`
+    VectorInt(Vec<i32>, bool),
+
+    /// See `Value::VectorUInt`
+    VectorUInt(Vec<u32>, bool),
+
+    /// See `Value::VectorDouble`
+    VectorDouble(Vec<f64>, bool),
+
+    /// See `Value::VectorObject` and `ObjectVector`
+    VectorObject {
+        /// The unique id for this vector
+        id: ObjectId,
+
+        /// The items of the vector
+        items: Vec<ValueRef<'a>>,
+
+        /// The name of the type of the items
+        type_name: Cow<'a, str>,
+
+        /// Whether the vector has a fixed length
+        fixed_length: bool,
+    },
+
+    /// See `Value::Dictionary` and `Dictionary`
+    Dictionary {
+        /// The unique id for this dictionary
+        id: ObjectId,
+
+        /// The key value pairs of the dictionary
+        entries: Vec<(ValueRef<'a>, ValueRef<'a>)>,
+
+        /// Whether the keys are weakly referenced
+        weak_keys: bool,
+    },
+
+    /// See `Value::Custom` and `CustomObject`
+    Custom {
+        /// The externalized elements, which are decoded by the registered decoders and are always owned
+        custom_elements: Vec<Element>,
+
+        /// The dynamic elements
+        elements: Vec<ElementRef<'a>>,
+
+        /// The class definition of the object
+        class_definition: Option<ClassDefinitionRef<'a>>,
+    },
+
+    /// See `Value::Reference`
+    Reference(Reference),
+
+    /// See `Value::Amf3ObjectReference`
+    Amf3ObjectReference(ObjectId),
+}
+
+fn into_owned_values(values: Vec<ValueRef<'_>>) -> Vec<Value> {
+    values.into_iter().map(ValueRef::into_owned).collect()
+}
+
+fn into_owned_elements(elements: Vec<ElementRef<'_>>) -> Vec<Element> {
+    elements.into_iter().map(ElementRef::into_owned).collect()
+}
+
+impl ValueRef<'_> {
+    /// Copy the borrowed data, creating a `Value`
+    pub fn into_owned(self) -> Value {
+        match self {
+            ValueRef::Number(n) => Value::Number(n),
+            ValueRef::Bool(b) => Value::Bool(b),
+            ValueRef::String(s) => Value::String(s.into_owned()),
+            ValueRef::Object { id, data } => Value::Object {
+                id,
+                data: ObjectValue {
+                    elements: into_owned_elements(data.elements),
+                    class_definition: data.class_definition.map(ClassDefinitionRef::into_owned),
+                },
+            },
+            ValueRef::Null => Value::Null,
+            ValueRef::Undefined => Value::Undefined,
+            ValueRef::ECMAArray {
+                id,
+                dense,
+                assoc,
+                declared_len,
+            } => Value::ECMAArray(EcmaArray {
+                id,
+                dense: into_owned_values(dense),
+                assoc: into_owned_elements(assoc),
+                declared_len,
+            }),
+            ValueRef::StrictArray(id, values) => Value::StrictArray(id, into_owned_values(values)),
+            ValueRef::Date(millis, time_zone) => Value::Date(millis, time_zone),
+            ValueRef::Unsupported => Value::Unsupported,
+            ValueRef::XML(content, string) => Value::XML(content.into_owned(), string),
+            #[cfg(feature = "amf3")]
+            ValueRef::AMF3(value) => Value::AMF3(Box::new(value.into_owned())),
+            ValueRef::Integer(i) => Value::Integer(i),
+            ValueRef::ByteArray(bytes) => Value::ByteArray(bytes.to_vec()),
+            ValueRef::VectorInt(items, fixed_length) => Value::VectorInt(items, fixed_length),
+            ValueRef::VectorUInt(items, fixed_length) => Value::VectorUInt(items, fixed_length),
+            ValueRef::VectorDouble(items, fixed_length) => Value::VectorDouble(items, fixed_length),
+            ValueRef::VectorObject {
+                id,
+                items,
+                type_name,
+                fixed_length,
+            } => Value::VectorObject(ObjectVector {
+                id,
+                items: into_owned_values(items),
+                type_name: type_name.into_owned(),
+                fixed_length,
+            }),
+            ValueRef::Dictionary {
+                id,
+                entries,
+                weak_keys,
+            } => Value::Dictionary(Dictionary {
+                id,
+                entries: entries
+                    .into_iter()
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect(),
+                weak_keys,
+            }),
+            ValueRef::Custom {
+                custom_elements,
+                elements,
+                class_definition,
+            } => Value::Custom(CustomObject {
+                custom_elements,
+                elements: into_owned_elements(elements),
+                class_definition: class_definition.map(ClassDefinitionRef::into_owned),
+            }),
+            ValueRef::Reference(r) => Value::Reference(r),
+            ValueRef::Amf3ObjectReference(id) => Value::Amf3ObjectReference(id),
+        }
+    }
+}
+
+/// An Lso whose body borrows from the input, see `Reader::parse_borrowed`
+#[derive(Clone, Debug, PartialEq)]
+pub struct LsoRef<'a> {
+    /// The header of this Lso
+    pub header: Header,
+
+    /// The elements at the root level of this Lso
+    pub body: Vec<ElementRef<'a>>,
+}
+
+impl LsoRef<'_> {
+    /// Copy the borrowed data, creating an `Lso`
+    pub fn into_owned(self) -> Lso {
+        Lso {
+            header: self.header,
+            body: into_owned_elements(self.body),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use super::Attribute;
+use crate::prelude::*;
 use enumset::EnumSet;
 
 /// A class definition (trait) used in AMF3
@@ -0,0 +1,171 @@
+use super::{
+    CustomObject, Dictionary, EcmaArray, Element, ObjectId, ObjectValue, ObjectVector, Value,
+};
+use crate::alias::{FromAmf, ToAmf};
+use crate::errors::ConvertError;
+use crate::prelude::*;
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+/// Implement `From<T> for Value` and `TryFrom<&Value> for T` with the conversion traits
+macro_rules! primitive {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Value {
+                fn from(value: $t) -> Self {
+                    value.to_amf()
+                }
+            }
+
+            impl TryFrom<&Value> for $t {
+                type Error = ConvertError;
+
+                fn try_from(value: &Value) -> Result<Self, Self::Error> {
+                    <$t>::from_amf(value)
+                }
+            }
+        )*
+    };
+}
+
+primitive!(f64, f32, i32, u32, i16, u16, i8, u8, bool, String);
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<&String> for Value {
+    fn from(value: &String) -> Self {
+        Value::String(value.clone())
+    }
+}
+
+impl From<Cow<'_, str>> for Value {
+    fn from(value: Cow<'_, str>) -> Self {
+        Value::String(value.into_owned())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(value: Vec<T>) -> Self {
+        Value::StrictArray(
+            ObjectId::INVALID,
+            value.into_iter().map(Into::into).collect(),
+        )
+    }
+}
+
+impl<T: Clone + Into<Value>> From<&[T]> for Value {
+    fn from(value: &[T]) -> Self {
+        Value::StrictArray(
+            ObjectId::INVALID,
+            value.iter().cloned().map(Into::into).collect(),
+        )
+    }
+}
+
+impl<K: Into<String>, V: Into<Value>> From<BTreeMap<K, V>> for Value {
+    fn from(value: BTreeMap<K, V>) -> Self {
+        Value::object(
+            value
+                .into_iter()
+                .map(|(k, v)| Element::new(k, v.into()))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Into<String>, V: Into<Value>, S> From<HashMap<K, V, S>> for Value {
+    fn from(value: HashMap<K, V, S>) -> Self {
+        Value::object(
+            value
+                .into_iter()
+                .map(|(k, v)| Element::new(k, v.into()))
+                .collect(),
+        )
+    }
+}
+
+impl From<ObjectValue> for Value {
+    fn from(data: ObjectValue) -> Self {
+        Value::Object {
+            id: ObjectId::INVALID,
+            data,
+        }
+    }
+}
+
+impl From<EcmaArray> for Value {
+    fn from(value: EcmaArray) -> Self {
+        Value::ECMAArray(value)
+    }
+}
+
+impl From<ObjectVector> for Value {
+    fn from(value: ObjectVector) -> Self {
+        Value::VectorObject(value)
+    }
+}
+
+impl From<Dictionary> for Value {
+    fn from(value: Dictionary) -> Self {
+        Value::Dictionary(value)
+    }
+}
+
+impl From<CustomObject> for Value {
+    fn from(value: CustomObject) -> Self {
+        Value::Custom(value)
+    }
+}
+
+impl<T: FromAmf> TryFrom<&Value> for Vec<T> {
+    type Error = ConvertError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        Vec::from_amf(value)
+    }
+}
+
+/// Get the elements of an object or the named entries of an ECMA array
+fn entries(value: &Value) -> Result<&[Element], ConvertError> {
+    match value {
+        Value::Object { data, .. } => Ok(&data.elements),
+        Value::ECMAArray(array) => Ok(&array.assoc),
+        _ => Err(ConvertError::UnexpectedType("object")),
+    }
+}
+
+impl<V: FromAmf> TryFrom<&Value> for BTreeMap<String, V> {
+    type Error = ConvertError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        entries(value)?
+            .iter()
+            .map(|e| Ok((e.name.clone(), V::from_amf(&e.value)?)))
+            .collect()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V: FromAmf> TryFrom<&Value> for HashMap<String, V> {
+    type Error = ConvertError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        entries(value)?
+            .iter()
+            .map(|e| Ok((e.name.clone(), V::from_amf(&e.value)?)))
+            .collect()
+    }
+}
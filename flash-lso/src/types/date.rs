@@ -0,0 +1,405 @@
+//! Helpers for working with `Value::Date`
+//!
+//! A date is stored as milliseconds since the unix epoch, in UTC, along with the raw AMF0 timezone if there was one.
+//! With the `chrono` and `time` features dates can be converted to and from `DateTime<Utc>`, `DateTime<FixedOffset>`
+//! and `OffsetDateTime`
+
+use crate::errors::ConvertError;
+use crate::prelude::*;
+use alloc::format;
+
+/// The largest absolute value of an ActionScript `Date`, in milliseconds since the epoch
+pub const MAX_TIME_MS: f64 = 8.64e15;
+
+const NANOS_PER_MILLI: i128 = 1_000_000;
+const MILLIS_PER_DAY: i128 = 86_400_000;
+
+/// Get the offset from UTC in minutes, positive east of UTC, described by the raw timezone of a `Value::Date`
+///
+/// Flash Player writes the result of `Date.getTimezoneOffset()` as a signed 16-bit integer, this is the number
+/// of minutes that local time is *behind* UTC. So a raw timezone of 240 is UTC-04:00 and this returns -240
+pub fn timezone_offset_minutes(timezone: u16) -> i32 {
+    -i32::from(timezone as i16)
+}
+
+/// Get the raw timezone of a `Value::Date` for an offset from UTC in minutes, positive east of UTC
+///
+/// This is the inverse of `timezone_offset_minutes`
+pub fn timezone_from_offset_minutes(offset: i32) -> Result<u16, ConvertError> {
+    i16::try_from(-offset)
+        .map(|tz| tz as u16)
+        .map_err(|_| ConvertError::InvalidDate)
+}
+
+/// Convert the time value of a date into nanoseconds since the epoch
+///
+/// This fails for NaN, infinite and out of range values, which an ActionScript `Date` treats as an invalid date
+pub fn unix_nanos(millis: f64) -> Result<i128, ConvertError> {
+    if !(-MAX_TIME_MS..=MAX_TIME_MS).contains(&millis) {
+        return Err(ConvertError::InvalidDate);
+    }
+
+    // Split before scaling, so that whole milliseconds are always exact
+    let whole = millis as i64;
+    let fraction = ((millis - whole as f64) * 1_000_000.0) as i64;
+    Ok(i128::from(whole) * NANOS_PER_MILLI + i128::from(fraction))
+}
+
+/// Convert nanoseconds since the epoch into the time value of a date
+pub fn millis_from_unix_nanos(nanos: i128) -> f64 {
+    let whole = nanos.div_euclid(NANOS_PER_MILLI);
+    let fraction = nanos.rem_euclid(NANOS_PER_MILLI);
+    whole as f64 + fraction as f64 / NANOS_PER_MILLI as f64
+}
+
+/// Convert a number of days since the epoch into a (year, month, day) triple in the proleptic Gregorian calendar
+fn civil_from_days(days: i128) -> (i128, i128, i128) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i128::from(month <= 2);
+    (year, month, day)
+}
+
+/// Format the time value of a date as ISO-8601 in UTC, matching ActionScript's `Date.toISOString`
+///
+/// Years outside of 0 to 9999 are written with a sign and six digits, for example `+275760-09-13T00:00:00.000Z`
+pub fn to_iso8601(millis: f64) -> Result<String, ConvertError> {
+    let millis = unix_nanos(millis)?.div_euclid(NANOS_PER_MILLI);
+    let (year, month, day) = civil_from_days(millis.div_euclid(MILLIS_PER_DAY));
+    let time = millis.rem_euclid(MILLIS_PER_DAY);
+
+    let year = if (0..=9999).contains(&year) {
+        format!("{year:04}")
+    } else {
+        format!("{year:+07}")
+    };
+    Ok(format!(
+        "{year}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        time / 3_600_000,
+        time / 60_000 % 60,
+        time / 1000 % 60,
+        time % 1000
+    ))
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_impls {
+    use super::{millis_from_unix_nanos, timezone_from_offset_minutes, unix_nanos};
+    use crate::alias::{FromAmf, ToAmf};
+    use crate::errors::ConvertError;
+    use crate::types::Value;
+    use chrono::{DateTime, FixedOffset, Utc};
+
+    const NANOS_PER_SECOND: i128 = 1_000_000_000;
+
+    fn date_nanos(value: &Value) -> Result<(i128, Option<u16>), ConvertError> {
+        match value {
+            Value::Date(millis, tz) => Ok((unix_nanos(*millis)?, *tz)),
+            _ => Err(ConvertError::UnexpectedType("date")),
+        }
+    }
+
+    fn nanos_of<Tz: chrono::TimeZone>(date: &DateTime<Tz>) -> i128 {
+        i128::from(date.timestamp()) * NANOS_PER_SECOND + i128::from(date.timestamp_subsec_nanos())
+    }
+
+    impl TryFrom<&Value> for DateTime<Utc> {
+        type Error = ConvertError;
+
+        fn try_from(value: &Value) -> Result<Self, Self::Error> {
+            let (nanos, _) = date_nanos(value)?;
+            let secs = i64::try_from(nanos.div_euclid(NANOS_PER_SECOND))
+                .map_err(|_| ConvertError::InvalidDate)?;
+            let subsec = nanos.rem_euclid(NANOS_PER_SECOND) as u32;
+            DateTime::from_timestamp(secs, subsec).ok_or(ConvertError::InvalidDate)
+        }
+    }
+
+    /// Uses the AMF0 timezone as the offset, or UTC if there isn't one
+    impl TryFrom<&Value> for DateTime<FixedOffset> {
+        type Error = ConvertError;
+
+        fn try_from(value: &Value) -> Result<Self, Self::Error> {
+            let utc = DateTime::<Utc>::try_from(value)?;
+            let (_, tz) = date_nanos(value)?;
+            let minutes = tz.map_or(0, super::timezone_offset_minutes);
+            let offset = FixedOffset::east_opt(minutes * 60).ok_or(ConvertError::InvalidDate)?;
+            Ok(utc.with_timezone(&offset))
+        }
+    }
+
+    impl From<DateTime<Utc>> for Value {
+        fn from(date: DateTime<Utc>) -> Self {
+            Value::Date(millis_from_unix_nanos(nanos_of(&date)), None)
+        }
+    }
+
+    /// Writes the offset as the AMF0 timezone, any seconds in the offset are dropped
+    impl From<DateTime<FixedOffset>> for Value {
+        fn from(date: DateTime<FixedOffset>) -> Self {
+            let minutes = date.offset().local_minus_utc() / 60;
+            Value::Date(
+                millis_from_unix_nanos(nanos_of(&date)),
+                timezone_from_offset_minutes(minutes).ok(),
+            )
+        }
+    }
+
+    impl FromAmf for DateTime<Utc> {
+        fn from_amf(value: &Value) -> Result<Self, ConvertError> {
+            value.try_into()
+        }
+    }
+
+    impl ToAmf for DateTime<Utc> {
+        fn to_amf(&self) -> Value {
+            (*self).into()
+        }
+    }
+
+    impl FromAmf for DateTime<FixedOffset> {
+        fn from_amf(value: &Value) -> Result<Self, ConvertError> {
+            value.try_into()
+        }
+    }
+
+    impl ToAmf for DateTime<FixedOffset> {
+        fn to_amf(&self) -> Value {
+            (*self).into()
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+mod time_impls {
+    use super::{millis_from_unix_nanos, timezone_from_offset_minutes, unix_nanos};
+    use crate::alias::{FromAmf, ToAmf};
+    use crate::errors::ConvertError;
+    use crate::types::Value;
+    use time::{OffsetDateTime, UtcOffset};
+
+    /// Uses the AMF0 timezone as the offset, or UTC if there isn't one
+    impl TryFrom<&Value> for OffsetDateTime {
+        type Error = ConvertError;
+
+        fn try_from(value: &Value) -> Result<Self, Self::Error> {
+            let Value::Date(millis, tz) = value else {
+                return Err(ConvertError::UnexpectedType("date"));
+            };
+
+            let utc = OffsetDateTime::from_unix_timestamp_nanos(unix_nanos(*millis)?)
+                .map_err(|_| ConvertError::InvalidDate)?;
+            let minutes = tz.map_or(0, super::timezone_offset_minutes);
+            let offset = UtcOffset::from_whole_seconds(minutes * 60)
+                .map_err(|_| ConvertError::InvalidDate)?;
+            utc.checked_to_offset(offset)
+                .ok_or(ConvertError::InvalidDate)
+        }
+    }
+
+    /// Writes the offset as the AMF0 timezone, any seconds in the offset are dropped
+    impl From<OffsetDateTime> for Value {
+        fn from(date: OffsetDateTime) -> Self {
+            Value::Date(
+                millis_from_unix_nanos(date.unix_timestamp_nanos()),
+                timezone_from_offset_minutes(i32::from(date.offset().whole_minutes())).ok(),
+            )
+        }
+    }
+
+    impl FromAmf for OffsetDateTime {
+        fn from_amf(value: &Value) -> Result<Self, ConvertError> {
+            value.try_into()
+        }
+    }
+
+    impl ToAmf for OffsetDateTime {
+        fn to_amf(&self) -> Value {
+            (*self).into()
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+pub use iso8601::Iso8601Dates;
+
+#[cfg(feature = "serde")]
+mod iso8601 {
+    use super::to_iso8601;
+    use crate::prelude::*;
+    use crate::types::{ClassDefinition, Element, Header, Lso, ObjectId, Reference, Value};
+    use serde::{Serialize, Serializer};
+
+    /// Serializes the wrapped `Lso`, `Element` or `Value` with dates as ISO-8601 strings
+    ///
+    /// The output otherwise matches the normal serde output, `Value::Date(ms, tz)` is written as
+    /// `Date(iso8601, tz)` with invalid dates written as `None`. This is for output only, it can't be deserialized
+    /// back into a `Value`
+    #[derive(Debug, Copy, Clone)]
+    pub struct Iso8601Dates<'a, T: ?Sized>(pub &'a T);
+
+    /// Serializes a slice of values as a sequence of `Iso8601Dates`
+    struct Seq<'a, T>(&'a [T]);
+
+    impl<'a, T> Serialize for Seq<'a, T>
+    where
+        Iso8601Dates<'a, T>: Serialize,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.0.iter().map(Iso8601Dates))
+        }
+    }
+
+    /// Serializes dictionary pairs as a sequence of `Iso8601Dates` tuples
+    struct Pairs<'a>(&'a [(Value, Value)]);
+
+    impl Serialize for Pairs<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(
+                self.0
+                    .iter()
+                    .map(|(k, v)| (Iso8601Dates(k), Iso8601Dates(v))),
+            )
+        }
+    }
+
+    // These mirror the types in `crate::types` so that the derived output is identical, apart from dates
+
+    #[derive(Serialize)]
+    #[serde(rename = "Lso")]
+    struct IsoLso<'a> {
+        header: &'a Header,
+        body: Seq<'a, Element>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename = "Element")]
+    struct IsoElement<'a> {
+        name: &'a String,
+        value: Iso8601Dates<'a, Value>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename = "ObjectValue")]
+    struct IsoObjectValue<'a> {
+        elements: Seq<'a, Element>,
+        class_definition: &'a Option<ClassDefinition>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename = "Value")]
+    #[allow(clippy::upper_case_acronyms)]
+    enum IsoValue<'a> {
+        Number(&'a f64),
+        Bool(&'a bool),
+        String(&'a String),
+        Object {
+            id: &'a ObjectId,
+            data: IsoObjectValue<'a>,
+        },
+        Null,
+        Undefined,
+        ECMAArray(&'a ObjectId, Seq<'a, Value>, Seq<'a, Element>, &'a u32),
+        StrictArray(&'a ObjectId, Seq<'a, Value>),
+        Date(Option<String>, &'a Option<u16>),
+        Unsupported,
+        XML(&'a String, &'a bool),
+        #[cfg(feature = "amf3")]
+        AMF3(Iso8601Dates<'a, Value>),
+        Integer(&'a i32),
+        ByteArray(&'a Vec<u8>),
+        VectorInt(&'a Vec<i32>, &'a bool),
+        VectorUInt(&'a Vec<u32>, &'a bool),
+        VectorDouble(&'a Vec<f64>, &'a bool),
+        VectorObject(&'a ObjectId, Seq<'a, Value>, &'a String, &'a bool),
+        Dictionary(&'a ObjectId, Pairs<'a>, &'a bool),
+        Custom(
+            Seq<'a, Element>,
+            Seq<'a, Element>,
+            &'a Option<ClassDefinition>,
+        ),
+        Reference(&'a Reference),
+        Amf3ObjectReference(&'a ObjectId),
+    }
+
+    impl Serialize for Iso8601Dates<'_, Lso> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            IsoLso {
+                header: &self.0.header,
+                body: Seq(&self.0.body),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl Serialize for Iso8601Dates<'_, Element> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            IsoElement {
+                name: &self.0.name,
+                value: Iso8601Dates(&self.0.value),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl Serialize for Iso8601Dates<'_, Value> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let value = match self.0 {
+                Value::Number(n) => IsoValue::Number(n),
+                Value::Bool(b) => IsoValue::Bool(b),
+                Value::String(s) => IsoValue::String(s),
+                Value::Object { id, data } => IsoValue::Object {
+                    id,
+                    data: IsoObjectValue {
+                        elements: Seq(&data.elements),
+                        class_definition: &data.class_definition,
+                    },
+                },
+                Value::Null => IsoValue::Null,
+                Value::Undefined => IsoValue::Undefined,
+                Value::ECMAArray(array) => IsoValue::ECMAArray(
+                    &array.id,
+                    Seq(&array.dense),
+                    Seq(&array.assoc),
+                    &array.declared_len,
+                ),
+                Value::StrictArray(id, values) => IsoValue::StrictArray(id, Seq(values)),
+                Value::Date(millis, tz) => IsoValue::Date(to_iso8601(*millis).ok(), tz),
+                Value::Unsupported => IsoValue::Unsupported,
+                Value::XML(s, is_string) => IsoValue::XML(s, is_string),
+                #[cfg(feature = "amf3")]
+                Value::AMF3(v) => IsoValue::AMF3(Iso8601Dates(v)),
+                Value::Integer(i) => IsoValue::Integer(i),
+                Value::ByteArray(b) => IsoValue::ByteArray(b),
+                Value::VectorInt(v, fixed) => IsoValue::VectorInt(v, fixed),
+                Value::VectorUInt(v, fixed) => IsoValue::VectorUInt(v, fixed),
+                Value::VectorDouble(v, fixed) => IsoValue::VectorDouble(v, fixed),
+                Value::VectorObject(vector) => IsoValue::VectorObject(
+                    &vector.id,
+                    Seq(&vector.items),
+                    &vector.type_name,
+                    &vector.fixed_length,
+                ),
+                Value::Dictionary(dictionary) => IsoValue::Dictionary(
+                    &dictionary.id,
+                    Pairs(&dictionary.entries),
+                    &dictionary.weak_keys,
+                ),
+                Value::Custom(custom) => IsoValue::Custom(
+                    Seq(&custom.custom_elements),
+                    Seq(&custom.elements),
+                    &custom.class_definition,
+                ),
+                Value::Reference(r) => IsoValue::Reference(r),
+                Value::Amf3ObjectReference(id) => IsoValue::Amf3ObjectReference(id),
+            };
+            value.serialize(serializer)
+        }
+    }
+}
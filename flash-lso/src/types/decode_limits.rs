@@ -0,0 +1,59 @@
+use crate::errors::Error;
+
+/// Limits on the values a single parse may decode, to bound the resources used by untrusted input
+///
+/// The default has no limits, beyond those of the input itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// How deeply values may be nested inside of arrays, objects, vectors and dictionaries, a value at the root of a body has a depth of 1
+    pub max_depth: usize,
+
+    /// The most items, elements or entries a single array, object, vector or dictionary may have
+    pub max_collection_length: usize,
+
+    /// The most entries each AMF3 reference table of a parse may have, strings, traits and objects
+    pub max_references: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self::UNLIMITED
+    }
+}
+
+impl DecodeLimits {
+    /// No limits at all
+    pub const UNLIMITED: Self = Self {
+        max_depth: usize::MAX,
+        max_collection_length: usize::MAX,
+        max_references: usize::MAX,
+    };
+
+    /// Check that a value nested `depth` levels deep may be decoded
+    pub(crate) fn check_depth(&self, depth: usize) -> Result<(), nom::Err<Error<'static>>> {
+        Self::check(depth, self.max_depth, "max_depth")
+    }
+
+    /// Check that a collection of `length` items may be decoded
+    pub(crate) fn check_length(&self, length: usize) -> Result<(), nom::Err<Error<'static>>> {
+        Self::check(length, self.max_collection_length, "max_collection_length")
+    }
+
+    /// Check that another entry may be added to a reference table that has `len` entries
+    #[cfg(feature = "amf3")]
+    pub(crate) fn check_references(&self, len: usize) -> Result<(), nom::Err<Error<'static>>> {
+        Self::check(len.saturating_add(1), self.max_references, "max_references")
+    }
+
+    fn check(
+        value: usize,
+        max: usize,
+        limit: &'static str,
+    ) -> Result<(), nom::Err<Error<'static>>> {
+        if value > max {
+            Err(nom::Err::Failure(Error::LimitExceeded(limit)))
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use super::Value;
+use crate::prelude::*;
 
 /// Represent a named element
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
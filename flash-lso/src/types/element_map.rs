@@ -0,0 +1,249 @@
+//! Looking up elements by name, in the lists of elements that Flash treats as maps
+//!
+//! The elements keep the order they are stored in. When several have the same name the last one is used, as it is
+//! by Flash Player
+
+use super::{CustomObject, EcmaArray, Element, Lso, ObjectValue, Value};
+use crate::errors::Error;
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+
+/// How elements with the same name as an earlier element of the same object are handled when decoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    /// Keep every element as it is stored
+    #[default]
+    Keep,
+
+    /// Keep one element for each name, at the position of the first with the value of the last, as Flash Player does
+    LastWins,
+
+    /// Fail to decode, with `Error::DuplicateKey`
+    Reject,
+}
+
+impl DuplicateKeys {
+    /// Apply this policy to a list of decoded elements
+    pub(crate) fn apply<E>(
+        self,
+        elements: &mut Vec<E>,
+        name: impl Fn(&E) -> &str,
+    ) -> Result<(), nom::Err<Error<'static>>> {
+        if self == DuplicateKeys::Keep || elements.len() < 2 {
+            return Ok(());
+        }
+
+        let mut positions = BTreeMap::new();
+        let mut kept = Vec::with_capacity(elements.len());
+        for element in elements.drain(..) {
+            match positions.get(name(&element)) {
+                Some(&index) if self == DuplicateKeys::LastWins => kept[index] = element,
+                Some(_) => {
+                    return Err(nom::Err::Failure(Error::DuplicateKey(
+                        name(&element).to_string(),
+                    )));
+                }
+                None => {
+                    positions.insert(name(&element).to_string(), kept.len());
+                    kept.push(element);
+                }
+            }
+        }
+        *elements = kept;
+        Ok(())
+    }
+}
+
+/// A view into a single element of a list, which may not exist yet
+#[derive(Debug)]
+pub enum Entry<'a> {
+    /// There is an element with the name
+    Occupied(OccupiedEntry<'a>),
+
+    /// There is no element with the name
+    Vacant(VacantEntry<'a>),
+}
+
+/// An element that exists, see `Entry`
+#[derive(Debug)]
+pub struct OccupiedEntry<'a> {
+    elements: &'a mut Vec<Element>,
+    index: usize,
+}
+
+/// An element that does not exist yet, see `Entry`
+#[derive(Debug)]
+pub struct VacantEntry<'a> {
+    elements: &'a mut Vec<Element>,
+    name: String,
+}
+
+impl<'a> Entry<'a> {
+    /// The name of the element
+    pub fn key(&self) -> &str {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Get the value, appending an element with the given value if there is none
+    pub fn or_insert(self, default: Value) -> &'a mut Value {
+        self.or_insert_with(|| default)
+    }
+
+    /// Get the value, appending an element with the result of the given function if there is none
+    pub fn or_insert_with(self, default: impl FnOnce() -> Value) -> &'a mut Value {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Modify the value if there is an element
+    pub fn and_modify(mut self, f: impl FnOnce(&mut Value)) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a> OccupiedEntry<'a> {
+    /// The name of the element
+    pub fn key(&self) -> &str {
+        &self.elements[self.index].name
+    }
+
+    /// Get the value
+    pub fn get(&self) -> &Value {
+        &self.elements[self.index].value
+    }
+
+    /// Get the value mutably
+    pub fn get_mut(&mut self) -> &mut Value {
+        &mut self.elements[self.index].value
+    }
+
+    /// Get the value mutably, for the lifetime of the list
+    pub fn into_mut(self) -> &'a mut Value {
+        &mut self.elements[self.index].value
+    }
+
+    /// Replace the value, returning the previous one
+    pub fn insert(&mut self, value: Value) -> Value {
+        core::mem::replace(self.get_mut(), value)
+    }
+
+    /// Remove every element with this name, returning the value that was used
+    pub fn remove(self) -> Value {
+        let name = self.elements[self.index].name.clone();
+        remove(self.elements, &name).expect("Occupied entry has an element")
+    }
+}
+
+impl<'a> VacantEntry<'a> {
+    /// The name of the element
+    pub fn key(&self) -> &str {
+        &self.name
+    }
+
+    /// Append an element with the given value
+    pub fn insert(self, value: Value) -> &'a mut Value {
+        self.elements.push(Element::new(self.name, value));
+        let last = self.elements.len() - 1;
+        &mut self.elements[last].value
+    }
+}
+
+fn position(elements: &[Element], name: &str) -> Option<usize> {
+    elements.iter().rposition(|e| e.name == name)
+}
+
+fn insert(elements: &mut Vec<Element>, name: impl Into<String>, value: Value) -> Option<Value> {
+    match entry(elements, name) {
+        Entry::Occupied(mut entry) => Some(entry.insert(value)),
+        Entry::Vacant(entry) => {
+            entry.insert(value);
+            None
+        }
+    }
+}
+
+fn remove(elements: &mut Vec<Element>, name: &str) -> Option<Value> {
+    let index = position(elements, name)?;
+    let value = elements.remove(index).value;
+    elements.retain(|e| e.name != name);
+    Some(value)
+}
+
+fn entry(elements: &mut Vec<Element>, name: impl Into<String>) -> Entry<'_> {
+    let name = name.into();
+    match position(elements, &name) {
+        Some(index) => Entry::Occupied(OccupiedEntry { elements, index }),
+        None => Entry::Vacant(VacantEntry { elements, name }),
+    }
+}
+
+/// Implement the map methods for a type, over the given list of elements
+macro_rules! element_map {
+    ($($t:ty => $field:ident),*) => {
+        $(
+            impl $t {
+                /// Get the value of the element with the given name
+                pub fn get(&self, name: &str) -> Option<&Value> {
+                    position(&self.$field, name).map(|index| &self.$field[index].value)
+                }
+
+                /// Get the value of the element with the given name mutably
+                pub fn get_mut(&mut self, name: &str) -> Option<&mut Value> {
+                    position(&self.$field, name).map(|index| &mut self.$field[index].value)
+                }
+
+                /// Whether there is an element with the given name
+                pub fn contains_key(&self, name: &str) -> bool {
+                    position(&self.$field, name).is_some()
+                }
+
+                /// Replace the value of the element with the given name in place, or append an element if there is none
+                ///
+                /// Returns the previous value
+                pub fn insert(&mut self, name: impl Into<String>, value: Value) -> Option<Value> {
+                    insert(&mut self.$field, name, value)
+                }
+
+                /// Remove every element with the given name, returning the value that was used
+                pub fn remove(&mut self, name: &str) -> Option<Value> {
+                    remove(&mut self.$field, name)
+                }
+
+                /// Get the element with the given name for in place modification
+                pub fn entry(&mut self, name: impl Into<String>) -> Entry<'_> {
+                    entry(&mut self.$field, name)
+                }
+
+                /// Iterate over the names and values of the elements, in the order they are stored
+                pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+                    self.$field.iter().map(|e| (e.name.as_str(), &e.value))
+                }
+
+                /// Iterate over the names and mutable values of the elements, in the order they are stored
+                pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut Value)> {
+                    self.$field.iter_mut().map(|e| (e.name.as_str(), &mut e.value))
+                }
+
+                /// Iterate over the names of the elements, in the order they are stored
+                pub fn keys(&self) -> impl Iterator<Item = &str> {
+                    self.$field.iter().map(|e| e.name.as_str())
+                }
+            }
+        )*
+    };
+}
+
+element_map!(
+    Lso => body,
+    ObjectValue => elements,
+    EcmaArray => assoc,
+    CustomObject => elements
+);
@@ -1,4 +1,5 @@
 use super::{AMFVersion, Element, Header};
+use crate::prelude::*;
 
 /// A container for lso files
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -30,7 +31,7 @@ impl Lso {
 
 impl IntoIterator for Lso {
     type Item = Element;
-    type IntoIter = std::vec::IntoIter<Self::Item>;
+    type IntoIter = vec::IntoIter<Self::Item>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.body.into_iter()
@@ -1,4 +1,5 @@
 use super::AMFVersion;
+use crate::prelude::*;
 
 /// The header of a lso file
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -1,21 +1,33 @@
 mod amf_version;
 mod attribute;
+mod borrowed;
 mod class_definition;
+mod convert;
+/// Helpers for working with `Value::Date`
+pub mod date;
+mod decode_limits;
 mod element;
+mod element_map;
 mod lso;
 mod lso_header;
 mod reference;
 mod value;
+/// Parsed access to the content of `Value::XML`
+#[cfg(feature = "xml")]
+pub mod xml;
 
 mod object_id;
 
 pub use amf_version::AMFVersion;
 pub use attribute::Attribute;
+pub use borrowed::{ClassDefinitionRef, ElementRef, LsoRef, ObjectValueRef, ValueRef};
 pub use class_definition::ClassDefinition;
+pub use decode_limits::DecodeLimits;
 pub use element::Element;
+pub use element_map::{DuplicateKeys, Entry, OccupiedEntry, VacantEntry};
 pub use lso::Lso;
 pub use lso_header::Header;
 pub use object_id::ObjectId;
 pub use reference::Reference;
-pub use value::ObjectValue;
 pub use value::Value;
+pub use value::{CustomObject, Dictionary, EcmaArray, ObjectValue, ObjectVector};
@@ -2,3 +2,15 @@
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Reference(pub(crate) u16);
+
+impl Reference {
+    /// Create a reference to the value at the given index in the AMF0 reference table
+    pub fn new(index: u16) -> Self {
+        Self(index)
+    }
+
+    /// The index of the referenced value in the AMF0 reference table
+    pub fn index(&self) -> u16 {
+        self.0
+    }
+}
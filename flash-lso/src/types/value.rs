@@ -1,4 +1,5 @@
 use super::{ClassDefinition, Element, ObjectId, Reference};
+use crate::prelude::*;
 
 /// The data contained within a Value of type Object
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -11,7 +12,236 @@ pub struct ObjectValue {
     pub class_definition: Option<ClassDefinition>,
 }
 
-//TODO: should amf3 assoc arrays be their own type with a dense and assoc section
+/// An ECMA array (amf0) or an associative array (amf3), which has a dense part and an associative part
+///
+/// Both formats use this type: amf3 reads and writes the dense part as it is, while amf0 reads every value as a
+/// named one and writes the dense part as elements named by their index, so a value converted between the two
+/// keeps all of its items
+///
+/// Serialized as the tuple `(id, dense, assoc, declared_len)`
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(from = "(ObjectId, Vec<Value>, Vec<Element>, u32)")
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EcmaArray {
+    /// The unique id for this array, referenced by `Amf3ObjectReference` instances, only used in amf3
+    pub id: ObjectId,
+
+    /// The values at the indices from 0, written before the named values
+    pub dense: Vec<Value>,
+
+    /// The named values
+    pub assoc: Vec<Element>,
+
+    /// The length written before the array in amf0, this can differ from the actual number of elements
+    ///
+    /// Not written in amf3, where it is the number of named values that were read
+    pub declared_len: u32,
+}
+
+impl EcmaArray {
+    /// Create an array with the given named values and no dense part, declaring the number of values as its length
+    pub fn new(assoc: Vec<Element>) -> Self {
+        Self {
+            id: ObjectId::INVALID,
+            dense: Vec::new(),
+            declared_len: assoc.len() as u32,
+            assoc,
+        }
+    }
+
+    /// Get the value at the given index of the dense part, named values are found with `get`
+    pub fn get_index(&self, index: usize) -> Option<&Value> {
+        self.dense.get(index)
+    }
+
+    /// The `length` Flash Player gives this array, one more than the largest index of the dense part or of an
+    /// element named by a number
+    pub fn length(&self) -> u32 {
+        self.assoc
+            .iter()
+            .filter_map(|e| {
+                e.name
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|i| i.to_string() == e.name)
+            })
+            .filter_map(|i| i.checked_add(1))
+            .fold(self.dense.len() as u32, u32::max)
+    }
+}
+
+impl From<(ObjectId, Vec<Value>, Vec<Element>, u32)> for EcmaArray {
+    fn from((id, dense, assoc, declared_len): (ObjectId, Vec<Value>, Vec<Element>, u32)) -> Self {
+        Self {
+            id,
+            dense,
+            assoc,
+            declared_len,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for EcmaArray {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.id, &self.dense, &self.assoc, self.declared_len).serialize(serializer)
+    }
+}
+
+/// A vector of objects (amf3)
+///
+/// Serialized as the tuple `(id, items, type_name, fixed_length)`
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(from = "(ObjectId, Vec<Value>, String, bool)")
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectVector {
+    /// The unique id for this vector, referenced by `Amf3ObjectReference` instances
+    pub id: ObjectId,
+
+    /// The items of this vector
+    pub items: Vec<Value>,
+
+    /// The name of the class of the items, empty for `Object`
+    pub type_name: String,
+
+    /// Whether the vector has a fixed length
+    pub fixed_length: bool,
+}
+
+impl ObjectVector {
+    /// Get the item at the given index
+    pub fn get(&self, index: usize) -> Option<&Value> {
+        self.items.get(index)
+    }
+}
+
+impl From<(ObjectId, Vec<Value>, String, bool)> for ObjectVector {
+    fn from((id, items, type_name, fixed_length): (ObjectId, Vec<Value>, String, bool)) -> Self {
+        Self {
+            id,
+            items,
+            type_name,
+            fixed_length,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ObjectVector {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.id, &self.items, &self.type_name, self.fixed_length).serialize(serializer)
+    }
+}
+
+/// A dictionary (amf3), whose keys can be any value
+///
+/// Serialized as the tuple `(id, entries, weak_keys)`
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(from = "(ObjectId, Vec<(Value, Value)>, bool)")
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dictionary {
+    /// The unique id for this dictionary, referenced by `Amf3ObjectReference` instances
+    pub id: ObjectId,
+
+    /// The keys and values of this dictionary, in the order they are stored
+    pub entries: Vec<(Value, Value)>,
+
+    /// Whether the keys are weakly referenced
+    pub weak_keys: bool,
+}
+
+impl Dictionary {
+    /// Get the value of the first entry with the given key
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+}
+
+impl From<(ObjectId, Vec<(Value, Value)>, bool)> for Dictionary {
+    fn from((id, entries, weak_keys): (ObjectId, Vec<(Value, Value)>, bool)) -> Self {
+        Self {
+            id,
+            entries,
+            weak_keys,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Dictionary {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.id, &self.entries, self.weak_keys).serialize(serializer)
+    }
+}
+
+/// An externalized object, such as from flex
+///
+/// Serialized as the tuple `(custom_elements, elements, class_definition)`
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(from = "(Vec<Element>, Vec<Element>, Option<ClassDefinition>)")
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomObject {
+    /// The elements decoded from the externalized data by a custom decoder
+    pub custom_elements: Vec<Element>,
+
+    /// The regular elements of the object
+    pub elements: Vec<Element>,
+
+    /// The class definition of the object
+    pub class_definition: Option<ClassDefinition>,
+}
+
+impl CustomObject {
+    /// Get the value of the custom element with the given name, regular elements are found with `get`
+    pub fn get_custom(&self, name: &str) -> Option<&Value> {
+        self.custom_elements
+            .iter()
+            .rev()
+            .find(|e| e.name == name)
+            .map(|e| &e.value)
+    }
+}
+
+impl From<(Vec<Element>, Vec<Element>, Option<ClassDefinition>)> for CustomObject {
+    fn from(
+        (custom_elements, elements, class_definition): (
+            Vec<Element>,
+            Vec<Element>,
+            Option<ClassDefinition>,
+        ),
+    ) -> Self {
+        Self {
+            custom_elements,
+            elements,
+            class_definition,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for CustomObject {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (
+            &self.custom_elements,
+            &self.elements,
+            &self.class_definition,
+        )
+            .serialize(serializer)
+    }
+}
+
 /// A single or compound value
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
@@ -41,8 +271,7 @@ pub enum Value {
     Undefined,
 
     /// Represent ECMA-Arrays (amf0) and associative arrays (amf3, even if they contain a dense part)
-    /// Final value represents the length of the array in amf0, this can differ from the actual number of elements
-    ECMAArray(ObjectId, Vec<Value>, Vec<Element>, u32),
+    ECMAArray(EcmaArray),
 
     /// Represent a strict array (amf0) or a dense array (amf3)
     StrictArray(ObjectId, Vec<Value>),
@@ -80,16 +309,13 @@ pub enum Value {
     VectorDouble(Vec<f64>, bool),
 
     /// Represent the object vector type (amf3)
-    /// Format is (values, is_fixed_length)
-    VectorObject(ObjectId, Vec<Value>, String, bool),
+    VectorObject(ObjectVector),
 
     /// Represent the dictionary type (amf3)
-    /// Format is ((key, value), has_weak_keys)
-    Dictionary(ObjectId, Vec<(Value, Value)>, bool),
+    Dictionary(Dictionary),
 
     /// Represent a external object, such as from flex
-    /// (custom_elements, regular elements, class def)
-    Custom(Vec<Element>, Vec<Element>, Option<ClassDefinition>),
+    Custom(CustomObject),
 
     /// Represent an existing value, stored by reference, the value here should be considered opaque
     Reference(Reference),
@@ -103,3 +329,85 @@ pub enum Value {
     /// we leave the handling of this to the user, sorry
     Amf3ObjectReference(ObjectId),
 }
+
+impl Value {
+    /// Create an anonymous object with the given elements
+    pub fn object(elements: Vec<Element>) -> Self {
+        Value::Object {
+            id: ObjectId::INVALID,
+            data: ObjectValue {
+                elements,
+                class_definition: None,
+            },
+        }
+    }
+
+    /// Create an object of the given sealed class, which declares each of the elements as a static property
+    pub fn typed_object(class_name: impl Into<String>, elements: Vec<Element>) -> Self {
+        let static_properties = elements.iter().map(|e| e.name.clone()).collect();
+        Value::Object {
+            id: ObjectId::INVALID,
+            data: ObjectValue {
+                elements,
+                class_definition: Some(ClassDefinition {
+                    static_properties,
+                    ..ClassDefinition::default_with_name(class_name.into())
+                }),
+            },
+        }
+    }
+
+    /// Get the text of a `String` value
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Get the number of a `Number` or `Integer` value
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Integer(i) => Some(f64::from(*i)),
+            _ => None,
+        }
+    }
+
+    /// Get the integer of an `Integer` value
+    pub fn as_i32(&self) -> Option<i32> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Get the value of a `Bool` value
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Get the data of an `Object` value
+    pub fn as_object(&self) -> Option<&ObjectValue> {
+        match self {
+            Value::Object { data, .. } => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Get the values of a `StrictArray` value
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::StrictArray(_, values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Whether this is `Null` or `Undefined`
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null | Value::Undefined)
+    }
+}
@@ -0,0 +1,381 @@
+//! A navigable tree for the content of `Value::XML`
+//!
+//! The tree keeps the original declaration, whitespace, comments and the raw form of any unmodified start tags
+//! and text, so a document that is parsed and written back without changes is identical to the original
+
+use crate::errors::ConvertError;
+use crate::types::Value;
+use core::fmt;
+use quick_xml::XmlVersion;
+use quick_xml::escape::{escape, unescape};
+use quick_xml::events::{BytesStart, Event};
+
+/// A parsed XML value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlDocument {
+    /// The top level nodes, including any declaration and the whitespace between nodes
+    pub nodes: Vec<XmlNode>,
+
+    /// The `is_string` flag of the original `Value::XML`
+    ///
+    /// In AMF3 this is `true` for an E4X `XML` object and `false` for a legacy `XMLDocument`
+    pub is_string: bool,
+}
+
+/// A node in an XML tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XmlNode {
+    /// An element, `<name>...</name>`
+    Element(XmlElement),
+
+    /// Text content, including whitespace
+    Text(XmlText),
+
+    /// A CDATA section, `<![CDATA[...]]>`
+    CData(String),
+
+    /// A comment, `<!--...-->`
+    Comment(String),
+
+    /// The XML declaration, the content of `<?xml ...?>`
+    Declaration(String),
+
+    /// A processing instruction, the content of `<?...?>`
+    ProcessingInstruction(String),
+
+    /// A document type declaration, the content of `<!DOCTYPE ...>`
+    DocType(String),
+}
+
+/// Text content, stored in its escaped form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlText {
+    raw: String,
+}
+
+impl XmlText {
+    /// Create a text node, escaping the given text
+    pub fn new(text: &str) -> Self {
+        Self {
+            raw: escape(text).into_owned(),
+        }
+    }
+
+    /// The escaped text, as it appears in the document
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// The unescaped text
+    ///
+    /// Unknown entity references are left as they are
+    pub fn text(&self) -> String {
+        unescape(&self.raw).map_or_else(|_| self.raw.clone(), |text| text.into_owned())
+    }
+}
+
+/// An XML element
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlElement {
+    name: String,
+    attributes: Vec<(String, String)>,
+
+    /// The original content of the start tag, used until the name or attributes are modified
+    raw_start: Option<String>,
+
+    /// The original content of the end tag, used until the name is modified
+    raw_end: Option<String>,
+
+    /// If the element was written as `<name/>`, this is kept as long as the element has no children
+    self_closing: bool,
+
+    /// The child nodes of this element
+    pub children: Vec<XmlNode>,
+}
+
+impl XmlElement {
+    /// Create an empty element with the given name
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            attributes: Vec::new(),
+            raw_start: None,
+            raw_end: None,
+            self_closing: true,
+            children: Vec::new(),
+        }
+    }
+
+    /// The name of this element, including any namespace prefix
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Rename this element
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+        self.raw_start = None;
+        self.raw_end = None;
+    }
+
+    /// The unescaped and normalized attributes of this element, in document order
+    pub fn attributes(&self) -> &[(String, String)] {
+        &self.attributes
+    }
+
+    /// Get the unescaped value of an attribute
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Set the value of an attribute, adding it after any existing attributes if it isn't present
+    pub fn set_attribute(&mut self, name: &str, value: &str) {
+        match self.attributes.iter_mut().find(|(k, _)| k == name) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.attributes.push((name.to_string(), value.to_string())),
+        }
+        self.raw_start = None;
+    }
+
+    /// Remove an attribute, returning its value if it was present
+    pub fn remove_attribute(&mut self, name: &str) -> Option<String> {
+        let index = self.attributes.iter().position(|(k, _)| k == name)?;
+        self.raw_start = None;
+        Some(self.attributes.remove(index).1)
+    }
+
+    /// Iterate over the child elements of this element
+    pub fn elements(&self) -> impl Iterator<Item = &XmlElement> {
+        self.children.iter().filter_map(|node| match node {
+            XmlNode::Element(e) => Some(e),
+            _ => None,
+        })
+    }
+
+    /// Iterate mutably over the child elements of this element
+    pub fn elements_mut(&mut self) -> impl Iterator<Item = &mut XmlElement> {
+        self.children.iter_mut().filter_map(|node| match node {
+            XmlNode::Element(e) => Some(e),
+            _ => None,
+        })
+    }
+
+    /// Get the first child element with the given name
+    pub fn element(&self, name: &str) -> Option<&XmlElement> {
+        self.elements().find(|e| e.name == name)
+    }
+
+    /// Get the first child element with the given name, mutably
+    pub fn element_mut(&mut self, name: &str) -> Option<&mut XmlElement> {
+        self.elements_mut().find(|e| e.name == name)
+    }
+
+    /// The unescaped text and CDATA content directly inside this element
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for node in &self.children {
+            match node {
+                XmlNode::Text(t) => text.push_str(&t.text()),
+                XmlNode::CData(c) => text.push_str(c),
+                _ => {}
+            }
+        }
+        text
+    }
+
+    /// Replace the children of this element with the given text
+    pub fn set_text(&mut self, text: &str) {
+        self.children = vec![XmlNode::Text(XmlText::new(text))];
+    }
+
+    fn from_start(start: &BytesStart<'_>, self_closing: bool) -> Result<Self, ConvertError> {
+        let attributes = start
+            .attributes()
+            .map(|attr| {
+                let attr = attr.map_err(|e| ConvertError::InvalidXml(e.to_string()))?;
+                let value = attr
+                    .normalized_value(XmlVersion::default())
+                    .map_err(|e| ConvertError::InvalidXml(e.to_string()))?;
+                Ok((attr.key.as_ref().to_string(), value.into_owned()))
+            })
+            .collect::<Result<_, ConvertError>>()?;
+
+        Ok(Self {
+            name: start.name().as_ref().to_string(),
+            attributes,
+            raw_start: Some(start.to_string()),
+            raw_end: None,
+            self_closing,
+            children: Vec::new(),
+        })
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<")?;
+        match &self.raw_start {
+            Some(raw) => f.write_str(raw)?,
+            None => {
+                f.write_str(&self.name)?;
+                for (k, v) in &self.attributes {
+                    write!(f, " {}=\"{}\"", k, escape(v.as_str()))?;
+                }
+            }
+        }
+
+        if self.self_closing && self.children.is_empty() {
+            return f.write_str("/>");
+        }
+
+        f.write_str(">")?;
+        for child in &self.children {
+            child.write(f)?;
+        }
+        match &self.raw_end {
+            Some(raw) => write!(f, "</{raw}>"),
+            None => write!(f, "</{}>", self.name),
+        }
+    }
+}
+
+impl XmlNode {
+    fn write(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XmlNode::Element(e) => e.write(f),
+            XmlNode::Text(t) => f.write_str(&t.raw),
+            XmlNode::CData(c) => write!(f, "<![CDATA[{c}]]>"),
+            XmlNode::Comment(c) => write!(f, "<!--{c}-->"),
+            XmlNode::Declaration(d) | XmlNode::ProcessingInstruction(d) => write!(f, "<?{d}?>"),
+            XmlNode::DocType(d) => write!(f, "<!DOCTYPE {d}>"),
+        }
+    }
+}
+
+impl XmlDocument {
+    /// Parse the content of a `Value::XML`
+    pub fn parse(content: &str, is_string: bool) -> Result<Self, ConvertError> {
+        let mut reader = quick_xml::Reader::from_str(content);
+        reader.config_mut().trim_text(false);
+
+        // The element currently being read, along with its ancestors
+        let mut open: Vec<XmlElement> = Vec::new();
+        let mut nodes = Vec::new();
+
+        loop {
+            let position = reader.buffer_position() as usize;
+            let event = reader
+                .read_event()
+                .map_err(|e| ConvertError::InvalidXml(e.to_string()))?;
+
+            let node = match event {
+                Event::Start(start) => {
+                    open.push(XmlElement::from_start(&start, false)?);
+                    continue;
+                }
+                Event::End(_) => match open.pop() {
+                    Some(mut element) => {
+                        // Whitespace after the name isn't part of the event, so the tag is read from the input
+                        let end = reader.buffer_position() as usize;
+                        element.raw_end = content.get(position + 2..end - 1).map(str::to_string);
+                        XmlNode::Element(element)
+                    }
+                    None => return Err(ConvertError::InvalidXml("Unmatched end tag".into())),
+                },
+                Event::Empty(start) => XmlNode::Element(XmlElement::from_start(&start, true)?),
+                Event::Text(text) => XmlNode::Text(XmlText {
+                    raw: text.to_string(),
+                }),
+                Event::GeneralRef(reference) => XmlNode::Text(XmlText {
+                    raw: format!("&{};", &*reference),
+                }),
+                Event::CData(data) => XmlNode::CData(data.to_string()),
+                Event::Comment(comment) => XmlNode::Comment(comment.to_string()),
+                Event::Decl(decl) => XmlNode::Declaration(decl.to_string()),
+                Event::PI(pi) => XmlNode::ProcessingInstruction(pi.to_string()),
+                Event::DocType(doctype) => XmlNode::DocType(doctype.to_string()),
+                Event::Eof => break,
+            };
+
+            let siblings = match open.last_mut() {
+                Some(parent) => &mut parent.children,
+                None => &mut nodes,
+            };
+
+            // Entity references are read separately from the text around them
+            match (siblings.last_mut(), node) {
+                (Some(XmlNode::Text(prev)), XmlNode::Text(text)) => prev.raw.push_str(&text.raw),
+                (_, node) => siblings.push(node),
+            }
+        }
+
+        if let Some(element) = open.last() {
+            return Err(ConvertError::InvalidXml(format!(
+                "Unclosed element `{}`",
+                element.name
+            )));
+        }
+
+        Ok(Self { nodes, is_string })
+    }
+
+    /// The first top level element
+    pub fn root(&self) -> Option<&XmlElement> {
+        self.nodes.iter().find_map(|node| match node {
+            XmlNode::Element(e) => Some(e),
+            _ => None,
+        })
+    }
+
+    /// The first top level element, mutably
+    pub fn root_mut(&mut self) -> Option<&mut XmlElement> {
+        self.nodes.iter_mut().find_map(|node| match node {
+            XmlNode::Element(e) => Some(e),
+            _ => None,
+        })
+    }
+
+    /// Convert this document back into a `Value::XML`
+    pub fn to_value(&self) -> Value {
+        Value::XML(self.to_string(), self.is_string)
+    }
+}
+
+impl fmt::Display for XmlDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for node in &self.nodes {
+            node.write(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for XmlElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f)
+    }
+}
+
+impl TryFrom<&Value> for XmlDocument {
+    type Error = ConvertError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::XML(content, is_string) => Self::parse(content, *is_string),
+            _ => Err(ConvertError::UnexpectedType("XML")),
+        }
+    }
+}
+
+impl From<&XmlDocument> for Value {
+    fn from(document: &XmlDocument) -> Self {
+        document.to_value()
+    }
+}
+
+impl From<XmlDocument> for Value {
+    fn from(document: XmlDocument) -> Self {
+        document.to_value()
+    }
+}
@@ -0,0 +1,325 @@
+//! Checking that values can be encoded, before any bytes are written
+//!
+//! Every problem is reported with the path to the value it was found in, such as `inventory.items[2].name`
+
+#[cfg(feature = "amf3")]
+use crate::amf3::length::MAX_LENGTH;
+#[cfg(feature = "amf3")]
+use crate::amf3::write::{AMF3Encoder, MAX_INTEGER, MIN_INTEGER};
+use crate::errors::{ValidationError, ValidationErrors, ValidationIssue};
+use crate::prelude::*;
+use crate::types::{AMFVersion, Attribute, ClassDefinition, Element, Lso, ObjectId, Value};
+#[cfg(feature = "amf3")]
+use crate::write::IntegerOverflow;
+use crate::write::{EncodeOptions, LongStrings};
+use alloc::collections::BTreeSet;
+use alloc::format;
+
+/// The characters that Flash Player does not accept in the name of a shared object
+const INVALID_NAME_CHARACTERS: &[char] = &[
+    ' ', '~', '%', '&', '\\', ';', ':', '"', '\'', ',', '<', '>', '?', '#',
+];
+
+/// A step in the path to a value
+pub(crate) enum Segment<'v> {
+    /// The element with the given name
+    Name(&'v str),
+
+    /// The item at the given index
+    Index(usize),
+}
+
+/// Format a path such as `inventory.items[2].name`
+pub(crate) fn path_string(path: &[Segment<'_>]) -> String {
+    let mut out = String::new();
+    for segment in path {
+        match segment {
+            Segment::Name(name) => {
+                if !out.is_empty() {
+                    out.push('.');
+                }
+                out.push_str(name);
+            }
+            Segment::Index(index) => out.push_str(&format!("[{index}]")),
+        }
+    }
+    out
+}
+
+/// Walks a value in the same order as the writers, collecting every problem
+pub(crate) struct Validator<'v> {
+    options: EncodeOptions,
+    encoders: BTreeSet<String>,
+    path: Vec<Segment<'v>>,
+    amf0_values: usize,
+    amf3_objects: BTreeSet<ObjectId>,
+    issues: Vec<ValidationIssue>,
+}
+
+impl<'v> Validator<'v> {
+    /// Create a validator for a writer with the given options and no custom encoders
+    pub(crate) fn new(options: EncodeOptions) -> Self {
+        Self {
+            options,
+            encoders: BTreeSet::new(),
+            path: Vec::new(),
+            amf0_values: 0,
+            amf3_objects: BTreeSet::new(),
+            issues: Vec::new(),
+        }
+    }
+
+    /// Create a validator for values written by the given encoder, including the objects it has already written
+    #[cfg(feature = "amf3")]
+    pub(crate) fn for_encoder(encoder: &AMF3Encoder, options: EncodeOptions) -> Self {
+        Self {
+            encoders: encoder.external_encoders.keys().cloned().collect(),
+            amf3_objects: encoder.written_objects(),
+            ..Self::new(options)
+        }
+    }
+
+    /// The result of validation
+    pub(crate) fn finish(self) -> Result<(), ValidationErrors> {
+        if self.issues.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors(self.issues))
+        }
+    }
+
+    fn issue(&mut self, error: ValidationError) {
+        let path = path_string(&self.path);
+        self.issues.push(ValidationIssue { path, error });
+    }
+
+    fn require(&mut self, valid: bool, error: ValidationError) {
+        if !valid {
+            self.issue(error);
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let encoding = self.options.string_encoding;
+        if !encoding.is_unicode() && encoding.encode(text).is_none() {
+            self.issue(ValidationError::UnmappableString(text.to_string()));
+        }
+    }
+
+    /// Check the header and body of an Lso
+    pub(crate) fn lso(&mut self, lso: &'v Lso) {
+        let name = &lso.header.name;
+        if name.len() > u16::MAX as usize || name.contains(INVALID_NAME_CHARACTERS) {
+            self.issue(ValidationError::InvalidHeaderName(name.clone()));
+        }
+        self.text(name);
+
+        self.elements(&lso.body, lso.header.format_version);
+    }
+
+    fn elements(&mut self, elements: &'v [Element], version: AMFVersion) {
+        for element in elements {
+            self.path.push(Segment::Name(&element.name));
+            self.text(&element.name);
+            self.value(&element.value, version);
+            self.path.pop();
+        }
+    }
+
+    fn values(&mut self, values: &'v [Value], version: AMFVersion) {
+        for (index, value) in values.iter().enumerate() {
+            self.path.push(Segment::Index(index));
+            self.value(value, version);
+            self.path.pop();
+        }
+    }
+
+    #[cfg_attr(not(feature = "amf3"), allow(unused_variables))]
+    fn object(&mut self, id: ObjectId, version: AMFVersion) {
+        #[cfg(feature = "amf3")]
+        if version == AMFVersion::AMF3 {
+            self.amf3_objects.insert(id);
+        }
+    }
+
+    fn class(&mut self, def: &ClassDefinition, version: AMFVersion) {
+        self.text(&def.name);
+        for name in &def.static_properties {
+            self.text(name);
+        }
+        if version != AMFVersion::AMF0
+            && def.attributes.contains(Attribute::External)
+            && !self.encoders.contains(&def.name)
+        {
+            self.issue(ValidationError::MissingCustomEncoder(def.name.clone()));
+        }
+    }
+
+    /// Check a value, and every value inside of it
+    pub(crate) fn value(&mut self, value: &'v Value, version: AMFVersion) {
+        let amf0 = version == AMFVersion::AMF0;
+        let amf0_values = self.amf0_values;
+        if amf0 {
+            // AMF0 references index every value written before them, not only objects
+            self.amf0_values += 1;
+        }
+
+        #[cfg(feature = "amf3")]
+        if amf0 && !is_amf0_type(value) {
+            // Written through the AVM+ switch marker, sharing the AMF3 reference tables of the body
+            return self.value(value, AMFVersion::AMF3);
+        }
+
+        #[cfg(feature = "amf3")]
+        if let Some(length) = amf3_length(value).filter(|_| !amf0) {
+            self.require(
+                length <= MAX_LENGTH as usize,
+                ValidationError::LengthOutOfRange(length),
+            );
+        }
+
+        if let Value::String(s) | Value::XML(s, _) = value {
+            self.text(s);
+        }
+
+        match value {
+            Value::String(s)
+                if amf0
+                    && s.len() > u16::MAX as usize
+                    && self.options.long_strings == LongStrings::Reject =>
+            {
+                self.issue(ValidationError::StringTooLong(s.len()))
+            }
+            Value::Object { id, data } => {
+                self.object(*id, version);
+                if let Some(def) = &data.class_definition {
+                    self.class(def, version);
+                }
+                self.elements(&data.elements, version);
+            }
+            Value::ECMAArray(array) => {
+                self.object(array.id, version);
+                self.values(&array.dense, version);
+                self.elements(&array.assoc, version);
+            }
+            Value::StrictArray(id, values) => {
+                self.object(*id, version);
+                self.values(values, version);
+            }
+            Value::Reference(r) if amf0 => self.require(
+                usize::from(r.0) < amf0_values,
+                ValidationError::DanglingReference(r.0),
+            ),
+            #[cfg(feature = "amf3")]
+            Value::AMF3(inner) => self.value(inner, version),
+            #[cfg(feature = "amf3")]
+            Value::Integer(i) if !amf0 => self.require(
+                self.options.integer_overflow == IntegerOverflow::Promote
+                    || (MIN_INTEGER..=MAX_INTEGER).contains(i),
+                ValidationError::IntegerOutOfRange(*i),
+            ),
+            Value::VectorObject(vector) if !amf0 => {
+                self.object(vector.id, version);
+                self.values(&vector.items, version);
+            }
+            Value::Dictionary(dictionary) if !amf0 => {
+                self.object(dictionary.id, version);
+                for (index, (key, value)) in dictionary.entries.iter().enumerate() {
+                    self.path.push(Segment::Index(index));
+                    self.path.push(Segment::Name("key"));
+                    self.value(key, version);
+                    self.path.pop();
+                    self.path.push(Segment::Name("value"));
+                    self.value(value, version);
+                    self.path.pop();
+                    self.path.pop();
+                }
+            }
+            Value::Custom(custom) if !amf0 => {
+                if let Some(def) = &custom.class_definition {
+                    self.class(def, version);
+                }
+                self.elements(&custom.custom_elements, version);
+                self.elements(&custom.elements, version);
+            }
+            Value::Amf3ObjectReference(id) if !amf0 => self.require(
+                self.amf3_objects.contains(id),
+                ValidationError::DanglingObjectReference(*id),
+            ),
+            Value::Reference(_) => self.unsupported("reference", version),
+            Value::Integer(_) => self.unsupported("integer", version),
+            Value::ByteArray(_) if amf0 => self.unsupported("byte array", version),
+            Value::VectorInt(..) if amf0 => self.unsupported("int vector", version),
+            Value::VectorUInt(..) if amf0 => self.unsupported("uint vector", version),
+            Value::VectorDouble(..) if amf0 => self.unsupported("number vector", version),
+            Value::VectorObject(..) => self.unsupported("object vector", version),
+            Value::Dictionary(..) => self.unsupported("dictionary", version),
+            Value::Custom(..) => self.unsupported("externalized object", version),
+            Value::Amf3ObjectReference(_) => self.unsupported("object reference", version),
+            _ => {}
+        }
+    }
+
+    fn unsupported(&mut self, type_name: &'static str, version: AMFVersion) {
+        self.issue(ValidationError::UnsupportedType(type_name, version));
+    }
+}
+
+/// The length that is written before a value in AMF3
+#[cfg(feature = "amf3")]
+fn amf3_length(value: &Value) -> Option<usize> {
+    match value {
+        Value::String(s) | Value::XML(s, _) => Some(s.len()),
+        Value::ByteArray(bytes) => Some(bytes.len()),
+        Value::StrictArray(_, values) => Some(values.len()),
+        Value::ECMAArray(array) => Some(array.dense.len()),
+        Value::VectorObject(vector) => Some(vector.items.len()),
+        Value::VectorInt(items, _) => Some(items.len()),
+        Value::VectorUInt(items, _) => Some(items.len()),
+        Value::VectorDouble(items, _) => Some(items.len()),
+        Value::Dictionary(dictionary) => Some(dictionary.entries.len()),
+        _ => None,
+    }
+}
+
+/// Whether a value has an AMF0 marker of its own
+///
+/// The AMF0 XML marker is read back as `XML(_, true)`, so `XML(_, false)` is only kept through the AVM+ switch marker
+#[cfg(feature = "amf3")]
+pub(crate) fn is_amf0_type(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::Number(_)
+            | Value::Bool(_)
+            | Value::String(_)
+            | Value::Object { .. }
+            | Value::Null
+            | Value::Undefined
+            | Value::ECMAArray(..)
+            | Value::StrictArray(..)
+            | Value::Date(..)
+            | Value::Unsupported
+            | Value::XML(_, true)
+            | Value::Reference(_)
+    )
+}
+
+impl Lso {
+    /// Check that this Lso can be written by a default `Writer`, reporting every problem found
+    ///
+    /// Externalizable classes are reported as missing an encoder, use `Writer::validate` to check against registered encoders
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut validator = Validator::new(EncodeOptions::default());
+        validator.lso(self);
+        validator.finish()
+    }
+}
+
+impl Value {
+    /// Check that this value can be written on its own in the given version, reporting every problem found
+    pub fn validate(&self, version: AMFVersion) -> Result<(), ValidationErrors> {
+        let mut validator = Validator::new(EncodeOptions::default());
+        validator.value(self, version);
+        validator.finish()
+    }
+}
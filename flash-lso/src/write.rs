@@ -1,54 +1,200 @@
 //! Handles writing of LSO files
-use std::io::Write;
 
 #[cfg(feature = "amf3")]
 use crate::FORMAT_VERSION_AMF3;
+use crate::alias::{ClassRegistry, TypedLso};
+use crate::amf0::write::AMF0Encoder;
 #[cfg(feature = "amf3")]
 use crate::amf3::write::AMF3Encoder;
-use crate::errors::Error;
-use crate::nom_utils::write_string;
+use crate::encoding::StringEncoding;
+use crate::errors::{EncodeError, Error, ValidationErrors};
+use crate::prelude::*;
 use crate::types::{AMFVersion, Header, Lso};
+#[cfg(feature = "amf3")]
+use crate::validate::Validator;
 use crate::{FORMAT_VERSION_AMF0, HEADER_SIGNATURE, HEADER_VERSION, PADDING};
 
+/// How AMF0 strings that are too long for a `String` marker are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LongStrings {
+    /// Write them as long strings
+    #[default]
+    Promote,
+
+    /// Fail with `ValidationError::StringTooLong`
+    Reject,
+}
+
+/// How AMF3 integers outside of the 29 bit range are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegerOverflow {
+    /// Write them as numbers, as Flash Player does for `int` values that don't fit
+    #[default]
+    Promote,
+
+    /// Fail with `ValidationError::IntegerOutOfRange`
+    Reject,
+}
+
+/// How the length of an AMF0 ECMA array is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EcmaArrayLength {
+    /// Write the length that was read, which Flash Player uses as the array `length`
+    #[default]
+    Preserve,
+
+    /// Write the `length` of the array from its indices, see `EcmaArray::length`
+    ///
+    /// Holes at the end of an array are not stored, so they are dropped from its length
+    Recompute,
+}
+
+/// Options that control how values are encoded
+///
+/// The defaults match the output of Flash Player
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeOptions {
+    /// Write repeated AMF3 strings as references to the first occurrence
+    pub string_references: bool,
+
+    /// Write repeated AMF3 class definitions as references to the first occurrence
+    pub trait_references: bool,
+
+    /// Write whole `Number`s that fit in 29 bits as AMF3 integers
+    pub integer_numbers: bool,
+
+    /// How AMF3 integers outside of the 29 bit range are written
+    pub integer_overflow: IntegerOverflow,
+
+    /// How AMF0 strings longer than 65535 bytes are written
+    pub long_strings: LongStrings,
+
+    /// How the length of AMF0 ECMA arrays is written
+    pub ecma_array_length: EcmaArrayLength,
+
+    /// How strings, element names and class names are encoded
+    pub string_encoding: StringEncoding,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            string_references: true,
+            trait_references: true,
+            integer_numbers: false,
+            integer_overflow: IntegerOverflow::Promote,
+            long_strings: LongStrings::Promote,
+            ecma_array_length: EcmaArrayLength::Preserve,
+            string_encoding: StringEncoding::Utf8,
+        }
+    }
+}
+
+impl EncodeOptions {
+    /// The options that produce the smallest output
+    pub fn compact() -> Self {
+        Self {
+            integer_numbers: true,
+            ..Self::default()
+        }
+    }
+}
+
 /// Handles writing a given LSO
 #[derive(Default)]
 pub struct Writer {
+    /// The options used for encoding, these replace the options of `amf3_encoder` when writing
+    pub options: EncodeOptions,
+
     /// The encoder used for writing Value::AMF3() wrapped types
     #[cfg(feature = "amf3")]
     pub amf3_encoder: AMF3Encoder,
+
+    /// The classes that `write_typed` can convert from Rust types
+    pub class_registry: ClassRegistry,
 }
 
 impl Writer {
+    /// Check that a given LSO can be written by this writer, reporting every problem found
+    ///
+    /// This is run by `write_full` before anything is written
+    pub fn validate(&self, lso: &Lso) -> Result<(), ValidationErrors> {
+        let mut validator = match lso.header.format_version {
+            AMFVersion::AMF0 => self.amf0_encoder().validator(),
+            #[cfg(feature = "amf3")]
+            AMFVersion::AMF3 => Validator::for_encoder(&self.amf3_encoder, self.options),
+        };
+
+        validator.lso(lso);
+        validator.finish()
+    }
+
     /// Write a given LSO
-    pub fn write_full<'a, 'b: 'a, W: Write + 'a>(
+    pub fn write_full<'a, 'b: 'a, W: Sink + 'a>(
         &'a mut self,
         writer: &mut W,
         lso: &'b mut Lso,
-    ) -> std::io::Result<()> {
+    ) -> Result<(), EncodeError> {
+        self.validate(lso)?;
+
         let mut buffer = Vec::new();
         if lso.header.format_version == AMFVersion::AMF0 {
-            crate::amf0::write::write_body(&mut buffer, &lso.body)?;
+            let mut encoder = self.amf0_encoder();
+            crate::amf0::write::write_body(&mut buffer, &mut encoder, &lso.body)?;
         } else {
             #[cfg(feature = "amf3")]
-            self.amf3_encoder.write_body(&mut buffer, &lso.body)?;
+            {
+                *self.amf3_encoder.options_mut() = self.options;
+                self.amf3_encoder.write_body(&mut buffer, &lso.body)?;
+            }
         }
 
-        lso.header.length = buffer.len() as u32 + header_length(&lso.header) as u32;
+        // The name may be a different length once encoded
+        let name = self
+            .options
+            .string_encoding
+            .encode_checked(&lso.header.name)?;
+        lso.header.length =
+            (buffer.len() + header_length(&lso.header) + name.len() - lso.header.name.len()) as u32;
 
-        write_header(writer, &lso.header)?;
+        write_header(writer, &lso.header, &name)?;
         writer.write_all(&buffer)?;
         Ok(())
     }
+
+    /// The encoder for an AMF0 body, which writes AMF3 values with the registered encoders
+    fn amf0_encoder(&self) -> AMF0Encoder {
+        #[cfg(feature = "amf3")]
+        {
+            let mut amf3_encoder = self.amf3_encoder.fresh();
+            *amf3_encoder.options_mut() = self.options;
+            AMF0Encoder::with_amf3_encoder(amf3_encoder)
+        }
+        #[cfg(not(feature = "amf3"))]
+        AMF0Encoder::new(self.options)
+    }
+
+    /// Write a given LSO, converting instances of registered classes into typed objects
+    pub fn write_typed<W: Sink>(
+        &mut self,
+        writer: &mut W,
+        lso: &TypedLso,
+    ) -> Result<(), EncodeError> {
+        let mut lso = self.class_registry.encode_lso(lso)?;
+        self.write_full(writer, &mut lso)
+    }
 }
 
-fn write_header<'a, 'b: 'a, W: Write + 'a>(
+fn write_header<'a, 'b: 'a, W: Sink + 'a>(
     writer: &mut W,
     header: &'b Header,
-) -> std::io::Result<()> {
+    name: &[u8],
+) -> Result<(), EncodeError> {
     writer.write_all(&HEADER_VERSION)?;
     writer.write_u32(header.length)?;
     writer.write_all(&HEADER_SIGNATURE)?;
-    write_string(writer, &header.name)?;
+    writer.write_u16(name.len() as u16)?;
+    writer.write_all(name)?;
     writer.write_all(&PADDING)?;
     writer.write_all(&PADDING)?;
     writer.write_all(&PADDING)?;
@@ -71,40 +217,101 @@ pub fn write_to_bytes<'a>(lso: &mut Lso) -> Result<Vec<u8>, Error<'a>> {
     let mut v = Vec::new();
 
     let mut s = Writer::default();
-    s.write_full(&mut v, lso)
-        .map_err(|e| Error::IoError(e.to_string(), e.kind()))?;
+    s.write_full(&mut v, lso)?;
     Ok(v)
 }
 
+/// A destination for encoded bytes
+///
+/// With the `std` feature this is implemented for every `std::io::Write`, otherwise it is implemented for `Vec<u8>`
+pub trait Sink {
+    /// Write all of the given bytes
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), EncodeError>;
+}
+
+#[cfg(feature = "std")]
+impl<T: std::io::Write + ?Sized> Sink for T {
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        std::io::Write::write_all(self, bytes)?;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Sink for Vec<u8> {
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<T: Sink + ?Sized> Sink for &mut T {
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        (**self).write_all(bytes)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Writer {
+    /// Write a given LSO to an async writer
+    ///
+    /// The LSO is encoded with `write_full` before being written
+    pub async fn write_async<W: tokio::io::AsyncWrite + Unpin + ?Sized>(
+        &mut self,
+        writer: &mut W,
+        lso: &mut Lso,
+    ) -> Result<(), crate::errors::AsyncError> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut buffer = Vec::new();
+        self.write_full(&mut buffer, lso)?;
+        writer.write_all(&buffer).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Wraps a possibly unsized sink so that it can be passed as a `dyn Sink`
+#[cfg(feature = "amf3")]
+pub(crate) struct SinkRef<'s, W: Sink + ?Sized>(pub(crate) &'s mut W);
+
+#[cfg(feature = "amf3")]
+impl<W: Sink + ?Sized> Sink for SinkRef<'_, W> {
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        self.0.write_all(bytes)
+    }
+}
+
 pub(crate) trait WriteExt {
-    fn write_u8(&mut self, value: u8) -> std::io::Result<()>;
-    fn write_u16(&mut self, value: u16) -> std::io::Result<()>;
-    fn write_u32(&mut self, value: u32) -> std::io::Result<()>;
-    fn write_i32(&mut self, value: i32) -> std::io::Result<()>;
-    fn write_f64(&mut self, value: f64) -> std::io::Result<()>;
+    fn write_u8(&mut self, value: u8) -> Result<(), EncodeError>;
+    fn write_u16(&mut self, value: u16) -> Result<(), EncodeError>;
+    fn write_u32(&mut self, value: u32) -> Result<(), EncodeError>;
+    fn write_i32(&mut self, value: i32) -> Result<(), EncodeError>;
+    fn write_f64(&mut self, value: f64) -> Result<(), EncodeError>;
 }
 
 impl<T> WriteExt for T
 where
-    T: Write,
+    T: Sink + ?Sized,
 {
-    fn write_u8(&mut self, value: u8) -> std::io::Result<()> {
+    fn write_u8(&mut self, value: u8) -> Result<(), EncodeError> {
         self.write_all(&[value])
     }
 
-    fn write_u16(&mut self, value: u16) -> std::io::Result<()> {
+    fn write_u16(&mut self, value: u16) -> Result<(), EncodeError> {
         self.write_all(&value.to_be_bytes())
     }
 
-    fn write_u32(&mut self, value: u32) -> std::io::Result<()> {
+    fn write_u32(&mut self, value: u32) -> Result<(), EncodeError> {
         self.write_all(&value.to_be_bytes())
     }
 
-    fn write_i32(&mut self, value: i32) -> std::io::Result<()> {
+    fn write_i32(&mut self, value: i32) -> Result<(), EncodeError> {
         self.write_all(&value.to_be_bytes())
     }
 
-    fn write_f64(&mut self, value: f64) -> std::io::Result<()> {
+    fn write_f64(&mut self, value: f64) -> Result<(), EncodeError> {
         self.write_all(&value.to_be_bytes())
     }
 }
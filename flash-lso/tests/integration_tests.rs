@@ -495,7 +495,7 @@ pub fn test_externalizable_object_back_reference() {
         .parse_single_element(data)
         .expect("first parse failed");
     assert!(
-        matches!(first, Value::Custom(_, _, _)),
+        matches!(first, Value::Custom(_)),
         "first element should be Custom, got {first:?}"
     );
 
@@ -507,3 +507,2119 @@ pub fn test_externalizable_object_back_reference() {
         "back-ref should resolve to Custom, got {second:?}"
     );
 }
+
+#[test]
+pub fn test_externalizable_without_encoder_is_error() {
+    use flash_lso::errors::{EncodeError, ValidationError, ValidationErrors, ValidationIssue};
+    use flash_lso::types::{AMFVersion, Attribute, ClassDefinition, Lso};
+
+    let class_def = ClassDefinition {
+        name: "X".to_string(),
+        attributes: Attribute::External.into(),
+        static_properties: Vec::new(),
+    };
+    let mut lso = Lso::new(
+        vec![Element::new(
+            "x",
+            Value::Custom(flash_lso::types::CustomObject {
+                custom_elements: Vec::new(),
+                elements: Vec::new(),
+                class_definition: Some(class_def),
+            }),
+        )],
+        "test",
+        AMFVersion::AMF3,
+    );
+
+    assert_eq!(
+        flash_lso::write::write_to_bytes(&mut lso),
+        Err(Error::Encode(EncodeError::Invalid(ValidationErrors(vec![
+            ValidationIssue {
+                path: "x".to_string(),
+                error: ValidationError::MissingCustomEncoder("X".to_string()),
+            }
+        ]))))
+    );
+
+    // Without validation, the same problem is found while writing
+    let mut bytes = Vec::new();
+    assert_eq!(
+        flash_lso::amf3::write::AMF3Encoder::default()
+            .write_value_element(&mut bytes, &lso.body[0].value),
+        Err(EncodeError::Unencodable(
+            ValidationError::MissingCustomEncoder("X".to_string())
+        ))
+    );
+}
+
+#[test]
+pub fn test_custom_encoder_shares_reference_tables() {
+    use flash_lso::amf3::custom_encoder::CustomEncoder;
+    use flash_lso::amf3::read::AMF3Decoder;
+    use flash_lso::amf3::write::AMF3Encoder;
+    use flash_lso::errors::EncodeError;
+    use flash_lso::types::{AMFVersion, Attribute, ClassDefinition, Lso};
+    use flash_lso::write::Sink;
+    use flash_lso::write::Writer;
+
+    #[derive(Default)]
+    struct TestEncoder;
+    impl CustomEncoder for TestEncoder {
+        fn encode(
+            &self,
+            writer: &mut dyn Sink,
+            elements: &[Element],
+            _class_def: &Option<ClassDefinition>,
+            encoder: &mut AMF3Encoder,
+        ) -> Result<(), EncodeError> {
+            let name = elements
+                .first()
+                .ok_or_else(|| EncodeError::MissingElement("name".to_string()))?;
+            encoder.write_value_element(writer, &name.value)
+        }
+    }
+
+    #[derive(Default)]
+    struct TestDecoder;
+    impl CustomDecoder for TestDecoder {
+        fn decode<'a>(&self, i: &'a [u8], dec: &mut AMF3Decoder) -> AMFResult<'a, Vec<Element>> {
+            let (i, name) = dec.parse_single_element(i)?;
+            Ok((i, vec![Element::new("name", name)]))
+        }
+    }
+
+    let class_def = ClassDefinition {
+        name: "X".to_string(),
+        attributes: Attribute::External.into(),
+        static_properties: Vec::new(),
+    };
+    let mut lso = Lso::new(
+        vec![
+            Element::new("a", Value::String("shared".to_string())),
+            Element::new(
+                "b",
+                Value::Custom(flash_lso::types::CustomObject {
+                    custom_elements: vec![Element::new(
+                        "name",
+                        Value::String("shared".to_string()),
+                    )],
+                    elements: Vec::new(),
+                    class_definition: Some(class_def),
+                }),
+            ),
+        ],
+        "test",
+        AMFVersion::AMF3,
+    );
+
+    let mut writer = Writer::default();
+    writer
+        .amf3_encoder
+        .register_custom_encoder::<TestEncoder>("X");
+    let mut bytes = Vec::new();
+    writer
+        .write_full(&mut bytes, &mut lso)
+        .expect("Failed to write");
+
+    // The second "shared" is written as a reference to the first (index 1, after "a")
+    assert!(bytes.windows(2).any(|w| w == [0x06, 0x02]));
+
+    let mut reader = Reader::default();
+    reader
+        .amf3_decoder
+        .register_custom_decoder::<TestDecoder>("X");
+    let lso2 = reader.parse(&bytes).expect("Failed to read");
+    assert_eq!(lso2.body, lso.body);
+}
+
+#[test]
+pub fn test_externalizable_decode_context() {
+    use flash_lso::amf3::custom_encoder::ExternalizableDecoder;
+    use flash_lso::amf3::decode_context::DecodeContext;
+
+    #[derive(Default)]
+    struct TestDecoder;
+    impl ExternalizableDecoder for TestDecoder {
+        fn decode<'a>(&self, ctx: &mut DecodeContext<'a, '_>) -> Result<Vec<Element>, Error<'a>> {
+            let id = ctx.read_u32_be()?;
+            let label = ctx.read_utf()?;
+            let name = ctx.read_amf3_value()?;
+            assert_eq!(ctx.position(), 4 + 2 + 2 + 2);
+            Ok(vec![
+                Element::new("id", Value::Number(id as f64)),
+                Element::new("label", Value::String(label)),
+                Element::new("name", name),
+            ])
+        }
+    }
+
+    // "a" = "n", "b" = externalizable "X" { id: 7, label: "hi", name: <reference to "n"> }
+    let data = [
+        0x00, 0xBF, 0x00, 0x00, 0x00, 0x1C, 0x54, 0x43, 0x53, 0x4F, 0x00, 0x04, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x01, 0x41, 0x00, 0x00, 0x00, 0x03, 0x03, 0x61, 0x06, 0x03, 0x6E, 0x00, 0x03,
+        0x62, 0x0A, 0x07, 0x03, 0x58, 0x00, 0x00, 0x00, 0x07, 0x00, 0x02, 0x68, 0x69, 0x06, 0x02,
+        0x00,
+    ];
+
+    let mut reader = Reader::default();
+    reader
+        .amf3_decoder
+        .register_custom_decoder::<TestDecoder>("X");
+    let lso = reader.parse(&data).expect("Failed to read");
+
+    assert_eq!(
+        lso.body[1].value,
+        Value::Custom(flash_lso::types::CustomObject {
+            custom_elements: vec![
+                Element::new("id", Value::Number(7.0)),
+                Element::new("label", Value::String("hi".to_string())),
+                Element::new("name", Value::String("n".to_string())),
+            ],
+            elements: Vec::new(),
+            class_definition: Some(flash_lso::types::ClassDefinition {
+                name: "X".to_string(),
+                attributes: flash_lso::types::Attribute::External.into(),
+                static_properties: Vec::new(),
+            })
+        })
+    );
+
+    // Reading past the end of the externalized data is reported with the crate error type
+    let mut truncated = data[..data.len() - 4].to_vec();
+    truncated[5] -= 4;
+    assert_eq!(
+        reader.parse(&truncated),
+        Err(nom::Err::Failure(Error::OutOfBounds))
+    );
+}
+
+#[test]
+pub fn test_unknown_externalizable_error() {
+    use flash_lso::amf3::read::AMF3Decoder;
+
+    let data = include_bytes!("./amf/externalizable-object-back-reference.amf");
+    let res = AMF3Decoder::default().parse_single_element(data);
+
+    assert_eq!(
+        res,
+        Err(nom::Err::Failure(Error::UnknownExternalizable {
+            class_name: "X".to_string(),
+            offset: 4
+        }))
+    );
+}
+
+#[test]
+pub fn test_unknown_externalizable_callback() {
+    use flash_lso::amf3::custom_encoder::ExternalizableFallback;
+    use flash_lso::amf3::read::AMF3Decoder;
+    use std::sync::Arc;
+
+    let data = include_bytes!("./amf/externalizable-object-back-reference.amf");
+
+    let mut decoder = AMF3Decoder::default();
+    decoder.set_externalizable_fallback(ExternalizableFallback::Callback(Arc::new(
+        |ctx, class_def| {
+            assert_eq!(class_def.name, "X");
+            let byte = ctx.read_u8()?;
+            Ok(vec![Element::new("byte", Value::Integer(byte as i32))])
+        },
+    )));
+
+    let (rest, first) = decoder
+        .parse_single_element(data)
+        .expect("first parse failed");
+    assert!(
+        matches!(&first, Value::Custom(custom) if custom.custom_elements[0].value == Value::Integer(0xab)),
+        "first element should be Custom, got {first:?}"
+    );
+
+    let (_rest, second) = decoder
+        .parse_single_element(rest)
+        .expect("back-ref parse failed");
+    assert_eq!(
+        second,
+        Value::Amf3ObjectReference(flash_lso::types::ObjectId(0))
+    );
+}
+
+#[test]
+pub fn test_unknown_externalizable_capture_remaining() {
+    use flash_lso::amf3::custom_encoder::{
+        CustomEncoder, ExternalizableFallback, OPAQUE_ELEMENT_NAME,
+    };
+    use flash_lso::amf3::write::AMF3Encoder;
+    use flash_lso::errors::EncodeError;
+    use flash_lso::types::{AMFVersion, Attribute, ClassDefinition, Lso};
+    use flash_lso::write::Sink;
+    use flash_lso::write::Writer;
+
+    #[derive(Default)]
+    struct TestEncoder;
+    impl CustomEncoder for TestEncoder {
+        fn encode(
+            &self,
+            writer: &mut dyn Sink,
+            _elements: &[Element],
+            _class_def: &Option<ClassDefinition>,
+            _encoder: &mut AMF3Encoder,
+        ) -> Result<(), EncodeError> {
+            writer.write_all(&[1, 2, 3])?;
+            Ok(())
+        }
+    }
+
+    let class_def = ClassDefinition {
+        name: "X".to_string(),
+        attributes: Attribute::External.into(),
+        static_properties: Vec::new(),
+    };
+    let mut lso = Lso::new(
+        vec![
+            Element::new("a", Value::Integer(1)),
+            Element::new(
+                "b",
+                Value::Custom(flash_lso::types::CustomObject {
+                    custom_elements: Vec::new(),
+                    elements: Vec::new(),
+                    class_definition: Some(class_def),
+                }),
+            ),
+        ],
+        "test",
+        AMFVersion::AMF3,
+    );
+
+    let mut writer = Writer::default();
+    writer
+        .amf3_encoder
+        .register_custom_encoder::<TestEncoder>("X");
+    let mut bytes = Vec::new();
+    writer
+        .write_full(&mut bytes, &mut lso)
+        .expect("Failed to write");
+
+    let mut reader = Reader::default();
+    reader
+        .amf3_decoder
+        .set_externalizable_fallback(ExternalizableFallback::CaptureRemaining);
+    let lso2 = reader.parse(&bytes).expect("Failed to read");
+
+    assert_eq!(lso2.body[0], lso.body[0]);
+    assert!(
+        matches!(&lso2.body[1].value, Value::Custom(flash_lso::types::CustomObject { custom_elements: el, class_definition: Some(def), .. })
+            if def.name == "X" && el[0].name == OPAQUE_ELEMENT_NAME && el[0].value == Value::ByteArray(vec![1, 2, 3])),
+        "expected opaque capture, got {:?}",
+        lso2.body[1]
+    );
+}
+
+#[test]
+pub fn test_class_alias_registry() {
+    use flash_lso::alias::{AmfClass, ToAmf, TypedElement, TypedLso, TypedValue, property};
+    use flash_lso::errors::ConvertError;
+    use flash_lso::types::{AMFVersion, Attribute, ClassDefinition, Header, ObjectId, ObjectValue};
+    use flash_lso::write::Writer;
+
+    #[derive(Debug, PartialEq)]
+    struct PlayerState {
+        name: String,
+        level: i32,
+        guild: Option<String>,
+    }
+
+    impl AmfClass for PlayerState {
+        const ALIAS: &'static str = "com.game.PlayerState";
+
+        fn static_properties() -> Vec<String> {
+            vec!["name".to_string(), "level".to_string(), "guild".to_string()]
+        }
+
+        fn to_elements(&self) -> Vec<Element> {
+            vec![
+                Element::new("name", self.name.to_amf()),
+                Element::new("level", self.level.to_amf()),
+                Element::new("guild", self.guild.to_amf()),
+            ]
+        }
+
+        fn from_elements(elements: &[Element]) -> Result<Self, ConvertError> {
+            Ok(Self {
+                name: property(elements, "name")?,
+                level: property(elements, "level")?,
+                guild: property(elements, "guild")?,
+            })
+        }
+    }
+
+    let player = PlayerState {
+        name: "cub3d".to_string(),
+        level: 12,
+        guild: None,
+    };
+    let other = Value::Object {
+        id: ObjectId::INVALID,
+        data: ObjectValue {
+            elements: vec![Element::new("x", Value::Integer(1))],
+            class_definition: Some(ClassDefinition {
+                name: "com.game.Unknown".to_string(),
+                attributes: Attribute::Dynamic.into(),
+                static_properties: Vec::new(),
+            }),
+        },
+    };
+    let players = Value::StrictArray(ObjectId::INVALID, vec![player.to_amf()]);
+    let lso = TypedLso {
+        header: Header::new("save", AMFVersion::AMF3),
+        body: vec![
+            TypedElement::new("player", TypedValue::new(player)),
+            TypedElement::new("other", other.clone()),
+            TypedElement::new("players", players),
+        ],
+    };
+
+    let mut writer = Writer::default();
+    writer.class_registry.register::<PlayerState>();
+    let mut bytes = Vec::new();
+    writer
+        .write_typed(&mut bytes, &lso)
+        .expect("Failed to write");
+
+    // Without the class registered, the player is read as a sealed typed object
+    let plain = Reader::default().parse(&bytes).expect("Failed to read");
+    if let Value::Object { data, .. } = &plain.body[0].value {
+        assert_eq!(data.class_definition, Some(PlayerState::class_definition()));
+    } else {
+        panic!("Expected object, got {:?}", plain.body[0].value);
+    }
+
+    let mut reader = Reader::default();
+    reader.class_registry.register::<PlayerState>();
+    let typed = reader.parse_typed(&bytes).expect("Failed to read");
+    assert_eq!(
+        typed.body[0].value.downcast_ref::<PlayerState>(),
+        Some(&PlayerState {
+            name: "cub3d".to_string(),
+            level: 12,
+            guild: None,
+        })
+    );
+    // Unregistered classes are left as values
+    assert!(matches!(
+        (typed.body[1].value.as_value(), &other),
+        (Some(Value::Object { data: a, .. }), Value::Object { data: b, .. }) if a == b
+    ));
+    // Only root level values are converted, registered classes nested in other values are left as values
+    assert!(matches!(
+        typed.body[2].value.as_value(),
+        Some(Value::StrictArray(_, values)) if matches!(&values[..], [Value::Object { data, .. }]
+            if data.class_definition == Some(PlayerState::class_definition()))
+    ));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+pub async fn test_async_lso_roundtrip() {
+    use flash_lso::write::Writer;
+
+    let data = include_bytes!("sol/AS2-Demo.sol");
+    let mut lso = Reader::default().parse(data).expect("Failed to parse");
+
+    let (mut client, mut server) = tokio::io::duplex(64);
+    let write = async {
+        Writer::default()
+            .write_async(&mut client, &mut lso)
+            .await
+            .expect("Failed to write");
+    };
+    let read = async {
+        Reader::default()
+            .read_async(&mut server)
+            .await
+            .expect("Failed to read")
+    };
+    let ((), read_lso) = tokio::join!(write, read);
+
+    assert_eq!(read_lso, lso);
+
+    // A truncated file is reported as an IO error rather than a decode error
+    let mut truncated = &data[..data.len() - 1];
+    assert!(matches!(
+        Reader::default().read_async(&mut truncated).await,
+        Err(flash_lso::errors::AsyncError::IoError(
+            _,
+            std::io::ErrorKind::UnexpectedEof
+        ))
+    ));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+pub async fn test_async_packet_roundtrip() {
+    use flash_lso::packet::{read, write};
+    use tokio::io::AsyncWriteExt;
+
+    let data = include_bytes!("packet/armorgames_auth_request.dat");
+    let packet = read::parse(data).expect("Failed to parse");
+
+    let (mut client, mut server) = tokio::io::duplex(64);
+    let write = async {
+        write::write_async(&mut client, &packet, true)
+            .await
+            .expect("Failed to write");
+        client.shutdown().await.expect("Failed to shutdown");
+    };
+    let read = async { read::read_async(&mut server).await.expect("Failed to read") };
+    let ((), read_packet) = tokio::join!(write, read);
+
+    assert_eq!(
+        write::write_to_bytes(&read_packet, true),
+        write::write_to_bytes(&packet, true)
+    );
+}
+
+#[test]
+pub fn test_date_helpers() {
+    use flash_lso::types::date;
+
+    let data = include_bytes!("sol/AS2-Date-Demo.sol");
+    let lso = Reader::default().parse(data).expect("Failed to parse");
+    let Value::Date(millis, Some(tz)) = lso.body[0].value else {
+        panic!("Expected a date with a timezone");
+    };
+
+    // Flash writes `getTimezoneOffset()`, which is positive west of UTC
+    assert_eq!(date::timezone_offset_minutes(tz), -240);
+    assert_eq!(date::timezone_from_offset_minutes(-240), Ok(tz));
+    assert_eq!(
+        date::to_iso8601(millis).as_deref(),
+        Ok("2014-09-02T10:23:03.774Z")
+    );
+
+    assert_eq!(
+        date::to_iso8601(-date::MAX_TIME_MS).as_deref(),
+        Ok("-271821-04-20T00:00:00.000Z")
+    );
+    assert_eq!(
+        date::to_iso8601(date::MAX_TIME_MS).as_deref(),
+        Ok("+275760-09-13T00:00:00.000Z")
+    );
+    assert_eq!(
+        date::to_iso8601(-1.0).as_deref(),
+        Ok("1969-12-31T23:59:59.999Z")
+    );
+
+    let invalid = Err(flash_lso::errors::ConvertError::InvalidDate);
+    assert_eq!(date::to_iso8601(f64::NAN), invalid);
+    assert_eq!(date::to_iso8601(date::MAX_TIME_MS + 1.0), invalid);
+    assert_eq!(
+        date::unix_nanos(f64::INFINITY),
+        Err(flash_lso::errors::ConvertError::InvalidDate)
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+pub fn test_date_iso8601_serde() {
+    use flash_lso::types::date::Iso8601Dates;
+
+    let data = include_bytes!("sol/AS2-Date-Demo.sol");
+    let mut lso = Reader::default().parse(data).expect("Failed to parse");
+
+    let json = serde_json::to_string(&Iso8601Dates(&lso)).expect("Failed to serialize");
+    assert_eq!(
+        json,
+        r#"{"header":{"length":49,"name":"AS2-Date-Demo","format_version":"AMF0"},"body":[{"name":"myDate","value":{"Date":["2014-09-02T10:23:03.774Z",240]}}]}"#
+    );
+
+    lso.body[0].value = Value::Date(f64::NAN, None);
+    let json = serde_json::to_string(&Iso8601Dates(&lso.body[0])).expect("Failed to serialize");
+    assert_eq!(json, r#"{"name":"myDate","value":{"Date":[null,null]}}"#);
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+pub fn test_date_chrono() {
+    use chrono::{DateTime, FixedOffset, Timelike, Utc};
+
+    let data = include_bytes!("sol/AS2-Date-Demo.sol");
+    let lso = Reader::default().parse(data).expect("Failed to parse");
+    let value = &lso.body[0].value;
+
+    let utc = DateTime::<Utc>::try_from(value).expect("Invalid date");
+    assert_eq!(utc.timestamp_millis(), 1409653383774);
+    assert_eq!(utc.hour(), 10);
+
+    let local = DateTime::<FixedOffset>::try_from(value).expect("Invalid date");
+    assert_eq!(local.offset().local_minus_utc(), -4 * 3600);
+    assert_eq!(local.hour(), 6);
+    assert_eq!(&Value::from(local), value);
+    assert_eq!(Value::from(utc), Value::Date(1409653383774.0, None));
+
+    assert_eq!(
+        DateTime::<Utc>::try_from(&Value::Date(f64::NAN, None)),
+        Err(flash_lso::errors::ConvertError::InvalidDate)
+    );
+}
+
+#[cfg(feature = "time")]
+#[test]
+pub fn test_date_time() {
+    use time::OffsetDateTime;
+
+    let data = include_bytes!("sol/AS2-Date-Demo.sol");
+    let lso = Reader::default().parse(data).expect("Failed to parse");
+    let value = &lso.body[0].value;
+
+    let date = OffsetDateTime::try_from(value).expect("Invalid date");
+    assert_eq!(date.unix_timestamp(), 1409653383);
+    assert_eq!(date.millisecond(), 774);
+    assert_eq!(date.offset().whole_minutes(), -240);
+    assert_eq!(date.hour(), 6);
+    assert_eq!(&Value::from(date), value);
+
+    assert_eq!(
+        OffsetDateTime::try_from(&Value::Date(1e16, None)),
+        Err(flash_lso::errors::ConvertError::InvalidDate)
+    );
+}
+
+#[cfg(feature = "xml")]
+#[test]
+pub fn test_xml_document_edit() {
+    use flash_lso::types::xml::{XmlDocument, XmlElement, XmlNode};
+    use flash_lso::write::write_to_bytes;
+
+    let data = include_bytes!("sol/AS3-XML-Demo.sol");
+    let mut lso = Reader::default().parse(data).expect("Failed to parse");
+
+    let mut doc = XmlDocument::try_from(&lso.body[0].value).expect("Invalid xml");
+    assert_eq!(doc.to_value(), lso.body[0].value);
+
+    let root = doc.root_mut().expect("Missing root");
+    assert_eq!(root.name(), "start");
+    let texts: Vec<_> = root.elements().map(|e| e.text()).collect();
+    assert_eq!(texts, ["test", "test2"]);
+
+    root.element_mut("p")
+        .expect("Missing element")
+        .set_text("a < b");
+    let mut level = XmlElement::new("level");
+    level.set_attribute("id", "3");
+    root.children.push(XmlNode::Element(level));
+
+    lso.body[0].value = doc.into();
+    let bytes = write_to_bytes(&mut lso).expect("Failed to write");
+    let lso = Reader::default().parse(&bytes).expect("Failed to parse");
+    assert_eq!(
+        lso.body[0].value,
+        Value::XML(
+            "<start>\n  <p>a &lt; b</p>\n  <p>test2</p>\n<level id=\"3\"/></start>".to_string(),
+            true
+        )
+    );
+
+    // Legacy XMLDocument values keep their type
+    let data = include_bytes!("sol/AS3-XMLDoc-Demo.sol");
+    let lso = Reader::default().parse(data).expect("Failed to parse");
+    let doc = XmlDocument::try_from(&lso.body[0].value).expect("Invalid xml");
+    assert!(!doc.is_string);
+    assert_eq!(doc.to_value(), lso.body[0].value);
+}
+
+#[cfg(feature = "xml")]
+#[test]
+pub fn test_xml_document_roundtrip() {
+    use flash_lso::errors::ConvertError;
+    use flash_lso::types::xml::XmlDocument;
+
+    let content = "<?xml version=\"1.0\" encoding='utf-8' ?>\n<!-- save -->\n<save  a = 'x&amp;y'>\n\t<![CDATA[<raw>]]>&amp;text&#33;<empty/><?pi data?>\n</save>\n";
+    let mut doc = XmlDocument::parse(content, false).expect("Invalid xml");
+    assert_eq!(doc.to_string(), content);
+
+    let root = doc.root_mut().expect("Missing root");
+    assert_eq!(root.attribute("a"), Some("x&y"));
+    assert_eq!(root.text(), "\n\t<raw>&text!\n");
+
+    root.set_attribute("a", "\"q\"");
+    assert_eq!(
+        doc.to_string(),
+        "<?xml version=\"1.0\" encoding='utf-8' ?>\n<!-- save -->\n<save a=\"&quot;q&quot;\">\n\t<![CDATA[<raw>]]>&amp;text&#33;<empty/><?pi data?>\n</save>\n"
+    );
+
+    // End tags are kept as they were written, until the element is renamed
+    let mut doc = XmlDocument::parse("<a><b>x</b ></a\n>", true).expect("Invalid xml");
+    assert_eq!(doc.to_string(), "<a><b>x</b ></a\n>");
+    doc.root_mut().expect("Missing root").set_name("c");
+    assert_eq!(doc.to_string(), "<c><b>x</b ></c>");
+
+    assert!(matches!(
+        XmlDocument::parse("<a><b></a>", true),
+        Err(ConvertError::InvalidXml(_))
+    ));
+    assert!(matches!(
+        XmlDocument::parse("<a>", true),
+        Err(ConvertError::InvalidXml(_))
+    ));
+    assert_eq!(
+        XmlDocument::try_from(&Value::Null),
+        Err(ConvertError::UnexpectedType("XML"))
+    );
+}
+
+#[cfg(feature = "zlib")]
+#[test]
+pub fn test_nested_byte_array_zlib() {
+    use flash_lso::amf3::read::AMF3Decoder;
+    use flash_lso::amf3::write::AMF3Encoder;
+    use flash_lso::nested::{Layer, NestedAmf};
+    use flash_lso::types::{AMFVersion, Attribute, ClassDefinition, ObjectId, ObjectValue};
+
+    let level = Value::Object {
+        id: ObjectId(0),
+        data: ObjectValue {
+            elements: vec![
+                Element::new("name", Value::String("level".to_string())),
+                Element::new("score", Value::Integer(10)),
+            ],
+            class_definition: Some(ClassDefinition {
+                attributes: Attribute::Dynamic.into(),
+                ..ClassDefinition::default()
+            }),
+        },
+    };
+
+    // Two calls to `writeObject` followed by `compress`, each value has its own reference tables
+    let mut stream = Vec::new();
+    for value in [&level, &level] {
+        AMF3Encoder::default()
+            .write_value_element(&mut stream, value)
+            .expect("Failed to write");
+    }
+    let compressed = Layer::Zlib.compress(&stream).expect("Failed to compress");
+    assert_eq!(Layer::Zlib.decompress(&compressed), Some(stream.clone()));
+
+    let decoder = AMF3Decoder::default();
+    let mut nested = NestedAmf::decode_value(&Value::ByteArray(compressed), &decoder)
+        .expect("Failed to find nested values");
+    assert_eq!(nested.layers, [Layer::Zlib]);
+    assert_eq!(nested.version, AMFVersion::AMF3);
+    assert_eq!(nested.values.len(), 2);
+
+    if let Value::Object { data, .. } = &mut nested.values[1] {
+        data.elements[1].value = Value::Integer(20);
+    }
+
+    let encoder = AMF3Encoder::default();
+    let value = nested.to_value(&encoder).expect("Failed to encode");
+    let reread = NestedAmf::decode_value(&value, &decoder).expect("Failed to find nested values");
+    assert_eq!(reread.layers, [Layer::Zlib]);
+    let scores: Vec<_> = reread
+        .values
+        .iter()
+        .map(|v| match v {
+            Value::Object { data, .. } => data.elements[1].value.clone(),
+            _ => Value::Undefined,
+        })
+        .collect();
+    assert_eq!(scores, [Value::Integer(10), Value::Integer(20)]);
+
+    // Raw deflate has no header, it is only detected by the contents decoding
+    let deflated = Layer::Deflate
+        .compress(&stream)
+        .expect("Failed to compress");
+    let nested = NestedAmf::decode(&deflated, &decoder).expect("Failed to find nested values");
+    assert_eq!(nested.layers, [Layer::Deflate]);
+    assert_eq!(nested.encode(&encoder), Ok(deflated));
+
+    // Plain streams have no layers
+    let nested = NestedAmf::decode(&stream, &decoder).expect("Failed to find nested values");
+    assert!(nested.layers.is_empty());
+    assert_eq!(nested.encode(&encoder), Ok(stream));
+}
+
+#[cfg(feature = "lzma")]
+#[test]
+pub fn test_nested_byte_array_lzma_amf0() {
+    use flash_lso::amf3::read::AMF3Decoder;
+    use flash_lso::amf3::write::AMF3Encoder;
+    use flash_lso::nested::{Layer, NestedAmf};
+    use flash_lso::types::AMFVersion;
+
+    // `objectEncoding = 0` then `writeObject("save")` and `compress(CompressionAlgorithm.LZMA)`
+    let stream = [0x02, 0x00, 0x04, b's', b'a', b'v', b'e'];
+    let compressed = Layer::Lzma.compress(&stream).expect("Failed to compress");
+    assert_eq!(&compressed[5..13], &(stream.len() as u64).to_le_bytes());
+
+    let nested = NestedAmf::decode(&compressed, &AMF3Decoder::default())
+        .expect("Failed to find nested values");
+    assert_eq!(nested.layers, [Layer::Lzma]);
+    assert_eq!(nested.version, AMFVersion::AMF0);
+    assert_eq!(nested.values, [Value::String("save".to_string())]);
+
+    let encoded = nested
+        .encode(&AMF3Encoder::default())
+        .expect("Failed to encode");
+    assert_eq!(Layer::Lzma.decompress(&encoded), Some(stream.to_vec()));
+}
+
+#[test]
+pub fn test_text_encoded_amf() {
+    use flash_lso::amf3::read::AMF3Decoder;
+    use flash_lso::amf3::write::AMF3Encoder;
+    use flash_lso::nested::{TextEncodedAmf, TextEncoding};
+    use flash_lso::types::AMFVersion;
+
+    let data = include_bytes!("amf/LearnToFly3.profileData.saveString.amf");
+    let (_, expected) = AMF3Decoder::default()
+        .parse_single_element(data)
+        .expect("Failed to parse");
+
+    // As written by `mx.utils.Base64Encoder`, which wraps lines at 76 characters
+    let base64 = TextEncoding::Base64 {
+        url_safe: false,
+        padded: true,
+        line_length: Some(76),
+        crlf: false,
+        trailing_newline: false,
+    };
+    let text = base64.encode(data);
+    assert!(text.lines().all(|line| line.len() <= 76));
+    assert_eq!(TextEncoding::detect(&text), Some((base64, data.to_vec())));
+
+    let decoder = AMF3Decoder::default();
+    let encoder = AMF3Encoder::default();
+    let nested = TextEncodedAmf::decode_value(&Value::String(text.clone()), &decoder)
+        .expect("Failed to find nested values");
+    assert_eq!(nested.encoding, base64);
+    assert_eq!(nested.nested.version, AMFVersion::AMF3);
+    assert_eq!(nested.nested.values, [expected]);
+    assert_eq!(nested.to_value(&encoder), Ok(Value::String(text)));
+
+    let hex = TextEncoding::Hex { uppercase: true };
+    let text = hex.encode(&[0x06, 0x05, b'a', b'b']);
+    assert_eq!(text, "06056162");
+    let nested = TextEncodedAmf::decode(&text, &decoder).expect("Failed to find nested values");
+    assert_eq!(nested.nested.values, [Value::String("ab".to_string())]);
+
+    assert_eq!(TextEncoding::detect("not encoded!"), None);
+    assert_eq!(TextEncodedAmf::decode("", &decoder), None);
+
+    // Only text that is written back unchanged is detected
+    let (encoding, bytes) = TextEncoding::detect("QUJD\r\nRA==\r\n").expect("Not detected");
+    assert_eq!(bytes, b"ABCD");
+    assert_eq!(encoding.encode(&bytes), "QUJD\r\nRA==\r\n");
+    assert_eq!(TextEncoding::detect(" QUJD"), None);
+    assert_eq!(TextEncoding::detect("QUJD\n\n"), None);
+    assert_eq!(TextEncoding::detect("QU\nJDRA=="), None);
+    assert_eq!(TextEncoding::detect(" 0605"), None);
+    // Hex in mixed case can't be written back as it was, but is valid base64
+    let (encoding, bytes) = TextEncoding::detect("0a0B").expect("Not detected");
+    assert_eq!(encoding.encode(&bytes), "0a0B");
+}
+
+#[cfg(feature = "zlib")]
+#[test]
+pub fn test_text_encoded_amf_compressed() {
+    use flash_lso::amf3::read::AMF3Decoder;
+    use flash_lso::amf3::write::AMF3Encoder;
+    use flash_lso::nested::{Layer, TextEncodedAmf, TextEncoding};
+
+    let data = include_bytes!("amf/LearnToFly3.profileData.saveString.amf");
+    let compressed = Layer::Zlib.compress(data).expect("Failed to compress");
+    let url_safe = TextEncoding::Base64 {
+        url_safe: true,
+        padded: false,
+        line_length: None,
+        crlf: false,
+        trailing_newline: false,
+    };
+    let text = url_safe.encode(&compressed);
+
+    let mut nested = TextEncodedAmf::decode(&text, &AMF3Decoder::default())
+        .expect("Failed to find nested values");
+    assert_eq!(nested.nested.layers, [Layer::Zlib]);
+    assert_eq!(
+        nested.encode(&AMF3Encoder::default()).as_deref(),
+        Ok(text.as_str())
+    );
+
+    // Edits are written back in the same form
+    nested.nested.values[0] = Value::String("edited".to_string());
+    let edited = nested
+        .encode(&AMF3Encoder::default())
+        .expect("Failed to encode");
+    let reread = TextEncodedAmf::decode(&edited, &AMF3Decoder::default())
+        .expect("Failed to find nested values");
+    assert_eq!(reread.nested.layers, [Layer::Zlib]);
+    assert_eq!(reread.nested.values, [Value::String("edited".to_string())]);
+    assert!(!edited.contains(['+', '/', '=']));
+}
+
+#[test]
+pub fn test_value_stream_reset_policy() {
+    use flash_lso::stream::{ResetPolicy, ValueStreamReader, ValueStreamWriter};
+    use flash_lso::types::AMFVersion;
+
+    let values = [
+        Value::String("level".to_string()),
+        Value::String("level".to_string()),
+        Value::Integer(3),
+    ];
+
+    let write = |policy| {
+        let mut writer = ValueStreamWriter::new(Vec::new(), AMFVersion::AMF3, policy);
+        for value in &values {
+            writer.write_value(value).expect("Failed to write");
+        }
+        writer.into_inner()
+    };
+    let per_value = write(ResetPolicy::PerValue);
+    let per_stream = write(ResetPolicy::PerStream);
+
+    // The second string is written as a reference when the tables are shared
+    assert_eq!(
+        &per_value[7..14],
+        &[0x06, 0x0B, b'l', b'e', b'v', b'e', b'l']
+    );
+    assert_eq!(&per_stream[7..9], &[0x06, 0x00]);
+
+    for (bytes, policy) in [
+        (&per_value, ResetPolicy::PerValue),
+        (&per_stream, ResetPolicy::PerStream),
+    ] {
+        let read: Result<Vec<_>, _> =
+            ValueStreamReader::new(bytes, AMFVersion::AMF3, policy).collect();
+        assert_eq!(read.expect("Failed to read"), values);
+    }
+
+    // Reading a shared stream with reset tables fails on the reference, and stops
+    let mut reader = ValueStreamReader::new(&per_stream, AMFVersion::AMF3, ResetPolicy::PerValue);
+    assert_eq!(reader.next(), Some(Ok(values[0].clone())));
+    assert!(matches!(reader.next(), Some(Err(_))));
+    assert_eq!(reader.next(), None);
+
+    // A manual reset starts new tables part way through a shared stream
+    let mut writer = ValueStreamWriter::new(Vec::new(), AMFVersion::AMF3, ResetPolicy::PerStream);
+    writer.write_value(&values[0]).expect("Failed to write");
+    writer.reset();
+    writer.write_value(&values[1]).expect("Failed to write");
+    assert_eq!(writer.get_ref(), &per_value[..14]);
+
+    let mut writer = ValueStreamWriter::new(Vec::new(), AMFVersion::AMF0, ResetPolicy::PerValue);
+    for value in &values[..2] {
+        writer.write_value(value).expect("Failed to write");
+    }
+    let bytes = writer.into_inner();
+    let mut reader = ValueStreamReader::new(&bytes, AMFVersion::AMF0, ResetPolicy::PerStream);
+    assert_eq!(reader.next(), Some(Ok(values[0].clone())));
+    assert_eq!(reader.remaining(), &bytes[8..]);
+    assert_eq!(reader.next(), Some(Ok(values[1].clone())));
+    assert_eq!(reader.next(), None);
+}
+
+#[test]
+pub fn test_reader_reuse() {
+    let demo = include_bytes!("sol/AS3-Demo.sol");
+    let xml = include_bytes!("sol/AS3-XML-Demo.sol");
+
+    // State from the first parse must not leak into the second
+    let mut reader = Reader::default();
+    reader.parse(demo).expect("Failed to parse");
+    let reused = reader.parse(xml).expect("Failed to parse");
+    assert_eq!(
+        reused,
+        Reader::default().parse(xml).expect("Failed to parse")
+    );
+
+    // Compared by debug output, as the demo contains NaN
+    let again = reader.parse(demo).expect("Failed to parse");
+    let fresh = Reader::default().parse(demo).expect("Failed to parse");
+    assert_eq!(format!("{again:?}"), format!("{fresh:?}"));
+}
+
+#[cfg(feature = "flex")]
+#[test]
+pub fn test_decoder_config() {
+    use flash_lso::amf3::read::DecoderConfig;
+    use flash_lso::extra::flex;
+
+    let mut config = DecoderConfig::default();
+    flex::read::register_config_decoders(&mut config);
+    assert!(config.has_custom_decoder("flex.messaging.io.ArrayCollection"));
+
+    // Clones share the registered decoders, but modifying one does not affect the others
+    let mut modified = config.clone();
+    modified.register_custom_decoder::<NoopDecoder>("Test");
+    assert!(modified.has_custom_decoder("Test"));
+    assert!(!config.has_custom_decoder("Test"));
+
+    let data = include_bytes!("sol/oppDetailPrefs.sol");
+    let mut reader = Reader::new(config.clone());
+    let first = reader.parse_incomplete(data).expect("Failed to parse").1;
+    let second = reader.parse_incomplete(data).expect("Failed to parse").1;
+    assert_eq!(first, second);
+    assert_eq!(
+        Reader::new(config)
+            .parse_incomplete(data)
+            .expect("Failed to parse")
+            .1,
+        first
+    );
+}
+
+#[test]
+pub fn test_config_shared_by_amf0_bodies() {
+    use flash_lso::types::{Attribute, ClassDefinition, CustomObject};
+
+    // An AMF0 body with "b" = externalizable "X" with no data, through the AVM+ switch marker
+    let data = [
+        0x00, 0xBF, 0x00, 0x00, 0x00, 0x1A, 0x54, 0x43, 0x53, 0x4F, 0x00, 0x04, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x01, 0x41, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x62, 0x11, 0x0A, 0x07, 0x03,
+        0x58, 0x00,
+    ];
+    assert!(Reader::default().parse(&data).is_err());
+
+    // Decoders registered after the reader is created are used for embedded AMF3 values
+    let mut reader = Reader::default();
+    reader
+        .amf3_decoder
+        .config_mut()
+        .register_custom_decoder::<NoopDecoder>("X");
+    let lso = reader.parse(&data).expect("Failed to parse");
+    assert_eq!(
+        lso.body[0].value,
+        Value::AMF3(Box::new(Value::Custom(CustomObject {
+            custom_elements: Vec::new(),
+            elements: Vec::new(),
+            class_definition: Some(ClassDefinition {
+                attributes: Attribute::External.into(),
+                ..ClassDefinition::default_with_name("X".to_string())
+            }),
+        })))
+    );
+    assert_eq!(
+        reader.parse_borrowed(&data).map(|lso| lso.body.len()),
+        Ok(1)
+    );
+}
+
+#[test]
+pub fn test_decode_limits() {
+    use flash_lso::types::DecodeLimits;
+
+    fn limit_error(data: &[u8], limits: DecodeLimits) -> Option<&'static str> {
+        let mut reader = Reader::default();
+        reader.set_limits(limits);
+        let owned = match reader.parse(data) {
+            Err(nom::Err::Failure(Error::LimitExceeded(limit))) => Some(limit),
+            _ => None,
+        };
+        let borrowed = match reader.parse_borrowed(data) {
+            Err(nom::Err::Failure(Error::LimitExceeded(limit))) => Some(limit),
+            _ => None,
+        };
+        assert_eq!(owned, borrowed);
+        owned
+    }
+
+    for data in [
+        include_bytes!("sol/AS2-Array-Demo.sol").as_slice(),
+        include_bytes!("sol/AS3-Array-Demo.sol").as_slice(),
+    ] {
+        assert_eq!(limit_error(data, DecodeLimits::default()), None);
+        assert_eq!(
+            limit_error(
+                data,
+                DecodeLimits {
+                    max_depth: 1,
+                    ..DecodeLimits::default()
+                }
+            ),
+            Some("max_depth")
+        );
+        assert_eq!(
+            limit_error(
+                data,
+                DecodeLimits {
+                    max_collection_length: 1,
+                    ..DecodeLimits::default()
+                }
+            ),
+            Some("max_collection_length")
+        );
+    }
+
+    assert_eq!(
+        limit_error(
+            include_bytes!("sol/AS3-Demo.sol"),
+            DecodeLimits {
+                max_references: 4,
+                ..DecodeLimits::default()
+            }
+        ),
+        Some("max_references")
+    );
+}
+
+#[test]
+pub fn test_shared_config_across_threads() {
+    use flash_lso::amf3::read::{AMF3Decoder, DecoderConfig};
+    use flash_lso::amf3::write::AMF3Encoder;
+    use flash_lso::write::Writer;
+
+    fn send_sync<T: Send + Sync>() {}
+    send_sync::<DecoderConfig>();
+    send_sync::<AMF3Decoder>();
+    send_sync::<Reader>();
+    send_sync::<AMF3Encoder>();
+    send_sync::<Writer>();
+
+    let mut config = DecoderConfig::default();
+    config.register_custom_decoder::<NoopDecoder>("Test");
+
+    let files: [&[u8]; 3] = [
+        include_bytes!("sol/AS3-XML-Demo.sol"),
+        include_bytes!("sol/AS2-Demo.sol"),
+        include_bytes!("sol/AS3-XMLDoc-Demo.sol"),
+    ];
+    let parsed: Vec<_> = std::thread::scope(|s| {
+        let handles: Vec<_> = files
+            .iter()
+            .map(|data| {
+                let config = &config;
+                s.spawn(move || {
+                    let mut reader = Reader::new(config.clone());
+                    reader.parse(data).expect("Failed to parse")
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().expect("Thread panicked"))
+            .collect()
+    });
+
+    for (lso, data) in parsed.iter().zip(files) {
+        assert_eq!(
+            lso,
+            &Reader::default().parse(data).expect("Failed to parse")
+        );
+    }
+}
+
+#[test]
+pub fn test_encode_options() {
+    use flash_lso::errors::{EncodeError, ValidationError};
+    use flash_lso::stream::{ResetPolicy, ValueStreamReader, ValueStreamWriter};
+    use flash_lso::types::{
+        AMFVersion, Attribute, ClassDefinition, EcmaArray, Lso, ObjectId, ObjectValue,
+    };
+    use flash_lso::write::{EcmaArrayLength, EncodeOptions, LongStrings};
+
+    let write = |version, options, values: &[Value]| {
+        let mut writer =
+            ValueStreamWriter::with_options(Vec::new(), version, ResetPolicy::PerStream, options);
+        for value in values {
+            writer.write_value(value)?;
+        }
+        Ok::<_, EncodeError>(writer.into_inner())
+    };
+    let read = |bytes: &[u8]| {
+        ValueStreamReader::new(bytes, AMFVersion::AMF3, ResetPolicy::PerStream)
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Failed to read")
+    };
+
+    let numbers = [
+        Value::Number(3.0),
+        Value::Number(-0.0),
+        Value::Number(268435456.0),
+        Value::Number(0.5),
+    ];
+    let compact =
+        write(AMFVersion::AMF3, EncodeOptions::compact(), &numbers).expect("Failed to write");
+    assert_eq!(&compact[..2], &[0x04, 0x03]);
+    assert_eq!(compact.len(), 2 + 9 * 3);
+    assert_eq!(
+        write(AMFVersion::AMF3, EncodeOptions::default(), &numbers)
+            .expect("Failed to write")
+            .len(),
+        9 * 4
+    );
+    assert_eq!(read(&compact)[0], Value::Integer(3));
+
+    let object = Value::Object {
+        id: ObjectId::INVALID,
+        data: ObjectValue {
+            elements: vec![Element::new("level", Value::String("level".to_string()))],
+            class_definition: Some(ClassDefinition {
+                name: "Save".to_string(),
+                attributes: Attribute::Dynamic.into(),
+                static_properties: Vec::new(),
+            }),
+        },
+    };
+    let objects = [object.clone(), object];
+    let shared =
+        write(AMFVersion::AMF3, EncodeOptions::default(), &objects).expect("Failed to write");
+    let unshared = write(
+        AMFVersion::AMF3,
+        EncodeOptions {
+            string_references: false,
+            trait_references: false,
+            ..EncodeOptions::default()
+        },
+        &objects,
+    )
+    .expect("Failed to write");
+    assert!(unshared.len() > shared.len());
+    assert_eq!(
+        unshared[..unshared.len() / 2],
+        unshared[unshared.len() / 2..]
+    );
+    assert_eq!(read(&shared), read(&unshared));
+
+    let long = [Value::String("a".repeat(70000))];
+    let promoted =
+        write(AMFVersion::AMF0, EncodeOptions::default(), &long).expect("Failed to write");
+    assert_eq!(promoted[0], 0x0C);
+    let rejected = EncodeOptions {
+        long_strings: LongStrings::Reject,
+        ..EncodeOptions::default()
+    };
+    assert!(matches!(
+        write(AMFVersion::AMF0, rejected, &long),
+        Err(EncodeError::Invalid(e)) if e.0[0].error == ValidationError::StringTooLong(70000)
+    ));
+
+    let array = [Value::ECMAArray(EcmaArray {
+        declared_len: 0,
+        ..EcmaArray::new(vec![
+            Element::new("0", Value::Null),
+            Element::new("5", Value::Null),
+            Element::new("05", Value::Null),
+            Element::new("b", Value::Null),
+        ])
+    })];
+    let preserved =
+        write(AMFVersion::AMF0, EncodeOptions::default(), &array).expect("Failed to write");
+    assert_eq!(&preserved[1..5], &[0, 0, 0, 0]);
+    let recomputed = EncodeOptions {
+        ecma_array_length: EcmaArrayLength::Recompute,
+        ..EncodeOptions::default()
+    };
+    let bytes = write(AMFVersion::AMF0, recomputed, &array).expect("Failed to write");
+    assert_eq!(&bytes[1..5], &[0, 0, 0, 6]);
+
+    // Flash Player counts only the indices, so the lengths it wrote are kept
+    let mut lso = Reader::default()
+        .parse(include_bytes!("sol/AS2-ECMAArray-Demo.sol"))
+        .expect("Failed to read");
+    let original = lso.clone();
+    let mut writer = flash_lso::write::Writer {
+        options: recomputed,
+        ..Default::default()
+    };
+    let mut bytes = Vec::new();
+    writer
+        .write_full(&mut bytes, &mut lso)
+        .expect("Failed to write");
+    let lso = Reader::default().parse(&bytes).expect("Failed to read");
+    let lengths = |lso: &Lso| {
+        lso.body
+            .iter()
+            .map(|e| match &e.value {
+                Value::ECMAArray(array) => (e.name.clone(), array.declared_len),
+                value => panic!("Expected an ECMAArray, got {value:?}"),
+            })
+            .collect::<Vec<_>>()
+    };
+    // Except for trailing holes, which are not stored
+    let mut expected = lengths(&original);
+    assert_eq!(expected[0], ("holeyArray".to_string(), 15));
+    expected[0].1 = 0;
+    assert_eq!(lengths(&lso), expected);
+    assert_eq!(
+        lengths(&lso)[2..5],
+        [
+            ("holeyArray2".to_string(), 2),
+            ("mixedArray".to_string(), 2),
+            ("myStringArray".to_string(), 0),
+        ]
+    );
+}
+
+#[test]
+pub fn test_validation() {
+    use flash_lso::amf3::write::AMF3Encoder;
+    use flash_lso::errors::{EncodeError, ValidationError, ValidationIssue};
+    use flash_lso::types::{AMFVersion, Dictionary, Lso, ObjectId, ObjectValue, Reference};
+    use flash_lso::write::Writer;
+
+    let issues = |result: Result<(), flash_lso::errors::ValidationErrors>| {
+        result
+            .expect_err("Expected validation to fail")
+            .0
+            .into_iter()
+            .map(|ValidationIssue { path, error }| (path, error))
+            .collect::<Vec<_>>()
+    };
+
+    let mut lso = Lso::new(
+        vec![
+            Element::new(
+                "save",
+                Value::Object {
+                    id: ObjectId::INVALID,
+                    data: ObjectValue {
+                        elements: vec![
+                            Element::new("coins", Value::Integer(3)),
+                            Element::new(
+                                "items",
+                                Value::StrictArray(
+                                    ObjectId::INVALID,
+                                    vec![Value::Null, Value::Reference(Reference::new(0))],
+                                ),
+                            ),
+                        ],
+                        class_definition: None,
+                    },
+                },
+            ),
+            Element::new("missing", Value::Reference(Reference::new(40))),
+        ],
+        "save:1",
+        AMFVersion::AMF0,
+    );
+    assert_eq!(
+        issues(lso.validate()),
+        vec![
+            (
+                "".to_string(),
+                ValidationError::InvalidHeaderName("save:1".to_string())
+            ),
+            (
+                "missing".to_string(),
+                ValidationError::DanglingReference(40)
+            ),
+        ]
+    );
+
+    let mut bytes = Vec::new();
+    assert!(matches!(
+        Writer::default().write_full(&mut bytes, &mut lso),
+        Err(EncodeError::Invalid(_))
+    ));
+    assert!(bytes.is_empty());
+
+    let value = Value::Dictionary(Dictionary {
+        id: ObjectId(1),
+        entries: vec![
+            (Value::Integer(3), Value::Amf3ObjectReference(ObjectId(1))),
+            (Value::Null, Value::Amf3ObjectReference(ObjectId(2))),
+        ],
+        weak_keys: false,
+    });
+    assert_eq!(
+        issues(value.validate(AMFVersion::AMF3)),
+        vec![(
+            "[1].value".to_string(),
+            ValidationError::DanglingObjectReference(ObjectId(2))
+        ),]
+    );
+    assert!(
+        AMF3Encoder::default()
+            .validate(&Value::Reference(Reference::new(0)))
+            .is_err()
+    );
+}
+
+#[test]
+pub fn test_amf0_avm_plus_switch() {
+    use flash_lso::types::{AMFVersion, Lso, ObjectId, ObjectVector};
+
+    let body = vec![
+        Element::new("score", Value::Integer(300)),
+        Element::new(
+            "names",
+            Value::VectorObject(ObjectVector {
+                id: ObjectId(0),
+                items: vec![Value::String("a".to_string())],
+                type_name: "String".to_string(),
+                fixed_length: false,
+            }),
+        ),
+        Element::new("same_names", Value::Amf3ObjectReference(ObjectId(0))),
+        Element::new("data", Value::ByteArray(vec![1, 2, 3])),
+    ];
+    let mut lso = Lso::new(body.clone(), "switch", AMFVersion::AMF0);
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write");
+
+    let wrapped = Reader::default().parse(&bytes).expect("Failed to parse");
+    assert_eq!(
+        wrapped.body[0].value,
+        Value::AMF3(Box::new(Value::Integer(300)))
+    );
+
+    let mut reader = Reader::default();
+    reader.amf0_decoder.unwrap_amf3 = true;
+    let unwrapped = reader.parse(&bytes).expect("Failed to parse");
+    assert_eq!(unwrapped.body, body);
+}
+
+#[test]
+pub fn test_amf0_xml_round_trip() {
+    use flash_lso::types::{AMFVersion, Lso};
+
+    let string = Value::XML("<a>1</a>".to_string(), true);
+    let document = Value::XML("<b>2</b>".to_string(), false);
+    let mut lso = Lso::new(
+        vec![
+            Element::new("string", string.clone()),
+            Element::new("document", document.clone()),
+        ],
+        "xml",
+        AMFVersion::AMF0,
+    );
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write");
+
+    // Only the AMF0 XML marker is written natively, the other flag through the AVM+ switch marker
+    let read = Reader::default().parse(&bytes).expect("Failed to parse");
+    assert_eq!(read.body[0].value, string);
+    assert_eq!(read.body[1].value, Value::AMF3(Box::new(document.clone())));
+
+    let mut reader = Reader::default();
+    reader.amf0_decoder.unwrap_amf3 = true;
+    let unwrapped = reader.parse(&bytes).expect("Failed to parse");
+    assert_eq!(unwrapped.body, lso.body);
+}
+
+#[test]
+pub fn test_integer_overflow() {
+    use flash_lso::amf3::write::AMF3Encoder;
+    use flash_lso::errors::{EncodeError, ValidationError};
+    use flash_lso::stream::{ResetPolicy, ValueStreamWriter};
+    use flash_lso::types::AMFVersion;
+    use flash_lso::write::{EncodeOptions, IntegerOverflow};
+
+    let score = Value::Integer(300_000_000);
+
+    let mut writer = ValueStreamWriter::new(Vec::new(), AMFVersion::AMF3, ResetPolicy::PerValue);
+    writer.write_value(&score).expect("Failed to write");
+    writer
+        .write_value(&Value::Integer(-(1 << 28)))
+        .expect("Failed to write");
+    let bytes = writer.into_inner();
+    let mut reader = Reader::default();
+    let (rest, value) = reader
+        .amf3_decoder
+        .parse_single_element(&bytes)
+        .expect("Failed to read");
+    assert_eq!(value, Value::Number(300_000_000.0));
+    assert_eq!(
+        reader.amf3_decoder.parse_single_element(rest),
+        Ok((&[][..], Value::Integer(-(1 << 28))))
+    );
+
+    let options = EncodeOptions {
+        integer_overflow: IntegerOverflow::Reject,
+        ..EncodeOptions::default()
+    };
+    assert!(matches!(
+        AMF3Encoder::new(options).validate(&score),
+        Err(e) if e.0[0].error == ValidationError::IntegerOutOfRange(300_000_000)
+    ));
+    let mut writer = ValueStreamWriter::with_options(
+        Vec::new(),
+        AMFVersion::AMF3,
+        ResetPolicy::PerValue,
+        options,
+    );
+    assert!(matches!(
+        writer.write_value(&score),
+        Err(EncodeError::Invalid(_))
+    ));
+}
+
+/// Decodes every externalizable as having no elements
+#[derive(Default)]
+struct NoopDecoder;
+
+impl flash_lso::amf3::custom_encoder::ExternalizableDecoder for NoopDecoder {
+    fn decode<'a>(
+        &self,
+        _ctx: &mut flash_lso::amf3::decode_context::DecodeContext<'a, '_>,
+    ) -> Result<Vec<Element>, Error<'a>> {
+        Ok(Vec::new())
+    }
+}
+
+#[test]
+pub fn test_string_encodings() {
+    use flash_lso::encoding::StringEncoding;
+    use flash_lso::errors::{EncodeError, ValidationError, ValidationErrors, ValidationIssue};
+    use flash_lso::types::{AMFVersion, Lso};
+    use flash_lso::write::{EncodeOptions, Writer};
+
+    let write = |lso: &mut Lso, encoding| {
+        let mut writer = Writer {
+            options: EncodeOptions {
+                string_encoding: encoding,
+                ..EncodeOptions::default()
+            },
+            ..Writer::default()
+        };
+        let mut bytes = Vec::new();
+        writer.write_full(&mut bytes, lso).map(|_| bytes)
+    };
+    let read = |bytes: &[u8], encoding| {
+        let mut reader = Reader::default();
+        reader.set_string_encoding(encoding);
+        reader.parse(bytes).expect("Failed to parse lso")
+    };
+
+    for version in [AMFVersion::AMF0, AMFVersion::AMF3] {
+        let mut lso = Lso::new(
+            vec![Element::new("name", Value::String("Z".to_string()))],
+            "save",
+            version,
+        );
+        let mut bytes = write(&mut lso, StringEncoding::Utf8).expect("Failed to write");
+        let at = bytes
+            .iter()
+            .position(|&b| b == b'Z')
+            .expect("Missing string");
+        bytes[at] = 0xE9;
+
+        assert!(Reader::default().parse(&bytes).is_err());
+
+        let mut raw = read(&bytes, StringEncoding::RawBytes);
+        assert_eq!(raw.body[0].value, Value::String("\u{E9}".to_string()));
+        assert_eq!(
+            write(&mut raw, StringEncoding::RawBytes).expect("Failed to write"),
+            bytes
+        );
+
+        let lossy = read(&bytes, StringEncoding::Utf8Lossy);
+        assert_eq!(lossy.body[0].value, Value::String("\u{FFFD}".to_string()));
+    }
+
+    let mut lso = Lso::new(
+        vec![Element::new("name", Value::String("\u{3042}".to_string()))],
+        "save",
+        AMFVersion::AMF0,
+    );
+    assert_eq!(
+        write(&mut lso, StringEncoding::RawBytes),
+        Err(EncodeError::Invalid(ValidationErrors(vec![
+            ValidationIssue {
+                path: "name".to_string(),
+                error: ValidationError::UnmappableString("\u{3042}".to_string()),
+            }
+        ])))
+    );
+
+    #[cfg(feature = "encoding")]
+    {
+        let shift_jis = StringEncoding::Legacy(flash_lso::encoding::encoding_rs::SHIFT_JIS);
+        let bytes = write(&mut lso, shift_jis).expect("Failed to write");
+        assert!(bytes.windows(2).any(|w| w == [0x82, 0xA0]));
+        assert_eq!(read(&bytes, shift_jis), lso);
+    }
+}
+
+#[test]
+pub fn test_lazy_lso() {
+    use flash_lso::types::{AMFVersion, Lso};
+    use flash_lso::write::Writer;
+
+    for data in [
+        &include_bytes!("sol/AS2-Demo.sol")[..],
+        &include_bytes!("sol/AS3-Demo.sol")[..],
+        &include_bytes!("sol/CoC_8.sol")[..],
+    ] {
+        let lso = Reader::default().parse(data).expect("Failed to parse lso");
+        let mut lazy = Reader::default()
+            .parse_lazy(data)
+            .expect("Failed to parse lazy lso");
+
+        assert_eq!(lazy.header, lso.header);
+        assert!(lazy.names().eq(lso.body.iter().map(|e| e.name.as_str())));
+
+        // Later elements are decoded with the reference tables left by the ones before them
+        // Compared through Debug, as NaN is not equal to itself
+        for (index, element) in lso.body.iter().enumerate().rev() {
+            let decoded = lazy.element(index).expect("Missing element");
+            assert_eq!(
+                format!("{:?}", decoded.expect("Failed to decode")),
+                format!("{element:?}")
+            );
+        }
+        assert_eq!(
+            format!("{:?}", lazy.to_lso().expect("Failed to decode")),
+            format!("{lso:?}")
+        );
+
+        let mut bytes = Vec::new();
+        lazy.write(&mut Writer::default(), &mut bytes)
+            .expect("Failed to write");
+        assert_eq!(bytes, data);
+
+        let last = lso.body.last().expect("Empty body").name.clone();
+        assert!(lazy.replace(&last, Value::Number(1.0)));
+        assert!(!lazy.replace("missing", Value::Null));
+        assert_eq!(lazy.get(&last), Some(Ok(Value::Number(1.0))));
+
+        let mut bytes = Vec::new();
+        lazy.write(&mut Writer::default(), &mut bytes)
+            .expect("Failed to write");
+        let mut expected = lso.clone();
+        expected.body.last_mut().expect("Empty body").value = Value::Number(1.0);
+        let written = Reader::default().parse(&bytes).expect("Failed to parse");
+        assert_eq!(
+            format!("{:?}", written.body),
+            format!("{:?}", expected.body)
+        );
+    }
+
+    // Of elements with the same name, the last one is looked up and replaced
+    let mut lso = Lso::new(
+        vec![
+            Element::new("a", Value::Number(1.0)),
+            Element::new("b", Value::Null),
+            Element::new("a", Value::Number(2.0)),
+        ],
+        "test",
+        AMFVersion::AMF0,
+    );
+    let mut data = Vec::new();
+    Writer::default()
+        .write_full(&mut data, &mut lso)
+        .expect("Failed to write");
+    let mut lazy = Reader::default()
+        .parse_lazy(&data)
+        .expect("Failed to parse lazy lso");
+    assert_eq!(lazy.get("a"), Some(Ok(Value::Number(2.0))));
+    assert!(lazy.replace("a", Value::Number(3.0)));
+    assert_eq!(
+        lazy.element(0).map(|e| e.map(|e| e.value)),
+        Some(Ok(Value::Number(1.0)))
+    );
+    assert_eq!(
+        lazy.element(2).map(|e| e.map(|e| e.value)),
+        Some(Ok(Value::Number(3.0)))
+    );
+}
+
+#[test]
+pub fn test_parse_borrowed() {
+    use flash_lso::types::ValueRef;
+    use std::borrow::Cow;
+
+    let reader = || {
+        #[cfg(feature = "flex")]
+        {
+            let mut config = flash_lso::amf3::read::DecoderConfig::default();
+            flash_lso::extra::flex::read::register_config_decoders(&mut config);
+            Reader::new(config)
+        }
+        #[cfg(not(feature = "flex"))]
+        Reader::default()
+    };
+
+    let mut paths = std::fs::read_dir("tests/sol")
+        .expect("Missing test files")
+        .map(|entry| entry.expect("Failed to list test files").path())
+        .filter(|path| path.extension().is_some_and(|e| e == "sol"))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    for path in paths {
+        let data = std::fs::read(&path).expect("Failed to read test file");
+        let owned = reader().parse(&data);
+        let borrowed = reader().parse_borrowed(&data);
+
+        match (owned, borrowed) {
+            // Compared through Debug, as NaN is not equal to itself
+            (Ok(owned), Ok(borrowed)) => assert_eq!(
+                format!("{:?}", borrowed.into_owned()),
+                format!("{owned:?}"),
+                "{path:?}"
+            ),
+            (Err(_), Err(_)) => {}
+            (owned, borrowed) => panic!("{path:?}: {owned:?} != {borrowed:?}"),
+        }
+    }
+
+    let data = include_bytes!("sol/AS3-Demo.sol");
+    let lso = Reader::default()
+        .parse_borrowed(data)
+        .expect("Failed to parse");
+    assert!(lso.body.iter().all(|e| matches!(e.name, Cow::Borrowed(_))));
+    assert!(
+        lso.body
+            .iter()
+            .any(|e| matches!(e.value, ValueRef::String(Cow::Borrowed(_))))
+    );
+}
+
+#[test]
+pub fn test_named_compound_values() {
+    let data = include_bytes!("sol/AS2-ECMAArray-Demo.sol");
+    let lso = Reader::default().parse(data).expect("Failed to read");
+    let mixed = lso
+        .body
+        .iter()
+        .find(|e| e.name == "mixedArray")
+        .expect("Missing mixedArray");
+    let Value::ECMAArray(array) = &mixed.value else {
+        panic!("Expected an ECMAArray, got {:?}", mixed.value);
+    };
+    assert_eq!(array.declared_len, 2);
+    assert_eq!(array.get_index(0), None);
+    assert_eq!(
+        array.get("propertyA"),
+        Some(&Value::String("aaaa".to_string()))
+    );
+    assert_eq!(array.get("missing"), None);
+
+    let data = include_bytes!("sol/AS3-VectorObject-Demo.sol");
+    let lso = Reader::default().parse(data).expect("Failed to read");
+    let Value::VectorObject(vector) = &lso.body[0].value else {
+        panic!("Expected a VectorObject, got {:?}", lso.body[0].value);
+    };
+    assert_eq!(vector.get(1), Some(&Value::Integer(3)));
+    assert!(!vector.fixed_length);
+
+    let data = include_bytes!("sol/AS3-Dictionary-Demo.sol");
+    let lso = Reader::default().parse(data).expect("Failed to read");
+    let Value::Dictionary(dictionary) = &lso.body[0].value else {
+        panic!("Expected a Dictionary, got {:?}", lso.body[0].value);
+    };
+    assert!(dictionary.get(&Value::String("key1".to_string())).is_some());
+    assert_eq!(dictionary.get(&Value::Null), None);
+
+    // The fixtures written with the positional variants are still read
+    #[cfg(feature = "serde")]
+    for name in [
+        "AS2-ECMAArray-Demo",
+        "AS3-Dictionary-Demo",
+        "AS3-VectorObject-Demo",
+    ] {
+        let json = std::fs::read_to_string(format!("tests/sol/{name}.json"))
+            .expect("Failed to read fixture");
+        let lso: flash_lso::types::Lso =
+            serde_json::from_str(&json).expect("Failed to deserialize");
+        assert_eq!(
+            serde_json::to_string(&lso).expect("Failed to serialize"),
+            json.trim_end()
+        );
+    }
+}
+
+#[test]
+pub fn test_amf_macro() {
+    use flash_lso::amf;
+    use flash_lso::errors::ConvertError;
+    use flash_lso::types::{AMFVersion, ClassDefinition, Lso, ObjectId, ObjectValue};
+    use flash_lso::write::Writer;
+    use std::collections::BTreeMap;
+
+    let name = "Bob".to_string();
+    let value = amf!({
+        "name": name,
+        "level": 3,
+        "health": num(100),
+        "ratio": -0.5,
+        "tags": ["a", "b", int(7)],
+        "created": date(1000.0, 60),
+        "save": bytes([1, 2, 3]),
+        "position": class("com.game.Point") { "x": 1.5, "y": undefined },
+        ("empty"): {},
+        "guild": null
+    });
+
+    let expected = Value::Object {
+        id: ObjectId::INVALID,
+        data: ObjectValue {
+            elements: vec![
+                Element::new("name", Value::String("Bob".to_string())),
+                Element::new("level", Value::Integer(3)),
+                Element::new("health", Value::Number(100.0)),
+                Element::new("ratio", Value::Number(-0.5)),
+                Element::new(
+                    "tags",
+                    Value::StrictArray(
+                        ObjectId::INVALID,
+                        vec![
+                            Value::String("a".to_string()),
+                            Value::String("b".to_string()),
+                            Value::Integer(7),
+                        ],
+                    ),
+                ),
+                Element::new("created", Value::Date(1000.0, Some(60))),
+                Element::new("save", Value::ByteArray(vec![1, 2, 3])),
+                Element::new(
+                    "position",
+                    Value::Object {
+                        id: ObjectId::INVALID,
+                        data: ObjectValue {
+                            elements: vec![
+                                Element::new("x", Value::Number(1.5)),
+                                Element::new("y", Value::Undefined),
+                            ],
+                            class_definition: Some(ClassDefinition {
+                                static_properties: vec!["x".to_string(), "y".to_string()],
+                                ..ClassDefinition::default_with_name("com.game.Point".to_string())
+                            }),
+                        },
+                    },
+                ),
+                Element::new("empty", Value::object(Vec::new())),
+                Element::new("guild", Value::Null),
+            ],
+            class_definition: None,
+        },
+    };
+    assert_eq!(value, expected);
+    assert_eq!(amf!([]), Value::StrictArray(ObjectId::INVALID, Vec::new()));
+
+    // The built values can be written and read back
+    let mut lso = Lso::new(
+        vec![Element::new("player", value.clone())],
+        "test",
+        AMFVersion::AMF3,
+    );
+    let mut bytes = Vec::new();
+    Writer::default()
+        .write_full(&mut bytes, &mut lso)
+        .expect("Failed to write");
+    let read = Reader::default().parse(&bytes).expect("Failed to read");
+    let object = read.body[0].value.as_object().expect("Not an object");
+    assert_eq!(
+        object.elements.iter().map(|e| &e.name).collect::<Vec<_>>(),
+        [
+            "name", "level", "health", "ratio", "tags", "created", "save", "position", "empty",
+            "guild"
+        ]
+    );
+    assert_eq!(object.get("save"), Some(&Value::ByteArray(vec![1, 2, 3])));
+    assert_eq!(
+        object
+            .get("position")
+            .and_then(Value::as_object)
+            .and_then(|p| p.get("x")),
+        Some(&Value::Number(1.5))
+    );
+
+    // Accessors
+    let object = value.as_object().expect("Not an object");
+    assert_eq!(object.get("name").and_then(Value::as_str), Some("Bob"));
+    assert_eq!(object.get("level").and_then(Value::as_f64), Some(3.0));
+    assert_eq!(object.get("level").and_then(Value::as_i32), Some(3));
+    assert_eq!(object.get("health").and_then(Value::as_i32), None);
+    assert_eq!(
+        object.get("tags").and_then(Value::as_array).map(<[_]>::len),
+        Some(3)
+    );
+    assert!(object.get("guild").is_some_and(Value::is_null));
+    assert_eq!(Value::Bool(true).as_bool(), Some(true));
+    assert_eq!(value.as_str(), None);
+
+    // Conversions
+    assert_eq!(Value::from(2.5), Value::Number(2.5));
+    assert_eq!(Value::from(2u8), Value::Integer(2));
+    assert_eq!(Value::from(Some("a")), Value::String("a".to_string()));
+    assert_eq!(Value::from(None::<bool>), Value::Null);
+    assert_eq!(
+        Value::from(vec![1, 2]),
+        Value::StrictArray(
+            ObjectId::INVALID,
+            vec![Value::Integer(1), Value::Integer(2)]
+        )
+    );
+    let map = BTreeMap::from([("a", 1.0), ("b", 2.0)]);
+    let map_value = Value::from(map);
+    assert_eq!(map_value, amf!({ "a": 1.0, "b": 2.0 }));
+
+    assert_eq!(f64::try_from(&Value::Integer(4)), Ok(4.0));
+    assert_eq!(
+        u8::try_from(&Value::Integer(300)),
+        Err(ConvertError::OutOfRange)
+    );
+    assert_eq!(
+        String::try_from(&Value::Null),
+        Err(ConvertError::UnexpectedType("string"))
+    );
+    assert_eq!(
+        Vec::<String>::try_from(&amf!(["x", "y"])),
+        Ok(vec!["x".to_string(), "y".to_string()])
+    );
+    assert_eq!(
+        BTreeMap::<String, f64>::try_from(&map_value),
+        Ok(BTreeMap::from([
+            ("a".to_string(), 1.0),
+            ("b".to_string(), 2.0)
+        ]))
+    );
+}
+
+#[test]
+pub fn test_element_map() {
+    use flash_lso::amf;
+    use flash_lso::types::{AMFVersion, DuplicateKeys, EcmaArray, Entry, Lso};
+    use flash_lso::write::Writer;
+
+    let mut lso = Lso::new(
+        vec![
+            Element::new("a", Value::Number(1.0)),
+            Element::new("b", Value::Number(2.0)),
+            Element::new("a", Value::Number(3.0)),
+        ],
+        "test",
+        AMFVersion::AMF0,
+    );
+
+    // Duplicates are last wins
+    assert_eq!(lso.get("a"), Some(&Value::Number(3.0)));
+    assert!(lso.contains_key("b"));
+    assert!(!lso.contains_key("c"));
+
+    assert_eq!(lso.insert("b", Value::Bool(true)), Some(Value::Number(2.0)));
+    assert_eq!(lso.insert("c", Value::Null), None);
+    assert_eq!(lso.keys().collect::<Vec<_>>(), ["a", "b", "a", "c"]);
+
+    if let Some(Value::Number(n)) = lso.get_mut("a") {
+        *n += 1.0;
+    }
+    assert_eq!(lso.get("a"), Some(&Value::Number(4.0)));
+
+    assert_eq!(lso.remove("a"), Some(Value::Number(4.0)));
+    assert_eq!(lso.remove("a"), None);
+    assert_eq!(
+        lso.iter().collect::<Vec<_>>(),
+        [("b", &Value::Bool(true)), ("c", &Value::Null)]
+    );
+
+    *lso.entry("d").or_insert(Value::Integer(0)) = Value::Integer(5);
+    lso.entry("d").and_modify(|v| *v = Value::Integer(6));
+    assert!(matches!(lso.entry("b"), Entry::Occupied(e) if e.get() == &Value::Bool(true)));
+    assert!(matches!(lso.entry("e"), Entry::Vacant(e) if e.key() == "e"));
+    assert_eq!(lso.get("d"), Some(&Value::Integer(6)));
+
+    // The same methods are on objects, ECMA arrays and externalized objects
+    let mut object = amf!({ "x": 1.0 });
+    if let Value::Object { data, .. } = &mut object {
+        data.insert("y", Value::Number(2.0));
+    }
+    assert_eq!(object, amf!({ "x": 1.0, "y": 2.0 }));
+
+    let mut array = EcmaArray::new(Vec::new());
+    array.insert("k", Value::Null);
+    assert_eq!(array.get("k"), Some(&Value::Null));
+    assert_eq!(array.declared_len, 0);
+
+    // Duplicate names when decoding
+    for version in [AMFVersion::AMF0, AMFVersion::AMF3] {
+        let body = vec![
+            Element::new("a", amf!({ "x": 1.0, "y": 2.0, "x": 3.0 })),
+            Element::new("b", Value::Null),
+            Element::new("a", Value::Null),
+        ];
+        let mut lso = Lso::new(body, "test", version);
+        let mut bytes = Vec::new();
+        Writer::default()
+            .write_full(&mut bytes, &mut lso)
+            .expect("Failed to write");
+
+        let kept = Reader::default().parse(&bytes).expect("Failed to read");
+        assert_eq!(kept.keys().collect::<Vec<_>>(), ["a", "b", "a"]);
+
+        let mut reader = Reader::default();
+        reader.set_duplicate_keys(DuplicateKeys::LastWins);
+        let last = reader.parse(&bytes).expect("Failed to read");
+        assert_eq!(last.keys().collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(last.get("a"), Some(&Value::Null));
+        let lazy = reader.parse_lazy(&bytes).expect("Failed to read");
+        assert_eq!(lazy.names().collect::<Vec<_>>(), ["a", "b"]);
+
+        // Objects keep the position of the first element and the value of the last
+        let mut reader = Reader::default();
+        reader.set_duplicate_keys(DuplicateKeys::LastWins);
+        let single = Lso::new(
+            vec![Element::new("o", amf!({ "x": 1.0, "y": 2.0, "x": 3.0 }))],
+            "test",
+            version,
+        );
+        let mut bytes = Vec::new();
+        Writer::default()
+            .write_full(&mut bytes, &mut single.clone())
+            .expect("Failed to write");
+        let read = reader.parse(&bytes).expect("Failed to read");
+        let object = read.body[0].value.as_object().expect("Not an object");
+        assert_eq!(
+            object.iter().collect::<Vec<_>>(),
+            [("x", &Value::Number(3.0)), ("y", &Value::Number(2.0))]
+        );
+
+        let mut reader = Reader::default();
+        reader.set_duplicate_keys(DuplicateKeys::Reject);
+        assert_eq!(
+            reader.parse(&bytes).map_err(|e| match e {
+                nom::Err::Failure(e) | nom::Err::Error(e) => e.into_owned(),
+                nom::Err::Incomplete(_) => Error::OutOfBounds,
+            }),
+            Err(Error::DuplicateKey("x".to_string()))
+        );
+    }
+}
+
+#[test]
+pub fn test_convert_versions() {
+    use flash_lso::amf;
+    use flash_lso::convert::{Loss, LossKind};
+    use flash_lso::types::{
+        AMFVersion, Attribute, ClassDefinition, Dictionary, Lso, ObjectId, ObjectValue, Reference,
+    };
+    use flash_lso::write::Writer;
+
+    // Compared by their debug output, as NaN is not equal to itself
+    fn assert_round_trip(lso: &mut Lso) {
+        let mut bytes = Vec::new();
+        Writer::default()
+            .write_full(&mut bytes, lso)
+            .expect("Failed to write");
+        let read = Reader::default().parse(&bytes).expect("Failed to read");
+        assert_eq!(format!("{:?}", read.body), format!("{:?}", lso.body));
+    }
+
+    // Everything but the time zone of dates is kept, and survives being converted back
+    let lso = Reader::default()
+        .parse(include_bytes!("sol/AS2-Demo.sol"))
+        .expect("Failed to read");
+    let mut amf3 = lso.convert_to(AMFVersion::AMF3);
+    assert_eq!(
+        amf3.losses,
+        vec![Loss {
+            path: "myDate".to_string(),
+            kind: LossKind::TimeZone(240),
+        }]
+    );
+    assert_eq!(amf3.lso.header.format_version, AMFVersion::AMF3);
+    assert_round_trip(&mut amf3.lso);
+    let mut amf0 = amf3.lso.convert_to(AMFVersion::AMF0);
+    assert_eq!(amf0.losses, vec![]);
+    assert_round_trip(&mut amf0.lso);
+
+    let lso = Reader::default()
+        .parse(include_bytes!("sol/AS3-Demo.sol"))
+        .expect("Failed to read");
+    let mut amf0 = lso.convert_to(AMFVersion::AMF0);
+    assert_eq!(amf0.losses, vec![]);
+    assert_round_trip(&mut amf0.lso);
+
+    // References, typed objects and AMF0 only types
+    let mut lso = Lso::new(
+        vec![
+            Element::new("point", amf!(class("Point") { "x": num(1), "y": num(2) })),
+            Element::new("same", Value::Reference(Reference::new(0))),
+            Element::new("date", Value::Date(0.0, Some(60))),
+            Element::new("none", Value::Unsupported),
+            Element::new("missing", Value::Reference(Reference::new(9))),
+        ],
+        "convert",
+        AMFVersion::AMF0,
+    );
+    let amf3 = lso.convert_to(AMFVersion::AMF3);
+    assert_eq!(
+        amf3.lso.body[0].value,
+        Value::Object {
+            id: ObjectId(0),
+            data: amf!(class("Point") { "x": num(1), "y": num(2) })
+                .as_object()
+                .cloned()
+                .expect("Expected an object"),
+        }
+    );
+    assert_eq!(
+        amf3.lso.body[1].value,
+        Value::Amf3ObjectReference(ObjectId(0))
+    );
+    assert_eq!(amf3.lso.body[2].value, Value::Date(0.0, None));
+    assert_eq!(amf3.lso.body[3].value, Value::Undefined);
+    assert_eq!(amf3.lso.body[4].value, Value::Null);
+    assert_eq!(
+        amf3.losses
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec![
+            "date: Time zone offset 60 was dropped",
+            "none: Unsupported value was converted to undefined",
+            "missing: Reference could not be mapped and was converted to null",
+        ]
+    );
+
+    // AMF3 only types are embedded, and dynamic classes are reduced to their name
+    let dynamic = ClassDefinition {
+        attributes: Attribute::Dynamic.into(),
+        ..ClassDefinition::default_with_name("Player".to_string())
+    };
+    lso.header.format_version = AMFVersion::AMF3;
+    lso.body = vec![
+        Element::new(
+            "player",
+            Value::Object {
+                id: ObjectId(0),
+                data: ObjectValue {
+                    elements: vec![Element::new("level", Value::Integer(3))],
+                    class_definition: Some(dynamic),
+                },
+            },
+        ),
+        Element::new(
+            "seen",
+            Value::Dictionary(Dictionary {
+                id: ObjectId(1),
+                entries: vec![(Value::Amf3ObjectReference(ObjectId(0)), Value::Bool(true))],
+                weak_keys: false,
+            }),
+        ),
+        Element::new("again", Value::Amf3ObjectReference(ObjectId(0))),
+    ];
+    let amf0 = lso.convert_to(AMFVersion::AMF0);
+    assert_eq!(
+        amf0.lso.body[0].value,
+        Value::Object {
+            id: ObjectId::INVALID,
+            data: ObjectValue {
+                elements: vec![Element::new("level", Value::Number(3.0))],
+                class_definition: Some(ClassDefinition::default_with_name("Player".to_string())),
+            },
+        }
+    );
+    assert_eq!(
+        amf0.lso.body[1].value,
+        Value::AMF3(Box::new(Value::Dictionary(Dictionary {
+            id: ObjectId(0),
+            entries: vec![(Value::Null, Value::Bool(true))],
+            weak_keys: false,
+        })))
+    );
+    assert_eq!(amf0.lso.body[2].value, Value::Reference(Reference::new(0)));
+    assert_eq!(
+        amf0.losses,
+        vec![
+            Loss {
+                path: "player".to_string(),
+                kind: LossKind::ClassTraits("Player".to_string()),
+            },
+            Loss {
+                path: "seen[0].key".to_string(),
+                kind: LossKind::Reference,
+            },
+        ]
+    );
+}
@@ -7,7 +7,8 @@ use clap::{Arg, Command};
 use flash_lso::amf3::read::AMF3Decoder;
 use flash_lso::extra::*;
 use flash_lso::read::Reader;
-use flash_lso::types::Lso;
+use flash_lso::types::{AMFVersion, Lso};
+use flash_lso::write::Writer;
 use std::path::PathBuf;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -19,6 +20,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .subcommand(Command::new("file").arg(Arg::new("INPUT").help("").required(true)))
         .subcommand(Command::new("object-amf3").arg(Arg::new("INPUT").help("").required(true)))
         .subcommand(Command::new("regen").arg(Arg::new("INPUT").help("").required(true)))
+        .subcommand(
+            Command::new("convert")
+                .arg(Arg::new("INPUT").help("").required(true))
+                .arg(Arg::new("OUTPUT").help("").required(true))
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .help("The AMF version of the output")
+                        .value_parser(["amf0", "amf3"])
+                        .required(true),
+                ),
+        )
         .subcommand_required(true)
         .get_matches();
 
@@ -107,6 +120,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             println!("{json}");
         }
+        "convert" => {
+            let output: &String = args.get_one("OUTPUT").unwrap();
+            let version = match args.get_one::<String>("to").unwrap().as_str() {
+                "amf0" => AMFVersion::AMF0,
+                _ => AMFVersion::AMF3,
+            };
+
+            let data = std::fs::read(PathBuf::from(file_name))?;
+            let lso = parse_file(&data).map_err(|e| format!("Couldn't read lso file: {e}"))?;
+            let mut conversion = lso.convert_to(version);
+            for loss in &conversion.losses {
+                eprintln!("{loss}");
+            }
+
+            std::fs::write(output, write_file(&mut conversion.lso)?)?;
+        }
         _ => {
             println!("Unknown command");
         }
@@ -122,3 +151,12 @@ fn parse_file(data: &[u8]) -> Result<Lso, Box<dyn std::error::Error + '_>> {
     let lso = d.parse(data)?;
     Ok(lso)
 }
+
+/// Write an Lso to bytes
+fn write_file(lso: &mut Lso) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut w = Writer::default();
+    flex::write::register_encoders(&mut w.amf3_encoder);
+    let mut data = Vec::new();
+    w.write_full(&mut data, lso)?;
+    Ok(data)
+}
@@ -3,7 +3,7 @@ use yew::prelude::*;
 
 use flash_lso::extra::flex;
 use flash_lso::read::Reader;
-use flash_lso::types::{Attribute, Element, Lso, ObjectValue, Value};
+use flash_lso::types::{Attribute, Element, Lso, ObjectValue, ObjectVector, Value};
 
 use crate::EditableValue;
 use crate::TreeNodePath;
@@ -307,14 +307,16 @@ impl Model {
                     </>
                 }
             }
-            Value::VectorObject(id, elements, name, fixed_length) => {
-                let elements_clone_2 = elements.clone();
+            Value::VectorObject(vector) => {
+                let fixed_length = vector.fixed_length;
+                let name = vector.type_name.clone();
+                let vector_clone_2 = vector.clone();
                 html! {
                     <>
-                    <StringInput onchange={ctx.link().callback(move |new_name| Msg::Edited(Value::VectorObject(id, elements.clone(), new_name, fixed_length)))} value={name.clone()}/>
+                    <StringInput onchange={ctx.link().callback(move |new_name| Msg::Edited(Value::VectorObject(ObjectVector { type_name: new_name, ..vector.clone() })))} value={name}/>
                     <div class="custom-control custom-switch">
                       <input type={"checkbox"} class={"custom-control-input"} id={"customSwitch1"} checked={fixed_length} onclick={ctx.link().callback(move |_| {
-                        Msg::Edited(Value::VectorObject(id, elements_clone_2.clone(), name.clone(), !fixed_length))
+                        Msg::Edited(Value::VectorObject(ObjectVector { fixed_length: !fixed_length, ..vector_clone_2.clone() }))
                       })}/>
                       <label class={"custom-control-label"} for={"customSwitch1"}>{"Fixed Length"}</label>
                     </div>
@@ -738,7 +740,7 @@ fn value_type_name(v: &Value) -> String {
         Value::Object { .. } => "Object".to_string(),
         Value::Null => "Null".to_string(),
         Value::Undefined => "Undefined".to_string(),
-        Value::ECMAArray(_, _, _, _) => "ECMAArray".to_string(),
+        Value::ECMAArray(_) => "ECMAArray".to_string(),
         Value::StrictArray(_, _) => "StrictArray".to_string(),
         Value::Date(_, _) => "Date".to_string(),
         Value::Unsupported => "Unsupported".to_string(),
@@ -751,11 +753,11 @@ fn value_type_name(v: &Value) -> String {
         Value::VectorInt(_, _) => "Vector<Int>".to_string(),
         Value::VectorUInt(_, _) => "Vector<UInt>".to_string(),
         Value::VectorDouble(_, _) => "Vector<Double>".to_string(),
-        Value::VectorObject(_, _, _, _) => "Vector<Object>".to_string(),
+        Value::VectorObject(_) => "Vector<Object>".to_string(),
         Value::Amf3ObjectReference(_) => "Reference".to_string(),
-        Value::Dictionary(_, _, _) => "Dictionary".to_string(),
-        Value::Custom(_, _, cd) => {
-            if let Some(cd) = cd {
+        Value::Dictionary(_) => "Dictionary".to_string(),
+        Value::Custom(custom) => {
+            if let Some(cd) = &custom.class_definition {
                 format!("Custom<{}>", cd.name)
             } else {
                 "Custom<Unknown>".to_string()
@@ -77,7 +77,8 @@ impl Component for TreeNode {
             }
             Msg::CustomElementChange(el) => {
                 match &mut self.value {
-                    Value::Custom(a, _b, _) => {
+                    Value::Custom(custom) => {
+                        let a = &mut custom.custom_elements;
                         let index = a.iter().position(|e| e.name == el.name);
                         if let Some(index) = index {
                             a[index] = el;
@@ -92,7 +93,8 @@ impl Component for TreeNode {
             }
             Msg::CustomElementChangeStandard(el) => {
                 match &mut self.value {
-                    Value::Custom(_a, b, _) => {
+                    Value::Custom(custom) => {
+                        let b = &mut custom.elements;
                         let index = b.iter().position(|e| e.name == el.name);
                         if let Some(index) = index {
                             b[index] = el;
@@ -166,9 +168,13 @@ impl TreeNode {
                 .elements
                 .iter()
                 .any(|e| e.name.contains(&ctx.props().filter)),
-            Value::ECMAArray(_id, e1, e2, _) => {
-                e2.iter().any(|e| e.name.contains(&ctx.props().filter))
-                    || e1
+            Value::ECMAArray(array) => {
+                array
+                    .assoc
+                    .iter()
+                    .any(|e| e.name.contains(&ctx.props().filter))
+                    || array
+                        .dense
                         .iter()
                         .enumerate()
                         .any(|(i, _e)| format!("{i}").contains(&ctx.props().filter))
@@ -177,13 +183,20 @@ impl TreeNode {
                 .iter()
                 .enumerate()
                 .any(|(i, _e)| format!("{i}").contains(&ctx.props().filter)),
-            Value::VectorObject(_, e1, _, _) => e1
+            Value::VectorObject(vector) => vector
+                .items
                 .iter()
                 .enumerate()
                 .any(|(i, _e)| format!("{i}").contains(&ctx.props().filter)),
-            Value::Custom(e1, e2, _) => {
-                e1.iter().any(|e| e.name.contains(&ctx.props().filter))
-                    || e2.iter().any(|e| e.name.contains(&ctx.props().filter))
+            Value::Custom(custom) => {
+                custom
+                    .custom_elements
+                    .iter()
+                    .any(|e| e.name.contains(&ctx.props().filter))
+                    || custom
+                        .elements
+                        .iter()
+                        .any(|e| e.name.contains(&ctx.props().filter))
             }
             _ => false,
         };
@@ -207,11 +220,11 @@ impl TreeNode {
             data,
             Value::Object { .. }
                 | Value::StrictArray(_, _)
-                | Value::ECMAArray(_, _, _, _)
-                | Value::VectorObject(_, _, _, _)
+                | Value::ECMAArray(_)
+                | Value::VectorObject(_)
                 | Value::AMF3(_)
-                | Value::Dictionary(_, _, _)
-                | Value::Custom(_, _, _)
+                | Value::Dictionary(_)
+                | Value::Custom(_)
         )
     }
 
@@ -238,22 +251,22 @@ impl TreeNode {
                     { for x.iter().enumerate().map(|(i, v)| self.view_array_element(ctx, i, v))}
                 </ul>
             },
-            Value::ECMAArray(_id, dense, assoc, _size) => html! {
+            Value::ECMAArray(array) => html! {
                     <ul>
-                       { for dense.iter().enumerate().map(|(i, v)| self.view_array_element(ctx, i, v))}
-                        { for assoc.iter().map(|e| html! {
+                       { for array.dense.iter().enumerate().map(|(i, v)| self.view_array_element(ctx, i, v))}
+                        { for array.assoc.iter().map(|e| html! {
                             <TreeNode filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} name={e.name.clone()} value={e.value.clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
                         })}
                     </ul>
             },
-            Value::VectorObject(_id, children, _name, _fixed_len) => html! {
+            Value::VectorObject(vector) => html! {
                 <ul>
-                   { for children.iter().enumerate().map(|(i, v)| self.view_array_element(ctx, i, v))}
+                   { for vector.items.iter().enumerate().map(|(i, v)| self.view_array_element(ctx, i, v))}
                 </ul>
             },
-            Value::Dictionary(_id, children, _) => html! {
+            Value::Dictionary(dictionary) => html! {
                 <ul>
-                    { for children.iter().map(|(k, v)| html! {
+                    { for dictionary.entries.iter().map(|(k, v)| html! {
                             <>
                             <li>
                                 <TreeNode filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} name="key" value={k.clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
@@ -265,12 +278,12 @@ impl TreeNode {
                         })}
                 </ul>
             },
-            Value::Custom(el, el2, _class_def) => html! {
+            Value::Custom(custom) => html! {
                 <ul>
                     <li>
                         {"Custom elements"}
                         <ul>
-                            { for el.iter().map(|e| html! {
+                            { for custom.custom_elements.iter().map(|e| html! {
                                 <TreeNode element_callback={ctx.link().callback(Msg::CustomElementChange)} filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} name={e.name.clone()} value={e.value.clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
                             })}
                         </ul>
@@ -278,7 +291,7 @@ impl TreeNode {
                     <li>
                         {"Standard elements"}
                         <ul>
-                           { for el2.iter().map(|e| html! {
+                           { for custom.elements.iter().map(|e| html! {
                                 <TreeNode element_callback={ctx.link().callback(Msg::CustomElementChangeStandard)} filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} name={e.name.clone()} value={e.value.clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
                             })}
                         </ul>